@@ -0,0 +1,282 @@
+import builtins
+import os
+from enum import IntEnum
+from typing import Any, Callable, Dict, Iterator, List, Literal, Optional, Tuple, Union
+
+HashLike = Union["hash", str, int]
+
+class ParamTypeKind(IntEnum):
+    BOOL = 1
+    I8 = 2
+    U8 = 3
+    I16 = 4
+    U16 = 5
+    I32 = 6
+    U32 = 7
+    FLOAT = 8
+    HASH = 9
+    STR = 10
+    LIST = 11
+    STRUCT = 12
+
+PARAM_TYPE_BOOL: ParamTypeKind
+PARAM_TYPE_I8: ParamTypeKind
+PARAM_TYPE_U8: ParamTypeKind
+PARAM_TYPE_I16: ParamTypeKind
+PARAM_TYPE_U16: ParamTypeKind
+PARAM_TYPE_I32: ParamTypeKind
+PARAM_TYPE_U32: ParamTypeKind
+PARAM_TYPE_FLOAT: ParamTypeKind
+PARAM_TYPE_HASH: ParamTypeKind
+PARAM_TYPE_STR: ParamTypeKind
+PARAM_TYPE_LIST: ParamTypeKind
+PARAM_TYPE_STRUCT: ParamTypeKind
+
+ParamValue = Union[bool, int, float, str, "hash"]
+# (path, old, new); added params have no old value, and removed params have no new value
+Change = Tuple[Tuple[Union[int, "hash", Tuple["hash", KeyValue]], ...], Optional["param"], Optional["param"]]
+# (path, base, ours, theirs); missing values were removed, or were never there
+Conflict = Tuple[
+    Tuple[Union[int, "hash", Tuple["hash", KeyValue]], ...],
+    Optional["param"],
+    Optional["param"],
+    Optional["param"],
+]
+KindLike = Union[ParamTypeKind, int, str]
+KeyValue = Union[bool, int, str, "hash"]
+# a list element found by the value of one of its fields
+FieldKey = Tuple[HashLike, KeyValue]
+# a '/' separated string, or a sequence of list indices, struct hashes, and field keys
+PathLike = Union[
+    str, "hash", Tuple[Union[int, HashLike, FieldKey], ...], List[Union[int, HashLike, FieldKey]]
+]
+Rounding = Literal["floor", "ceil", "round", "trunc"]
+HashOrder = Union[Literal["traversal", "sorted"], List[HashLike]]
+StringOrder = Literal["traversal", "last", "sorted"]
+Strategy = Literal["ours", "theirs", "base"]
+KeyOrder = Literal["label", "hash"]
+
+class param:
+    def __init__(
+        self, filename: builtins.str, readonly: builtins.bool = False, cache: Optional[Cache] = None
+    ) -> None: ...
+    @staticmethod
+    def open_lazy(filename: builtins.str) -> param: ...
+    @staticmethod
+    def bool(value: builtins.bool) -> param: ...
+    @staticmethod
+    def i8(value: builtins.int) -> param: ...
+    @staticmethod
+    def u8(value: builtins.int) -> param: ...
+    @staticmethod
+    def i16(value: builtins.int) -> param: ...
+    @staticmethod
+    def u16(value: builtins.int) -> param: ...
+    @staticmethod
+    def i32(value: builtins.int) -> param: ...
+    @staticmethod
+    def u32(value: builtins.int) -> param: ...
+    @staticmethod
+    def float(value: builtins.float) -> param: ...
+    @staticmethod
+    def str(value: builtins.str) -> param: ...
+    @staticmethod
+    def hash(value: HashLike) -> param: ...
+    @staticmethod
+    def list(value: List[param]) -> param: ...
+    @staticmethod
+    def struct(value: List[Tuple[HashLike, param]]) -> param: ...
+    @staticmethod
+    def of(kind: KindLike, value: Any) -> param: ...
+    @staticmethod
+    def infer(value: Any) -> param: ...
+    @staticmethod
+    def build(spec: Any, template: Optional[param] = None) -> param: ...
+    def set_bool(self, value: builtins.bool) -> None: ...
+    def set_i8(self, value: builtins.int) -> None: ...
+    def set_u8(self, value: builtins.int) -> None: ...
+    def set_i16(self, value: builtins.int) -> None: ...
+    def set_u16(self, value: builtins.int) -> None: ...
+    def set_i32(self, value: builtins.int) -> None: ...
+    def set_u32(self, value: builtins.int) -> None: ...
+    def set_float(self, value: builtins.float) -> None: ...
+    def set_str(self, value: builtins.str) -> None: ...
+    def set_hash(self, value: HashLike) -> None: ...
+    def set_list(self, value: List[param]) -> None: ...
+    def set_struct(self, value: List[Tuple[HashLike, param]]) -> None: ...
+    def convert(self, kind: KindLike, rounding: Optional[Rounding] = None) -> None: ...
+    def save(
+        self,
+        filename: builtins.str,
+        hash_order: HashOrder = "traversal",
+        string_order: StringOrder = "traversal",
+    ) -> None: ...
+    def roundtrip_check(
+        self,
+        filename: builtins.str,
+        hash_order: HashOrder = "traversal",
+        string_order: StringOrder = "traversal",
+    ) -> Optional[Tuple[builtins.int, builtins.str, Optional[Tuple[Union[builtins.int, _Hash], ...]]]]: ...
+    @staticmethod
+    def hash_table(filename: builtins.str) -> List[_Hash]: ...
+    def to_xml(self, filename: builtins.str, labels: Optional[LabelMap] = None) -> None: ...
+    @staticmethod
+    def from_xml(filename: builtins.str, labels: Optional[LabelMap] = None) -> param: ...
+    def to_yaml(self, template: Optional[param] = None, labels: Optional[LabelMap] = None) -> builtins.str: ...
+    @staticmethod
+    def from_yaml(
+        text: builtins.str, template: Optional[param] = None, labels: Optional[LabelMap] = None
+    ) -> param: ...
+    def to_toml(self, template: Optional[param] = None, labels: Optional[LabelMap] = None) -> builtins.str: ...
+    @staticmethod
+    def from_toml(
+        text: builtins.str, template: Optional[param] = None, labels: Optional[LabelMap] = None
+    ) -> param: ...
+    def clone(self) -> param: ...
+    def freeze(self, in_place: builtins.bool = False) -> param: ...
+    @property
+    def readonly(self) -> builtins.bool: ...
+    def track_changes(self) -> None: ...
+    def changes(self) -> Dict[Tuple[Union[builtins.int, _Hash], ...], Tuple[Optional[param], Optional[param]]]: ...
+    @property
+    def is_dirty(self) -> builtins.bool: ...
+    def on_change(self, callback: Callable[[Tuple[Union[builtins.int, _Hash], ...], Optional[param], Optional[param]], Any]) -> None: ...
+    def transaction(self) -> Transaction: ...
+    def track_history(self, limit: builtins.int = 1000) -> None: ...
+    def undo(self) -> builtins.bool: ...
+    def redo(self) -> builtins.bool: ...
+    def diff(self, other: param, key_fields: Optional[List[HashLike]] = None) -> List[Change]: ...
+    def patch(self, changes: List[Change], labels: Optional[LabelMap] = None) -> None: ...
+    def apply_overlay(
+        self,
+        overlay: Union[Dict[Any, Any], builtins.str],
+        list_key_fields: Optional[List[HashLike]] = None,
+        labels: Optional[LabelMap] = None,
+    ) -> None: ...
+    def clone_with(
+        self,
+        overrides: Dict[PathLike, Any],
+        create: builtins.bool = False,
+        labels: Optional[LabelMap] = None,
+    ) -> param: ...
+    def pformat(
+        self,
+        max_depth: Optional[builtins.int] = None,
+        max_items: Optional[builtins.int] = None,
+        labels: Union[builtins.bool, LabelMap] = True,
+    ) -> builtins.str: ...
+    def _repr_pretty_(self, p: Any, cycle: builtins.bool) -> None: ...
+    def _repr_html_(self) -> builtins.str: ...
+    @property
+    def type(self) -> ParamTypeKind: ...
+    @property
+    def value(self) -> ParamValue: ...
+    @value.setter
+    def value(self, value: Union[ParamValue, HashLike]) -> None: ...
+    def sort(
+        self,
+        key: Union[PathLike, Callable[[param], Any], None] = None,
+        reverse: builtins.bool = False,
+        labels: Optional[LabelMap] = None,
+    ) -> None: ...
+    def sort_keys(self, by: KeyOrder = "label", labels: Optional[LabelMap] = None) -> None: ...
+    def find_by(
+        self,
+        field: HashLike,
+        value: Union[KeyValue, param],
+        labels: Optional[LabelMap] = None,
+    ) -> Optional[builtins.int]: ...
+    def find(
+        self,
+        value: Union[ParamValue, param, None] = None,
+        kind: Optional[KindLike] = None,
+        key: Optional[HashLike] = None,
+        labels: Optional[LabelMap] = None,
+    ) -> List[Tuple[Union[builtins.int, _Hash], ...]]: ...
+    def stats(self) -> Dict[builtins.str, Any]: ...
+    def strings(self) -> Dict[builtins.str, builtins.int]: ...
+    def replace_string(self, old: builtins.str, new: builtins.str) -> builtins.int: ...
+    def to_numpy(self) -> Any: ...
+    @staticmethod
+    def from_numpy(array: Any, kind: KindLike, rounding: Optional[Rounding] = None) -> param: ...
+    def assign_array(self, array: Any, rounding: Optional[Rounding] = None) -> None: ...
+    def to_table(self, labels: Optional[LabelMap] = None) -> Dict[builtins.str, List[Any]]: ...
+    @staticmethod
+    def from_table(
+        rows: Union[Dict[builtins.str, List[Any]], List[Dict[builtins.str, Any]]],
+        template: param,
+        labels: Optional[LabelMap] = None,
+    ) -> param: ...
+    def to_csv(self, filename: builtins.str, labels: Optional[LabelMap] = None) -> None: ...
+    @staticmethod
+    def from_csv(filename: builtins.str, template: param, labels: Optional[LabelMap] = None) -> param: ...
+    def __len__(self) -> builtins.int: ...
+    def __getitem__(self, key: Union[builtins.int, HashLike]) -> Union[param, List[param]]: ...
+    def __setitem__(self, key: Union[builtins.int, HashLike], value: param) -> None: ...
+    def __iter__(self) -> Iterator[Union[param, Tuple[_Hash, param]]]: ...
+    def __eq__(self, other: object) -> builtins.bool: ...
+    def __ne__(self, other: object) -> builtins.bool: ...
+
+class hash:
+    def __init__(self, value: HashLike, labels: Optional[LabelMap] = None) -> None: ...
+    @staticmethod
+    def algo(string: str) -> hash: ...
+    @staticmethod
+    def load_labels(filepath: str) -> None: ...
+    @staticmethod
+    def set_strict(strict: bool) -> None: ...
+    @property
+    def value(self) -> int: ...
+    def to_label(self, labels: Optional[LabelMap] = None) -> str: ...
+    def __hash__(self) -> int: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
+
+class Cache:
+    def __init__(self, dir: Union[builtins.str, "os.PathLike[builtins.str]"]) -> None: ...
+    @property
+    def dir(self) -> builtins.str: ...
+    def clear(self) -> None: ...
+
+class Transaction:
+    def __enter__(self) -> param: ...
+    def __exit__(self, exc_type: Any, exc_value: Any, traceback: Any) -> builtins.bool: ...
+
+# the param class has a method named "hash", which shadows this class within its body
+_Hash = hash
+
+class LabelMap:
+    def __init__(self) -> None: ...
+    @staticmethod
+    def global_map() -> LabelMap: ...
+    def load_labels(self, filepath: str) -> None: ...
+    def add_labels(self, labels: List[str]) -> None: ...
+    def add_custom_labels(self, labels: List[Tuple[HashLike, str]]) -> None: ...
+    def merge(self, other: LabelMap) -> None: ...
+    def copy(self) -> LabelMap: ...
+    def clear(self) -> None: ...
+    @property
+    def strict(self) -> bool: ...
+    @strict.setter
+    def strict(self, strict: bool) -> None: ...
+    def label_of(self, hash: HashLike) -> Optional[str]: ...
+    def hash_of(self, label: str) -> hash: ...
+    def __len__(self) -> int: ...
+    def __contains__(self, hash: HashLike) -> bool: ...
+
+def merge3(
+    base: param,
+    ours: param,
+    theirs: param,
+    strategy: Strategy = "ours",
+    key_fields: Optional[List[HashLike]] = None,
+) -> Tuple[param, List[Conflict]]: ...
+def grep(
+    dir: Union[builtins.str, "os.PathLike[builtins.str]"],
+    value: Union[ParamValue, param],
+    kind: Optional[KindLike] = None,
+    labels: Optional[LabelMap] = None,
+) -> Iterator[Tuple[builtins.str, Tuple[Union[builtins.int, _Hash], ...], ParamTypeKind]]: ...
+
+# the entry point for the `pyprc` command line tool, which reads its arguments from sys.argv
+def main() -> None: ...
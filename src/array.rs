@@ -0,0 +1,178 @@
+use crate::convert::{Number, Rounding};
+use crate::kind::kind_name;
+use crate::tree::Arena;
+use crate::{Param, ParamList2, ParamType};
+use prc::ParamKind;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+const DTYPES: [&str; 8] = [
+    "bool", "int8", "uint8", "int16", "uint16", "int32", "uint32", "float32",
+];
+
+fn push_bytes(value: &ParamKind, bytes: &mut Vec<u8>) -> Option<u8> {
+    let (kind, value_bytes) = match value {
+        ParamKind::Bool(v) => (1, vec![*v as u8]),
+        ParamKind::I8(v) => (2, v.to_ne_bytes().to_vec()),
+        ParamKind::U8(v) => (3, vec![*v]),
+        ParamKind::I16(v) => (4, v.to_ne_bytes().to_vec()),
+        ParamKind::U16(v) => (5, v.to_ne_bytes().to_vec()),
+        ParamKind::I32(v) => (6, v.to_ne_bytes().to_vec()),
+        ParamKind::U32(v) => (7, v.to_ne_bytes().to_vec()),
+        ParamKind::Float(v) => (8, v.to_ne_bytes().to_vec()),
+        _ => return None,
+    };
+    bytes.extend(value_bytes);
+    Some(kind)
+}
+
+/// Integer and boolean arrays are read as integers, so that no precision is lost
+fn read_numbers(array: &Bound<'_, PyAny>) -> PyResult<Vec<Number>> {
+    let numpy = array.py().import("numpy")?;
+    let array = numpy.call_method1("asarray", (array,))?;
+    let ndim: usize = array.getattr("ndim")?.extract()?;
+    if ndim != 1 {
+        return Err(PyValueError::new_err(format!(
+            "Expected a one-dimensional array, found {} dimensions",
+            ndim
+        )));
+    }
+    let dtype = array.getattr("dtype")?;
+    let is_float = match &*dtype.getattr("kind")?.extract::<String>()? {
+        "b" | "i" | "u" => false,
+        "f" => true,
+        _ => {
+            return Err(PyTypeError::new_err(format!(
+                "Expected an array of numbers, found dtype '{}'",
+                dtype.str()?
+            )))
+        }
+    };
+    let wide = if is_float { "float64" } else { "int64" };
+    let bytes = numpy
+        .call_method1("ascontiguousarray", (array, wide))?
+        .call_method0("tobytes")?;
+    let numbers = bytes
+        .downcast::<PyBytes>()?
+        .as_bytes()
+        .chunks_exact(8)
+        .map(|chunk| {
+            let chunk = chunk.try_into().expect("chunks are 8 bytes long");
+            match is_float {
+                true => Number::Float(f64::from_ne_bytes(chunk)),
+                false => Number::Int(i64::from_ne_bytes(chunk)),
+            }
+        })
+        .collect();
+    Ok(numbers)
+}
+
+pub(crate) fn from_array(
+    array: &Bound<'_, PyAny>,
+    kind: u8,
+    rounding: Option<Rounding>,
+) -> PyResult<Param> {
+    if !(1..=8).contains(&kind) {
+        return Err(PyTypeError::new_err(format!(
+            "Arrays can only be made into lists of numeric params, not {}",
+            kind_name(kind)
+        )));
+    }
+    let values = read_numbers(array)?
+        .into_iter()
+        .map(|number| number.to_param(kind, rounding))
+        .collect::<PyResult<Vec<_>>>()?;
+    let mut arena = Arena::default();
+    let ids = values.into_iter().map(|value| arena.push(value)).collect();
+    let id = arena.push(ParamType::List(ParamList2(ids)));
+    Ok(Param::new_tree(arena, id))
+}
+
+impl Param {
+    pub(crate) fn array<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let (kind, bytes) = {
+            let arena = self.lock()?;
+            let ParamType::List(v) = &arena[self.id()] else {
+                return Err(PyTypeError::new_err(
+                    "Only list-type params can be made into arrays",
+                ));
+            };
+            let mut kind = None;
+            let mut bytes = vec![];
+            for id in &v.0 {
+                let elem_kind = push_bytes(&arena.param_kind(&arena[*id])?, &mut bytes);
+                if elem_kind.is_none() || (kind.is_some() && kind != elem_kind) {
+                    return Err(PyTypeError::new_err(
+                        "Only lists of numeric params which all have the same type can be made into arrays",
+                    ));
+                }
+                kind = elem_kind;
+            }
+            let kind = kind.ok_or_else(|| {
+                PyValueError::new_err("Cannot tell which dtype to use for an empty list")
+            })?;
+            (kind, bytes)
+        };
+        py.import("numpy")?
+            .call_method1(
+                "frombuffer",
+                (PyBytes::new(py, &bytes), DTYPES[kind as usize - 1]),
+            )?
+            // arrays over bytes are read-only, so give the caller its own copy
+            .call_method0("copy")
+    }
+
+    /// The changes are undone together
+    pub(crate) fn assign_numbers(
+        &self,
+        array: &Bound<'_, PyAny>,
+        rounding: Option<Rounding>,
+    ) -> PyResult<()> {
+        let numbers = read_numbers(array)?;
+        let mut arena = self.lock_mut()?;
+        let ParamType::List(v) = &arena[self.id()] else {
+            return Err(PyTypeError::new_err(
+                "Cannot assign arrays to params other than list-type params",
+            ));
+        };
+        let ids = v.0.clone();
+        if ids.len() != numbers.len() {
+            return Err(PyValueError::new_err(format!(
+                "Cannot assign an array of length {} to a list of length {}",
+                numbers.len(),
+                ids.len()
+            )));
+        }
+        for id in &ids {
+            arena.load(*id)?;
+        }
+        let changes = ids
+            .into_iter()
+            .zip(numbers)
+            .filter_map(|(id, number)| {
+                let old = &arena[id];
+                let kind = old.type_number();
+                if kind > 8 {
+                    return Some(Err(PyTypeError::new_err(format!(
+                        "Cannot assign a number to a {} param",
+                        kind_name(kind)
+                    ))));
+                }
+                match number.to_param(kind, rounding) {
+                    Ok(new) if old.number() == new.number() => None,
+                    result => Some(result.map(|new| (id, new))),
+                }
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        if changes.is_empty() {
+            return Ok(());
+        }
+        arena.begin();
+        for (id, new) in changes {
+            arena.set(id, new);
+        }
+        arena.end();
+        Ok(())
+    }
+}
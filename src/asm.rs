@@ -0,0 +1,612 @@
+use crate::path::{Key, ParamPath};
+use crate::Hash;
+use prc::hash40::Hash40;
+use prc::{ParamKind, ParamStruct, MAGIC};
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+use std::collections::{HashMap, HashSet};
+
+/// The 0 hash always comes first, whatever the order
+#[derive(Debug, Clone, Default)]
+pub(crate) enum HashOrder {
+    /// The same as prc-rs
+    #[default]
+    Traversal,
+    Sorted,
+    /// Hashes which aren't given follow in traversal order
+    Given(Vec<Hash40>),
+}
+
+/// The ref table holds both strings and struct tables
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) enum StringOrder {
+    /// Strings go between struct tables, the same as prc-rs
+    #[default]
+    Traversal,
+    Last,
+    Sorted,
+}
+
+impl<'py> FromPyObject<'py> for HashOrder {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if let Ok(s) = ob.downcast::<PyString>() {
+            match &*s.to_cow()? {
+                "traversal" => Ok(HashOrder::Traversal),
+                "sorted" => Ok(HashOrder::Sorted),
+                other => Err(PyValueError::new_err(format!(
+                    "Unknown hash order '{}'; expected 'traversal', 'sorted', or a list of hashes",
+                    other
+                ))),
+            }
+        } else {
+            let hashes: Vec<Hash> = ob.extract().map_err(|_| {
+                PyTypeError::new_err("Hash order must be a string or a list of hashes")
+            })?;
+            Ok(HashOrder::Given(
+                hashes.into_iter().map(|hash| hash.inner).collect(),
+            ))
+        }
+    }
+}
+
+impl<'py> FromPyObject<'py> for StringOrder {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match &*ob.downcast::<PyString>()?.to_cow()? {
+            "traversal" => Ok(StringOrder::Traversal),
+            "last" => Ok(StringOrder::Last),
+            "sorted" => Ok(StringOrder::Sorted),
+            other => Err(PyValueError::new_err(format!(
+                "Unknown string order '{}'; expected 'traversal', 'last', or 'sorted'",
+                other
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SaveOptions {
+    pub(crate) hash_order: HashOrder,
+    pub(crate) string_order: StringOrder,
+}
+
+/// For tracing a byte of an assembled file back to a param
+#[derive(Default)]
+pub(crate) struct Spans {
+    hash_users: Vec<Option<ParamPath>>,
+    ref_entries: Vec<(usize, usize, ParamPath)>,
+    params: Vec<(usize, usize, ParamPath)>,
+}
+
+pub(crate) struct Assembled {
+    pub(crate) bytes: Vec<u8>,
+    hash_size: usize,
+    ref_size: usize,
+    spans: Option<Spans>,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum RefEntry {
+    Str(String),
+    /// The hash index and offset of each field, relative to the struct
+    Table(Vec<(u32, u32)>),
+}
+
+struct RefUse {
+    entry: RefEntry,
+    param_offset: usize,
+    path: Option<ParamPath>,
+}
+
+struct Assembler {
+    hash_indices: HashMap<Hash40, u32>,
+    params: Vec<u8>,
+    refs: Vec<RefUse>,
+    path: Vec<Key>,
+    spans: Option<Spans>,
+}
+
+/// With `record` set, the result also tracks which param each part of the file came from
+pub(crate) fn assemble(root: &ParamStruct, options: &SaveOptions, record: bool) -> Assembled {
+    let hashes = hash_table(root, &options.hash_order);
+    let mut asm = Assembler {
+        hash_indices: hashes
+            .iter()
+            .enumerate()
+            .map(|(index, hash)| (*hash, index as u32))
+            .collect(),
+        params: Vec::new(),
+        refs: Vec::new(),
+        path: Vec::new(),
+        spans: record.then(|| Spans {
+            hash_users: vec![None; hashes.len()],
+            ..Default::default()
+        }),
+    };
+    if let Some(spans) = &mut asm.spans {
+        spans.params.push((0, 0, ParamPath::default()));
+    }
+    asm.write_struct(root);
+    if let Some(spans) = &mut asm.spans {
+        spans.params[0].1 = asm.params.len();
+    }
+
+    // ref entries are placed in order, with duplicates sharing the offset of the first copy
+    let mut order: Vec<usize> = (0..asm.refs.len()).collect();
+    match options.string_order {
+        StringOrder::Traversal => {}
+        StringOrder::Last => {
+            order.sort_by_key(|&index| matches!(asm.refs[index].entry, RefEntry::Str(_)))
+        }
+        StringOrder::Sorted => {
+            order.sort_by(|&a, &b| match (&asm.refs[a].entry, &asm.refs[b].entry) {
+                (RefEntry::Str(a), RefEntry::Str(b)) => a.as_bytes().cmp(b.as_bytes()),
+                (a, b) => matches!(a, RefEntry::Str(_)).cmp(&matches!(b, RefEntry::Str(_))),
+            })
+        }
+    }
+    let mut ref_table = Vec::new();
+    let mut offsets: HashMap<&RefEntry, u32> = HashMap::new();
+    for index in order {
+        let RefUse {
+            entry,
+            param_offset,
+            path,
+        } = &asm.refs[index];
+        let offset = *offsets.entry(entry).or_insert_with(|| {
+            let start = ref_table.len();
+            match entry {
+                RefEntry::Str(s) => {
+                    ref_table.extend_from_slice(s.as_bytes());
+                    ref_table.push(0);
+                }
+                RefEntry::Table(table) => {
+                    for (hash_index, offset) in table {
+                        ref_table.extend_from_slice(&hash_index.to_le_bytes());
+                        ref_table.extend_from_slice(&offset.to_le_bytes());
+                    }
+                }
+            }
+            if let (Some(spans), Some(path)) = (&mut asm.spans, path) {
+                spans
+                    .ref_entries
+                    .push((start, ref_table.len(), path.clone()));
+            }
+            start as u32
+        });
+        asm.params[*param_offset..*param_offset + 4].copy_from_slice(&offset.to_le_bytes());
+    }
+
+    let hash_size = 8 * hashes.len();
+    let mut bytes = Vec::with_capacity(0x10 + hash_size + ref_table.len() + asm.params.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&(hash_size as u32).to_le_bytes());
+    bytes.extend_from_slice(&(ref_table.len() as u32).to_le_bytes());
+    for hash in &hashes {
+        bytes.extend_from_slice(&hash.0.to_le_bytes());
+    }
+    bytes.extend_from_slice(&ref_table);
+    bytes.extend_from_slice(&asm.params);
+    Assembled {
+        bytes,
+        hash_size,
+        ref_size: ref_table.len(),
+        spans: asm.spans,
+    }
+}
+
+fn hash_table(root: &ParamStruct, order: &HashOrder) -> Vec<Hash40> {
+    fn visit(param: &ParamKind, hashes: &mut Vec<Hash40>, seen: &mut HashSet<Hash40>) {
+        match param {
+            ParamKind::Hash(hash) if seen.insert(*hash) => hashes.push(*hash),
+            ParamKind::List(v) => v.0.iter().for_each(|p| visit(p, hashes, seen)),
+            ParamKind::Struct(v) => {
+                for (hash, p) in &v.0 {
+                    if seen.insert(*hash) {
+                        hashes.push(*hash);
+                    }
+                    visit(p, hashes, seen);
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut hashes = vec![Hash40(0)];
+    let mut seen = HashSet::from([Hash40(0)]);
+    if let HashOrder::Given(given) = order {
+        for hash in given {
+            if seen.insert(*hash) {
+                hashes.push(*hash);
+            }
+        }
+    }
+    for (hash, p) in &root.0 {
+        if seen.insert(*hash) {
+            hashes.push(*hash);
+        }
+        visit(p, &mut hashes, &mut seen);
+    }
+    if let HashOrder::Sorted = order {
+        hashes[1..].sort();
+    }
+    hashes
+}
+
+impl Assembler {
+    fn hash_index(&mut self, hash: Hash40) -> u32 {
+        let index = self.hash_indices[&hash];
+        if let Some(spans) = &mut self.spans {
+            let user = &mut spans.hash_users[index as usize];
+            if user.is_none() {
+                *user = Some(ParamPath(self.path.clone()));
+            }
+        }
+        index
+    }
+
+    fn current_path(&self) -> Option<ParamPath> {
+        self.spans.as_ref().map(|_| ParamPath(self.path.clone()))
+    }
+
+    fn write_param(&mut self, param: &ParamKind) {
+        let start = self.params.len();
+        let span = self.spans.as_mut().map(|spans| {
+            spans
+                .params
+                .push((start, start, ParamPath(self.path.clone())));
+            spans.params.len() - 1
+        });
+        match param {
+            ParamKind::Bool(v) => self.params.extend_from_slice(&[1, *v as u8]),
+            ParamKind::I8(v) => self.params.extend_from_slice(&[2, *v as u8]),
+            ParamKind::U8(v) => self.params.extend_from_slice(&[3, *v]),
+            ParamKind::I16(v) => {
+                self.params.push(4);
+                self.params.extend_from_slice(&v.to_le_bytes());
+            }
+            ParamKind::U16(v) => {
+                self.params.push(5);
+                self.params.extend_from_slice(&v.to_le_bytes());
+            }
+            ParamKind::I32(v) => {
+                self.params.push(6);
+                self.params.extend_from_slice(&v.to_le_bytes());
+            }
+            ParamKind::U32(v) => {
+                self.params.push(7);
+                self.params.extend_from_slice(&v.to_le_bytes());
+            }
+            ParamKind::Float(v) => {
+                self.params.push(8);
+                self.params.extend_from_slice(&v.to_le_bytes());
+            }
+            ParamKind::Hash(v) => {
+                let index = self.hash_index(*v);
+                self.params.push(9);
+                self.params.extend_from_slice(&index.to_le_bytes());
+            }
+            ParamKind::Str(v) => {
+                self.params.push(10);
+                self.refs.push(RefUse {
+                    entry: RefEntry::Str(v.clone()),
+                    param_offset: self.params.len(),
+                    path: self.current_path(),
+                });
+                self.params.extend_from_slice(&[0; 4]);
+            }
+            ParamKind::List(v) => {
+                self.params.push(11);
+                self.params
+                    .extend_from_slice(&(v.0.len() as u32).to_le_bytes());
+                let table = self.params.len();
+                self.params.resize(table + 4 * v.0.len(), 0);
+                for (index, child) in v.0.iter().enumerate() {
+                    let offset = (self.params.len() - start) as u32;
+                    self.params[table + 4 * index..table + 4 * index + 4]
+                        .copy_from_slice(&offset.to_le_bytes());
+                    self.path.push(Key::Index(index));
+                    self.write_param(child);
+                    self.path.pop();
+                }
+            }
+            ParamKind::Struct(v) => self.write_struct(v),
+        }
+        if let (Some(spans), Some(span)) = (&mut self.spans, span) {
+            spans.params[span].1 = self.params.len();
+        }
+    }
+
+    fn write_struct(&mut self, param: &ParamStruct) {
+        let start = self.params.len();
+        self.params.push(12);
+        self.params
+            .extend_from_slice(&(param.0.len() as u32).to_le_bytes());
+        let ref_use = self.refs.len();
+        self.refs.push(RefUse {
+            entry: RefEntry::Table(Vec::with_capacity(param.0.len())),
+            param_offset: self.params.len(),
+            path: self.current_path(),
+        });
+        self.params.extend_from_slice(&[0; 4]);
+
+        // fields are written sorted by hash, keeping the order of duplicates
+        let mut sorted: Vec<_> = param.0.iter().collect();
+        sorted.sort_by_key(|(hash, _)| *hash);
+        for (hash, child) in sorted {
+            self.path.push(Key::Hash(*hash));
+            let field = (self.hash_index(*hash), (self.params.len() - start) as u32);
+            if let RefEntry::Table(table) = &mut self.refs[ref_use].entry {
+                table.push(field);
+            }
+            self.write_param(child);
+            self.path.pop();
+        }
+    }
+}
+
+impl Assembled {
+    pub(crate) fn first_difference(
+        &self,
+        expected: &[u8],
+    ) -> Option<(usize, &'static str, Option<ParamPath>)> {
+        let offset = self
+            .bytes
+            .iter()
+            .zip(expected)
+            .position(|(a, b)| a != b)
+            .or_else(|| {
+                (self.bytes.len() != expected.len()).then_some(self.bytes.len().min(expected.len()))
+            })?;
+
+        let ref_start = 0x10 + self.hash_size;
+        let param_start = ref_start + self.ref_size;
+        let spans = self.spans.as_ref();
+        let (section, path) = if offset < 0x10 {
+            ("header", None)
+        } else if offset < ref_start {
+            let index = (offset - 0x10) / 8;
+            let user = spans.and_then(|spans| spans.hash_users[index].clone());
+            ("hash table", user)
+        } else if offset < param_start {
+            let relative = offset - ref_start;
+            let user = spans.and_then(|spans| {
+                spans
+                    .ref_entries
+                    .iter()
+                    .find(|(start, end, _)| (*start..*end).contains(&relative))
+                    .map(|(_, _, path)| path.clone())
+            });
+            ("ref table", user)
+        } else if offset < self.bytes.len() {
+            let relative = offset - param_start;
+            // params are recorded parents first, so the last match is the innermost
+            let user = spans.and_then(|spans| {
+                spans
+                    .params
+                    .iter()
+                    .rev()
+                    .find(|(start, end, _)| (*start..*end).contains(&relative))
+                    .map(|(_, _, path)| path.clone())
+            });
+            ("params", user)
+        } else {
+            ("end of file", None)
+        };
+        Some((offset, section, path))
+    }
+}
+
+pub(crate) fn read_hash_table(bytes: &[u8]) -> PyResult<Vec<Hash40>> {
+    let invalid = || PyValueError::new_err("Not a valid param file");
+    if bytes.get(..8) != Some(MAGIC) {
+        return Err(invalid());
+    }
+    let hash_size = bytes
+        .get(8..12)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+        .ok_or_else(invalid)?;
+    let table = bytes.get(0x10..0x10 + hash_size).ok_or_else(invalid)?;
+    Ok(table
+        .chunks_exact(8)
+        .map(|b| Hash40(u64::from_le_bytes(b.try_into().unwrap())))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prc::ParamList;
+    use std::io::Cursor;
+
+    fn hash(value: u64) -> Hash40 {
+        Hash40(value)
+    }
+
+    fn entry(kind: u64, name: &str) -> ParamKind {
+        ParamKind::Struct(ParamStruct(vec![
+            (hash(0x30), ParamKind::Hash(hash(kind))),
+            (hash(0x20), ParamKind::Str(name.into())),
+            (hash(0x10), ParamKind::U8(kind as u8)),
+        ]))
+    }
+
+    fn sample() -> ParamStruct {
+        ParamStruct(vec![
+            (hash(0x90), ParamKind::Bool(true)),
+            (hash(0x81), ParamKind::I8(-1)),
+            (hash(0x82), ParamKind::U8(2)),
+            (hash(0x83), ParamKind::I16(-3)),
+            (hash(0x84), ParamKind::U16(4)),
+            (hash(0x85), ParamKind::I32(-5)),
+            (hash(0x86), ParamKind::U32(6)),
+            (hash(0x87), ParamKind::Float(0.5)),
+            (hash(0x88), ParamKind::Str("zeta".into())),
+            (
+                hash(0x40),
+                ParamKind::List(ParamList(vec![
+                    entry(0x71, "beta"),
+                    entry(0x72, "alpha"),
+                    entry(0x71, "beta"),
+                ])),
+            ),
+            (hash(0x41), ParamKind::List(ParamList(vec![]))),
+            (
+                hash(0x42),
+                ParamKind::Struct(ParamStruct(vec![(hash(0x10), ParamKind::U8(9))])),
+            ),
+            (hash(0x44), ParamKind::Struct(ParamStruct(vec![]))),
+            (hash(0x43), ParamKind::U8(7)),
+            (hash(0x43), ParamKind::U8(8)),
+        ])
+    }
+
+    /// Python errors can't be linked into tests
+    fn hash_table(bytes: &[u8]) -> Vec<Hash40> {
+        let size = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        bytes[0x10..0x10 + size]
+            .chunks_exact(8)
+            .map(|b| Hash40(u64::from_le_bytes(b.try_into().unwrap())))
+            .collect()
+    }
+
+    /// prc-rs reads fields in hash table order, so compare them sorted by hash
+    fn sorted(param: ParamKind) -> ParamKind {
+        match param {
+            ParamKind::List(v) => ParamKind::List(ParamList(v.0.into_iter().map(sorted).collect())),
+            ParamKind::Struct(mut v) => {
+                v.0.sort_by_key(|(hash, _)| *hash);
+                ParamKind::Struct(ParamStruct(
+                    v.0.into_iter().map(|(h, p)| (h, sorted(p))).collect(),
+                ))
+            }
+            param => param,
+        }
+    }
+
+    fn options(hash_order: HashOrder, string_order: StringOrder) -> SaveOptions {
+        SaveOptions {
+            hash_order,
+            string_order,
+        }
+    }
+
+    fn all_options() -> Vec<SaveOptions> {
+        let hash_orders = [
+            HashOrder::Traversal,
+            HashOrder::Sorted,
+            HashOrder::Given(vec![hash(0x72), hash(0x10)]),
+        ];
+        let string_orders = [
+            StringOrder::Traversal,
+            StringOrder::Last,
+            StringOrder::Sorted,
+        ];
+        hash_orders
+            .iter()
+            .flat_map(|h| string_orders.iter().map(|s| options(h.clone(), *s)))
+            .collect()
+    }
+
+    #[test]
+    fn default_options_match_prc_rs() {
+        let root = sample();
+        let mut expected = Cursor::new(vec![]);
+        prc::write_stream(&mut expected, &root).unwrap();
+        let assembled = assemble(&root, &SaveOptions::default(), false);
+        assert_eq!(assembled.bytes, expected.into_inner());
+    }
+
+    #[test]
+    fn every_order_reads_back() {
+        let root = sample();
+        for options in all_options() {
+            let bytes = assemble(&root, &options, false).bytes;
+            let read = prc::read_stream(&mut Cursor::new(&bytes)).unwrap();
+            let (read, expected) = (ParamKind::Struct(read), ParamKind::Struct(sample()));
+            assert_eq!(sorted(read), sorted(expected), "{:?}", options);
+        }
+    }
+
+    #[test]
+    fn hash_orders() {
+        let root = sample();
+        let table = |order| {
+            let bytes = assemble(&root, &options(order, StringOrder::Traversal), false).bytes;
+            hash_table(&bytes)
+        };
+        let traversal = table(HashOrder::Traversal);
+        assert_eq!(traversal[0], hash(0));
+        assert_eq!(traversal[1], hash(0x90));
+
+        let sorted = table(HashOrder::Sorted);
+        assert_eq!(sorted[0], hash(0));
+        assert!(sorted.windows(2).all(|w| w[0] < w[1]));
+
+        let given = table(HashOrder::Given(vec![hash(0x72), hash(0x10)]));
+        assert_eq!(given[..3], [hash(0), hash(0x72), hash(0x10)]);
+        assert_eq!(given.len(), traversal.len());
+    }
+
+    #[test]
+    fn string_orders() {
+        let root = sample();
+        let refs = |order| {
+            let assembled = assemble(&root, &options(HashOrder::Traversal, order), false);
+            let start = 0x10 + assembled.hash_size;
+            assembled.bytes[start..start + assembled.ref_size].to_vec()
+        };
+        let strings = |refs: &[u8]| {
+            ["zeta", "beta", "alpha"]
+                .into_iter()
+                .map(|s| {
+                    refs.windows(s.len())
+                        .position(|w| w == s.as_bytes())
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+        let traversal = refs(StringOrder::Traversal);
+        let last = refs(StringOrder::Last);
+        let sorted = refs(StringOrder::Sorted);
+        assert_eq!(traversal.len(), last.len());
+        assert_ne!(traversal, last);
+        // beta and alpha are first used in the list, which comes before zeta
+        let in_last = strings(&last);
+        assert!(in_last[1] < in_last[2] && in_last[2] < in_last[0]);
+        let in_sorted = strings(&sorted);
+        assert!(in_sorted[2] < in_sorted[1] && in_sorted[1] < in_sorted[0]);
+        // every string comes after the last struct table
+        assert!(last[in_last[0]..]
+            .iter()
+            .all(|b| *b == 0 || b.is_ascii_lowercase()));
+    }
+
+    #[test]
+    fn first_difference() {
+        let root = sample();
+        let assembled = assemble(&root, &SaveOptions::default(), true);
+        assert_eq!(assembled.first_difference(&assembled.bytes), None);
+
+        let mut changed = assembled.bytes.clone();
+        let last = changed.len() - 1;
+        changed[last] ^= 1;
+        let (offset, section, path) = assembled.first_difference(&changed).unwrap();
+        assert_eq!((offset, section), (last, "params"));
+        assert_eq!(path.unwrap().0, [Key::Hash(hash(0x90))]);
+
+        let mut changed = assembled.bytes.clone();
+        changed[0x18] ^= 1;
+        let (_, section, path) = assembled.first_difference(&changed).unwrap();
+        assert_eq!(section, "hash table");
+        assert_eq!(path.unwrap().0, [Key::Hash(hash(0x90))]);
+
+        let short = &assembled.bytes[..last];
+        let (offset, section, _) = assembled.first_difference(short).unwrap();
+        assert_eq!((offset, section), (last, "params"));
+
+        let mut long = assembled.bytes.clone();
+        long.push(0);
+        let (offset, section, _) = assembled.first_difference(&long).unwrap();
+        assert_eq!((offset, section), (last + 1, "end of file"));
+    }
+}
@@ -0,0 +1,122 @@
+use crate::convert::{from_value, infer};
+use crate::kind::{extract_kind, is_kind_name, kind_name};
+use crate::tree::Draft;
+use crate::{Hash, Param, ParamType};
+use prc::hash40::Hash40;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyTuple};
+
+/// A tuple of a type with a value such as `("u8", 5)` creates a param of that type. Other
+/// values use the type of the matching param in the template, or else an inferred type
+pub(crate) fn build(spec: &Bound<'_, PyAny>, template: Option<&Param>) -> PyResult<Draft> {
+    if let Ok(p) = spec.downcast::<Param>() {
+        return Ok(Draft::Shared(p.borrow().clone_ref()));
+    }
+    if let Some((kind, value)) = annotation(spec)? {
+        return build_typed(kind, &value, template);
+    }
+    match template.map(Param::type_number) {
+        Some(kind) => build_typed(kind, spec, template),
+        None if spec.is_instance_of::<PyDict>() => build_typed(12, spec, None),
+        None if spec.is_instance_of::<PyList>() || spec.is_instance_of::<PyTuple>() => {
+            build_typed(11, spec, None)
+        }
+        None => infer(spec),
+    }
+}
+
+/// The type has to be a name or a `ParamTypeKind`, so that a tuple of two numbers stays a list
+fn annotation<'py>(spec: &Bound<'py, PyAny>) -> PyResult<Option<(u8, Bound<'py, PyAny>)>> {
+    let Ok(tuple) = spec.downcast::<PyTuple>() else {
+        return Ok(None);
+    };
+    if tuple.len() != 2 || !is_kind_name(&tuple.get_item(0)?) {
+        return Ok(None);
+    }
+    match extract_kind(&tuple.get_item(0)?) {
+        Ok(kind) => Ok(Some((kind, tuple.get_item(1)?))),
+        Err(_) => Ok(None),
+    }
+}
+
+fn build_typed(kind: u8, value: &Bound<'_, PyAny>, template: Option<&Param>) -> PyResult<Draft> {
+    if let Ok(p) = value.downcast::<Param>() {
+        return Ok(Draft::Shared(p.borrow().clone_ref()));
+    }
+    match kind {
+        11 => {
+            if !(value.is_instance_of::<PyList>() || value.is_instance_of::<PyTuple>()) {
+                return Err(expected("a list or tuple", kind, value));
+            }
+            let elements = template.map(list_elements).transpose()?.unwrap_or_default();
+            let list = value
+                .try_iter()?
+                .enumerate()
+                .map(|(index, item)| {
+                    let child_template = elements.get(index).or(elements.first());
+                    build(&item?, child_template)
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            Ok(Draft::List(list))
+        }
+        12 => {
+            let pairs: Vec<(Bound<PyAny>, Bound<PyAny>)> =
+                if let Ok(dict) = value.downcast::<PyDict>() {
+                    dict.iter().collect()
+                } else if value.is_instance_of::<PyList>() || value.is_instance_of::<PyTuple>() {
+                    value
+                        .try_iter()?
+                        .map(|item| item?.extract())
+                        .collect::<PyResult<_>>()?
+                } else {
+                    return Err(expected("a dict or list of key-value pairs", kind, value));
+                };
+            let fields = template.map(struct_fields).transpose()?.unwrap_or_default();
+            let fields = pairs
+                .iter()
+                .map(|(key, item)| {
+                    let hash = key.extract::<Hash>()?.inner;
+                    let child_template = fields.iter().find(|(h, _)| *h == hash).map(|(_, p)| p);
+                    Ok((hash, build(item, child_template)?))
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            Ok(Draft::Struct(fields))
+        }
+        _ => from_value(kind, value),
+    }
+}
+
+pub(crate) fn list_elements(template: &Param) -> PyResult<Vec<Param>> {
+    let arena = template.lock()?;
+    Ok(match &arena[template.id()] {
+        ParamType::List(v) => v.0.iter().map(|p| arena.handle(template, *p)).collect(),
+        _ => vec![],
+    })
+}
+
+pub(crate) fn struct_fields(template: &Param) -> PyResult<Vec<(Hash40, Param)>> {
+    let arena = template.lock()?;
+    Ok(match &arena[template.id()] {
+        ParamType::Struct(v) => {
+            v.0.iter()
+                .map(|(h, p)| (*h, arena.handle(template, *p)))
+                .collect()
+        }
+        _ => vec![],
+    })
+}
+
+fn expected(what: &str, kind: u8, value: &Bound<'_, PyAny>) -> PyErr {
+    let type_name = value
+        .get_type()
+        .name()
+        .map(|name| name.to_string())
+        .unwrap_or_default();
+    PyTypeError::new_err(format!(
+        "Expected {} to build a {} param, found '{}'",
+        what,
+        kind_name(kind),
+        type_name
+    ))
+}
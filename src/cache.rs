@@ -0,0 +1,296 @@
+use crate::tree::{Arena, NodeId};
+use crate::{Hash, Param, ParamList2, ParamStruct2, ParamType};
+use prc::hash40::Hash40;
+use prc::ParamKind;
+use pyo3::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Changed whenever the layout of entries changes, so that old entries are ignored
+const ENTRY_MAGIC: &[u8; 8] = b"pyprcc02";
+
+/// An entry is used as long as the file has the same modified time and size, or failing that,
+/// the same contents
+#[derive(Debug)]
+struct Header {
+    source: PathBuf,
+    modified: u64,
+    size: u64,
+    hash: u64,
+}
+
+/// FNV-1a, which unlike the standard library's hashers is the same in every version of rust
+fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn write_u32(out: &mut Vec<u8>, value: usize) {
+    out.extend((value as u32).to_le_bytes());
+}
+
+impl Header {
+    fn write(&self, out: &mut Vec<u8>) {
+        let source = self.source.as_os_str().as_encoded_bytes();
+        out.extend(ENTRY_MAGIC);
+        write_u32(out, source.len());
+        out.extend(source);
+        out.extend(self.modified.to_le_bytes());
+        out.extend(self.size.to_le_bytes());
+        out.extend(self.hash.to_le_bytes());
+    }
+
+    fn read(reader: &mut Reader, source: &Path) -> Option<Self> {
+        if reader.take(8)? != ENTRY_MAGIC {
+            return None;
+        }
+        let len = reader.u32()?;
+        if reader.take(len)? != source.as_os_str().as_encoded_bytes() {
+            return None;
+        }
+        Some(Header {
+            source: source.into(),
+            modified: reader.u64()?,
+            size: reader.u64()?,
+            hash: reader.u64()?,
+        })
+    }
+}
+
+/// Children always come before their parents, so that an entry can be read back in one pass
+fn encode(param: &ParamKind, out: &mut Vec<u8>, count: &mut usize) -> usize {
+    let mut body = vec![];
+    let kind = match param {
+        ParamKind::Bool(v) => {
+            body.push(*v as u8);
+            1
+        }
+        ParamKind::I8(v) => {
+            body.extend(v.to_le_bytes());
+            2
+        }
+        ParamKind::U8(v) => {
+            body.push(*v);
+            3
+        }
+        ParamKind::I16(v) => {
+            body.extend(v.to_le_bytes());
+            4
+        }
+        ParamKind::U16(v) => {
+            body.extend(v.to_le_bytes());
+            5
+        }
+        ParamKind::I32(v) => {
+            body.extend(v.to_le_bytes());
+            6
+        }
+        ParamKind::U32(v) => {
+            body.extend(v.to_le_bytes());
+            7
+        }
+        ParamKind::Float(v) => {
+            body.extend(v.to_le_bytes());
+            8
+        }
+        ParamKind::Hash(v) => {
+            body.extend(v.0.to_le_bytes());
+            9
+        }
+        ParamKind::Str(v) => {
+            write_u32(&mut body, v.len());
+            body.extend(v.as_bytes());
+            10
+        }
+        ParamKind::List(v) => {
+            write_u32(&mut body, v.0.len());
+            for child in &v.0 {
+                let id = encode(child, out, count);
+                write_u32(&mut body, id);
+            }
+            11
+        }
+        ParamKind::Struct(v) => {
+            write_u32(&mut body, v.0.len());
+            for (hash, child) in &v.0 {
+                let id = encode(child, out, count);
+                body.extend(hash.0.to_le_bytes());
+                write_u32(&mut body, id);
+            }
+            12
+        }
+    };
+    out.push(kind);
+    out.extend(body);
+    *count += 1;
+    *count - 1
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.bytes.len() {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N).map(|bytes| bytes.try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> Option<usize> {
+        self.array().map(|b| u32::from_le_bytes(b) as usize)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn child(&mut self, next: usize) -> Option<NodeId> {
+        self.u32().filter(|id| *id < next).map(|id| id as NodeId)
+    }
+
+    fn node(&mut self, next: usize) -> Option<ParamType> {
+        let [kind] = self.array()?;
+        Some(match kind {
+            1 => ParamType::Bool(self.array::<1>()?[0] != 0),
+            2 => ParamType::I8(i8::from_le_bytes(self.array()?)),
+            3 => ParamType::U8(u8::from_le_bytes(self.array()?)),
+            4 => ParamType::I16(i16::from_le_bytes(self.array()?)),
+            5 => ParamType::U16(u16::from_le_bytes(self.array()?)),
+            6 => ParamType::I32(i32::from_le_bytes(self.array()?)),
+            7 => ParamType::U32(u32::from_le_bytes(self.array()?)),
+            8 => ParamType::Float(f32::from_le_bytes(self.array()?)),
+            9 => ParamType::Hash(Hash::from(Hash40(self.u64()?))),
+            10 => {
+                let len = self.u32()?;
+                ParamType::Str(String::from_utf8(self.take(len)?.to_vec()).ok()?.into())
+            }
+            11 => {
+                let len = self.u32()?;
+                let children = (0..len).map(|_| self.child(next)).collect::<Option<_>>()?;
+                ParamType::List(ParamList2(children))
+            }
+            12 => {
+                let len = self.u32()?;
+                let fields = (0..len)
+                    .map(|_| Some((Hash40(self.u64()?), self.child(next)?)))
+                    .collect::<Option<_>>()?;
+                ParamType::Struct(ParamStruct2(fields))
+            }
+            _ => return None,
+        })
+    }
+
+    fn tree(&mut self) -> Option<Param> {
+        let count = self.u32()?;
+        let mut arena = Arena::default();
+        for next in 0..count {
+            let param = self.node(next)?;
+            arena.push(param);
+        }
+        let root = count.checked_sub(1)?;
+        self.bytes
+            .is_empty()
+            .then(|| Param::new_tree(arena, root as NodeId))
+    }
+}
+
+/// Entries are stored by the path of the file they came from
+#[pyclass(module = "pyprc")]
+pub(crate) struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    fn entry_path(&self, source: &Path) -> PathBuf {
+        let name = content_hash(source.as_os_str().as_encoded_bytes());
+        self.dir.join(format!("{:016x}.bin", name))
+    }
+
+    /// Other processes never see an entry which is only partly written
+    fn write_entry(path: &Path, header: &Header, params: &[u8]) -> PyResult<()> {
+        let mut bytes = vec![];
+        header.write(&mut bytes);
+        bytes.extend(params);
+        let temp = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&temp, bytes)?;
+        fs::rename(&temp, path)?;
+        Ok(())
+    }
+
+    pub(crate) fn load(&self, filename: &str) -> PyResult<Param> {
+        let source = fs::canonicalize(filename)?;
+        let metadata = fs::metadata(&source)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        let mut header = Header {
+            source,
+            modified,
+            size: metadata.len(),
+            hash: 0,
+        };
+        let entry_path = self.entry_path(&header.source);
+        let entry = fs::read(&entry_path).unwrap_or_default();
+        let mut reader = Reader { bytes: &entry };
+        if let Some(found) = Header::read(&mut reader, &header.source) {
+            let params = reader.bytes;
+            let unchanged = found.modified == header.modified && found.size == header.size;
+            // a file which was only touched still has the same contents
+            if unchanged || content_hash(&fs::read(&header.source)?) == found.hash {
+                if let Some(param) = reader.tree() {
+                    if !unchanged {
+                        header.hash = found.hash;
+                        Cache::write_entry(&entry_path, &header, params)?;
+                    }
+                    return Ok(param);
+                }
+            }
+        }
+
+        header.hash = content_hash(&fs::read(&header.source)?);
+        let param = Param::open(filename)?;
+        let mut params = vec![];
+        let mut count = 0;
+        encode(&ParamKind::try_from(&param)?, &mut params, &mut count);
+        let mut bytes = vec![];
+        write_u32(&mut bytes, count);
+        bytes.extend(params);
+        Cache::write_entry(&entry_path, &header, &bytes)?;
+        Ok(param)
+    }
+}
+
+#[pymethods]
+impl Cache {
+    #[new]
+    fn new(dir: PathBuf) -> PyResult<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Cache { dir })
+    }
+
+    #[getter]
+    fn dir(&self) -> PathBuf {
+        self.dir.clone()
+    }
+
+    fn clear(&self) -> PyResult<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "bin") {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
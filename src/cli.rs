@@ -0,0 +1,237 @@
+use crate::asm::SaveOptions;
+use crate::convert::parse_value;
+use crate::diff::{changes_from_json, changes_to_json, diff};
+use crate::labels::Labels;
+use crate::lazy::open_lazy;
+use crate::path::ParamPath;
+use crate::pretty::TreeFormat;
+use crate::tree::Draft;
+use crate::{xml, Param, ParamType};
+use prc::ParamKind;
+use pyo3::exceptions::{PySystemExit, PyValueError};
+use pyo3::prelude::*;
+use std::fs::{read_to_string, write};
+use std::io::{ErrorKind, Write};
+
+const USAGE: &str = "\
+usage: pyprc [--labels <file>] <command> [<args>]
+
+commands:
+  dump <file>                         print the param tree
+  get <file> <path>                   print the param (or tree) at a path
+  set <file> <path> <value> -o <out>  set the value at a path, keeping its type
+  to-xml <file> [-o <out>]            convert a param file to XML
+  from-xml <file> -o <out>            convert XML to a param file
+  diff <a> <b> [-k <field>] [-o <out>]
+                                      write the changes from a to b as a JSON patch
+  patch <file> <patch> -o <out>       apply a patch written by diff
+
+options:
+  -l, --labels <file>  load hash labels (hash,label per line); may be repeated
+  -o, --output <file>  write to this file instead of stdout
+  -d, --depth <n>      only print this many levels of the tree
+  -k, --key <field>    match list elements by this field when diffing; may be repeated
+  -h, --help           print this message
+
+Paths separate keys with '/'. Numbers are list indices, and anything else is a hash label
+or a hexadecimal hash, e.g. 'fighter_param_table/3/jump_count_max'.";
+
+#[pyfunction]
+pub(crate) fn main(py: Python) -> PyResult<()> {
+    let argv: Vec<String> = py.import("sys")?.getattr("argv")?.extract()?;
+    let result = Args::parse(argv.get(1..).unwrap_or_default())
+        .map_err(PyValueError::new_err)
+        .and_then(|args| args.run());
+    match result {
+        Ok(Some(output)) => {
+            let mut stdout = std::io::stdout().lock();
+            match stdout.write_all(output.as_bytes()) {
+                Err(e) if e.kind() != ErrorKind::BrokenPipe => Err(e.into()),
+                _ => Ok(()),
+            }
+        }
+        Ok(None) => Ok(()),
+        Err(e) => {
+            eprintln!("error: {}", e.value(py));
+            Err(PySystemExit::new_err(1))
+        }
+    }
+}
+
+struct Args {
+    command: String,
+    positional: Vec<String>,
+    output: Option<String>,
+    depth: Option<usize>,
+    keys: Vec<String>,
+}
+
+impl Args {
+    fn parse(argv: &[String]) -> Result<Self, String> {
+        let mut positional = vec![];
+        let mut output = None;
+        let mut depth = None;
+        let mut keys = vec![];
+        let mut args = argv.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => positional.insert(0, String::from("help")),
+                "-o" | "--output" => {
+                    output = Some(args.next().ok_or("missing file after --output")?.clone())
+                }
+                "-d" | "--depth" => {
+                    let value = args.next().ok_or("missing number after --depth")?;
+                    depth = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid depth '{}'", value))?,
+                    )
+                }
+                "-k" | "--key" => {
+                    keys.push(args.next().ok_or("missing field after --key")?.clone())
+                }
+                "-l" | "--labels" => {
+                    let file = args.next().ok_or("missing file after --labels")?;
+                    Labels::global()
+                        .inner
+                        .lock()
+                        .unwrap()
+                        .add_custom_labels_from_path(file)
+                        .map_err(|e| format!("could not load labels from '{}': {:?}", file, e))?
+                }
+                _ => positional.push(arg.clone()),
+            }
+        }
+        if positional.is_empty() {
+            return Err(format!("missing command\n\n{}", USAGE));
+        }
+        Ok(Args {
+            command: positional.remove(0),
+            positional,
+            output,
+            depth,
+            keys,
+        })
+    }
+
+    fn expect<const N: usize>(&self) -> PyResult<[&str; N]> {
+        if self.positional.len() != N {
+            return Err(PyValueError::new_err(format!(
+                "'{}' expects {} arguments, found {}\n\n{}",
+                self.command,
+                N,
+                self.positional.len(),
+                USAGE
+            )));
+        }
+        Ok(std::array::from_fn(|i| self.positional[i].as_str()))
+    }
+
+    fn output(&self) -> PyResult<&str> {
+        self.output.as_deref().ok_or_else(|| {
+            PyValueError::new_err(format!("'{}' needs an output file (-o)", self.command))
+        })
+    }
+
+    fn emit(&self, mut text: String) -> PyResult<Option<String>> {
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        match &self.output {
+            Some(file) => {
+                write(file, text)?;
+                Ok(None)
+            }
+            None => Ok(Some(text)),
+        }
+    }
+
+    fn run(&self) -> PyResult<Option<String>> {
+        let labels = Labels::global();
+        let tree = TreeFormat {
+            labels: Some(labels.clone()),
+            max_depth: self.depth,
+            max_items: None,
+        };
+        match self.command.as_str() {
+            "help" => Ok(Some(format!("{}\n", USAGE))),
+            "dump" => {
+                let [file] = self.expect()?;
+                self.emit(tree.text(&open_lazy(file)?)?)
+            }
+            "get" => {
+                let [file, path] = self.expect()?;
+                let root = open_lazy(file)?;
+                let param = root.get_path(&ParamPath::parse(path, &labels)?, &labels)?;
+                let text = match &param.lock()?[param.id()] {
+                    ParamType::List(_) | ParamType::Struct(_) => None,
+                    value => Some(tree.value_text(value)),
+                };
+                let text = match text {
+                    Some(text) => text,
+                    None => tree.text(&param)?,
+                };
+                self.emit(text)
+            }
+            "set" => {
+                let [file, path, value] = self.expect()?;
+                let output = self.output()?;
+                let root = Param::open(file)?;
+                let target = root.get_path(&ParamPath::parse(path, &labels)?, &labels)?;
+                let kind = target.type_number();
+                let parsed = parse_value(kind, value, &labels.inner.lock().unwrap())
+                    .map_err(PyValueError::new_err)?;
+                target.set_draft(Draft::Kind(parsed))?;
+                root.save_with(output, &SaveOptions::default())
+                    .map(|_| None)
+            }
+            "to-xml" => {
+                let [file] = self.expect()?;
+                let root = Param::open(file)?;
+                match &self.output {
+                    Some(output) => root.to_xml(output, None).map(|_| None),
+                    None => {
+                        let ParamKind::Struct(root) = ParamKind::try_from(&root)? else {
+                            unreachable!("param files always have a struct root")
+                        };
+                        let mut text = vec![];
+                        xml::write_xml(&root, &labels.inner.lock().unwrap(), &mut text)?;
+                        self.emit(String::from_utf8_lossy(&text).into_owned())
+                    }
+                }
+            }
+            "from-xml" => {
+                let [file] = self.expect()?;
+                let output = self.output()?;
+                Param::from_xml(file, None)?
+                    .save_with(output, &SaveOptions::default())
+                    .map(|_| None)
+            }
+            "diff" => {
+                let [a, b] = self.expect()?;
+                let a = ParamKind::try_from(&Param::open(a)?)?;
+                let b = ParamKind::try_from(&Param::open(b)?)?;
+                let keys = self
+                    .keys
+                    .iter()
+                    .map(|key| labels.hash(key).map(|hash| hash.inner))
+                    .collect::<PyResult<Vec<_>>>()?;
+                self.emit(changes_to_json(&diff(&a, &b, &keys))?)
+            }
+            "patch" => {
+                let [file, patch] = self.expect()?;
+                let output = self.output()?;
+                let root = Param::open(file)?;
+                for change in changes_from_json(&read_to_string(patch)?)? {
+                    root.apply_change(&change, &labels)?;
+                }
+                root.save_with(output, &SaveOptions::default())
+                    .map(|_| None)
+            }
+            other => Err(PyValueError::new_err(format!(
+                "unknown command '{}'\n\n{}",
+                other, USAGE
+            ))),
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use crate::duplicate::Duplicate;
+use crate::kind::kind_name;
+use crate::tree::Draft;
+use crate::{Hash, Param, ParamType};
+use prc::hash40::label_map::LabelMap;
+use prc::hash40::Hash40;
+use prc::ParamKind;
+use pyo3::exceptions::{PyOverflowError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Rounding {
+    Floor,
+    Ceil,
+    /// Halves are rounded away from zero
+    Round,
+    Trunc,
+}
+
+impl<'py> FromPyObject<'py> for Rounding {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match ob.extract::<String>()?.as_str() {
+            "floor" => Ok(Rounding::Floor),
+            "ceil" => Ok(Rounding::Ceil),
+            "round" => Ok(Rounding::Round),
+            "trunc" => Ok(Rounding::Trunc),
+            other => Err(PyValueError::new_err(format!(
+                "Unknown rounding mode '{}'. Expected one of 'floor', 'ceil', 'round', or 'trunc'",
+                other
+            ))),
+        }
+    }
+}
+
+impl Rounding {
+    fn apply(self, value: f64) -> f64 {
+        match self {
+            Rounding::Floor => value.floor(),
+            Rounding::Ceil => value.ceil(),
+            Rounding::Round => value.round(),
+            Rounding::Trunc => value.trunc(),
+        }
+    }
+}
+
+/// Widened so it can be converted into any other number type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl ParamType {
+    pub(crate) fn number(&self) -> Option<Number> {
+        match self {
+            ParamType::Bool(v) => Some(Number::Int(*v as i64)),
+            ParamType::I8(v) => Some(Number::Int(*v as i64)),
+            ParamType::U8(v) => Some(Number::Int(*v as i64)),
+            ParamType::I16(v) => Some(Number::Int(*v as i64)),
+            ParamType::U16(v) => Some(Number::Int(*v as i64)),
+            ParamType::I32(v) => Some(Number::Int(*v as i64)),
+            ParamType::U32(v) => Some(Number::Int(*v as i64)),
+            ParamType::Float(v) => Some(Number::Float(*v as f64)),
+            _ => None,
+        }
+    }
+
+    /// Floats are only converted to integers when they're whole, or a rounding mode is given
+    pub(crate) fn convert(&self, kind: u8, rounding: Option<Rounding>) -> PyResult<ParamType> {
+        if kind == self.type_number() {
+            return Ok(self.duplicate());
+        }
+        let number = self.number().ok_or_else(|| {
+            PyTypeError::new_err(format!(
+                "Cannot convert a {} param into {}; only numeric params can be converted",
+                kind_name(self.type_number()),
+                kind_name(kind)
+            ))
+        })?;
+        number.to_param(kind, rounding)
+    }
+}
+
+impl Number {
+    pub(crate) fn to_param(self, kind: u8, rounding: Option<Rounding>) -> PyResult<ParamType> {
+        if kind == 8 {
+            return match self {
+                Number::Int(v) if v as f32 as i128 != v as i128 => Err(PyOverflowError::new_err(
+                    format!("Value {} can't be stored exactly as a float", v),
+                )),
+                Number::Int(v) => Ok(ParamType::Float(v as f32)),
+                Number::Float(v) => Ok(ParamType::Float(v as f32)),
+            };
+        }
+
+        let int = match self {
+            Number::Int(v) => v,
+            Number::Float(v) => {
+                let rounded = match rounding {
+                    Some(rounding) => rounding.apply(v),
+                    None if v.fract() == 0.0 => v,
+                    None => {
+                        return Err(PyValueError::new_err(format!(
+                            "Cannot convert {} into {} without a rounding mode",
+                            v,
+                            kind_name(kind)
+                        )))
+                    }
+                };
+                if !rounded.is_finite() || rounded < i64::MIN as f64 || rounded > i64::MAX as f64 {
+                    return Err(out_of_range(rounded, kind));
+                }
+                rounded as i64
+            }
+        };
+
+        macro_rules! narrow {
+            ($id:ident) => {
+                int.try_into()
+                    .map(ParamType::$id)
+                    .map_err(|_| out_of_range(int, kind))
+            };
+        }
+        match kind {
+            1 => match int {
+                0 | 1 => Ok(ParamType::Bool(int == 1)),
+                _ => Err(out_of_range(int, kind)),
+            },
+            2 => narrow!(I8),
+            3 => narrow!(U8),
+            4 => narrow!(I16),
+            5 => narrow!(U16),
+            6 => narrow!(I32),
+            7 => narrow!(U32),
+            _ => Err(PyTypeError::new_err(format!(
+                "Cannot convert a numeric param into {}",
+                kind_name(kind)
+            ))),
+        }
+    }
+}
+
+/// Accepts the same values as the static constructor for that type
+pub(crate) fn from_value(kind: u8, value: &Bound<'_, PyAny>) -> PyResult<Draft> {
+    Ok(Draft::Kind(match kind {
+        1 => ParamKind::Bool(value.extract()?),
+        2 => ParamKind::I8(value.extract()?),
+        3 => ParamKind::U8(value.extract()?),
+        4 => ParamKind::I16(value.extract()?),
+        5 => ParamKind::U16(value.extract()?),
+        6 => ParamKind::I32(value.extract()?),
+        7 => ParamKind::U32(value.extract()?),
+        8 if value.is_instance_of::<PyInt>() && !value.is_instance_of::<PyBool>() => {
+            let int = value.extract().map_err(|_| out_of_range(value, kind))?;
+            let ParamType::Float(v) = Number::Int(int).to_param(kind, None)? else {
+                unreachable!("numbers are converted to the type asked for")
+            };
+            ParamKind::Float(v)
+        }
+        8 => ParamKind::Float(value.extract()?),
+        9 => ParamKind::Hash(value.extract::<Hash>()?.inner),
+        10 => ParamKind::Str(value.extract()?),
+        11 => {
+            let list: Vec<PyRef<Param>> = value.extract()?;
+            return Ok(Draft::List(
+                list.iter().map(|p| Draft::Shared(p.clone_ref())).collect(),
+            ));
+        }
+        _ => {
+            let fields: Vec<(Hash, PyRef<Param>)> = value.extract()?;
+            return Ok(Draft::Struct(
+                fields
+                    .iter()
+                    .map(|(h, p)| (h.inner, Draft::Shared(p.clone_ref())))
+                    .collect(),
+            ));
+        }
+    }))
+}
+
+/// Lists become list params, and dicts become struct params
+pub(crate) fn infer(value: &Bound<'_, PyAny>) -> PyResult<Draft> {
+    if let Ok(p) = value.downcast::<Param>() {
+        Ok(Draft::Kind(ParamKind::try_from(&*p.borrow())?))
+    } else if let Ok(v) = value.downcast::<PyBool>() {
+        Ok(Draft::Kind(ParamKind::Bool(v.is_true())))
+    } else if value.is_instance_of::<PyInt>() {
+        let int: i64 = value
+            .extract()
+            .map_err(|_| PyOverflowError::new_err("Integer is too large for any param type"))?;
+        infer_int(int).map(Draft::Kind)
+    } else if value.is_instance_of::<PyFloat>() {
+        Ok(Draft::Kind(ParamKind::Float(value.extract()?)))
+    } else if value.is_instance_of::<PyString>() {
+        Ok(Draft::Kind(ParamKind::Str(value.extract()?)))
+    } else if let Ok(v) = value.downcast::<Hash>() {
+        Ok(Draft::Kind(ParamKind::Hash(v.borrow().inner)))
+    } else if value.is_instance_of::<PyList>() || value.is_instance_of::<PyTuple>() {
+        let list = value
+            .try_iter()?
+            .map(|item| infer_child(&item?))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(Draft::List(list))
+    } else if let Ok(dict) = value.downcast::<PyDict>() {
+        let fields = dict
+            .iter()
+            .map(|(k, v)| Ok((k.extract::<Hash>()?.inner, infer_child(&v)?)))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(Draft::Struct(fields))
+    } else {
+        Err(PyTypeError::new_err(format!(
+            "Cannot infer a param type for a value of type '{}'",
+            value.get_type().name()?
+        )))
+    }
+}
+
+/// Children which are already params are shared, the same as in `param.list` and `param.struct`
+fn infer_child(value: &Bound<'_, PyAny>) -> PyResult<Draft> {
+    match value.downcast::<Param>() {
+        Ok(p) => Ok(Draft::Shared(p.borrow().clone_ref())),
+        Err(_) => infer(value),
+    }
+}
+
+/// Unsigned types are preferred
+pub(crate) fn infer_int(int: i64) -> PyResult<ParamKind> {
+    if let Ok(v) = u8::try_from(int) {
+        Ok(ParamKind::U8(v))
+    } else if let Ok(v) = i8::try_from(int) {
+        Ok(ParamKind::I8(v))
+    } else if let Ok(v) = u16::try_from(int) {
+        Ok(ParamKind::U16(v))
+    } else if let Ok(v) = i16::try_from(int) {
+        Ok(ParamKind::I16(v))
+    } else if let Ok(v) = u32::try_from(int) {
+        Ok(ParamKind::U32(v))
+    } else if let Ok(v) = i32::try_from(int) {
+        Ok(ParamKind::I32(v))
+    } else {
+        Err(PyOverflowError::new_err(format!(
+            "Integer {} is too large for any param type",
+            int
+        )))
+    }
+}
+
+fn out_of_range<T: std::fmt::Display>(value: T, kind: u8) -> PyErr {
+    PyOverflowError::new_err(format!(
+        "Value {} is out of range for {}",
+        value,
+        kind_name(kind)
+    ))
+}
+
+/// Hashes may be labels or hexadecimal values
+pub(crate) fn parse_value(kind: u8, text: &str, labels: &LabelMap) -> Result<ParamKind, String> {
+    macro_rules! parse {
+        ($id:ident) => {
+            text.trim()
+                .parse()
+                .map(ParamKind::$id)
+                .map_err(|_| format!("Could not parse '{}' as {}", text, kind_name(kind)))
+        };
+    }
+    match kind {
+        1 => parse!(Bool),
+        2 => parse!(I8),
+        3 => parse!(U8),
+        4 => parse!(I16),
+        5 => parse!(U16),
+        6 => parse!(I32),
+        7 => parse!(U32),
+        8 => parse!(Float),
+        9 => Hash40::from_hex_str(text)
+            .ok()
+            .or_else(|| labels.hash_of(text))
+            .map(ParamKind::Hash)
+            .ok_or_else(|| format!("Could not convert '{}' into a hash", text)),
+        10 => Ok(ParamKind::Str(text.to_string())),
+        _ => Err(format!(
+            "Cannot parse a {} param from text",
+            kind_name(kind)
+        )),
+    }
+}
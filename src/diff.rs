@@ -0,0 +1,297 @@
+use crate::labels::Labels;
+use crate::path::{missing, Key, KeyValue, ParamPath};
+use crate::tree::Draft;
+use crate::Param;
+use prc::hash40::Hash40;
+use prc::{ParamKind, ParamList};
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+
+/// A change without an old value adds a new param, and one without a new value removes it
+#[derive(Debug, Clone)]
+pub(crate) struct Change {
+    pub(crate) path: ParamPath,
+    pub(crate) old: Option<ParamKind>,
+    pub(crate) new: Option<ParamKind>,
+}
+
+/// The path is read once the label map is known
+pub(crate) type PyChange<'py> = (
+    Bound<'py, PyAny>,
+    Option<PyRef<'py, Param>>,
+    Option<PyRef<'py, Param>>,
+);
+
+/// Applying the changes in order reproduces `b`
+pub(crate) fn diff(a: &ParamKind, b: &ParamKind, key_fields: &[Hash40]) -> Vec<Change> {
+    let mut changes = vec![];
+    diff_into(&ParamPath::default(), a, b, key_fields, &mut changes);
+    changes
+}
+
+fn diff_into(
+    path: &ParamPath,
+    a: &ParamKind,
+    b: &ParamKind,
+    key_fields: &[Hash40],
+    changes: &mut Vec<Change>,
+) {
+    match (a, b) {
+        (ParamKind::List(a), ParamKind::List(b)) => {
+            if let Some(field) = key_field(&[a, b], key_fields) {
+                return diff_keyed(path, field, a, b, key_fields, changes);
+            }
+            let common = a.0.len().min(b.0.len());
+            for index in 0..common {
+                diff_into(
+                    &path.push(Key::Index(index)),
+                    &a.0[index],
+                    &b.0[index],
+                    key_fields,
+                    changes,
+                );
+            }
+            for (index, added) in b.0.iter().enumerate().skip(common) {
+                changes.push(Change {
+                    path: path.push(Key::Index(index)),
+                    old: None,
+                    new: Some(added.clone()),
+                });
+            }
+            // removed from the back, so that earlier indices stay valid
+            for (index, removed) in a.0.iter().enumerate().skip(common).rev() {
+                changes.push(Change {
+                    path: path.push(Key::Index(index)),
+                    old: Some(removed.clone()),
+                    new: None,
+                });
+            }
+        }
+        (ParamKind::Struct(a), ParamKind::Struct(b)) => {
+            for (hash, old) in &a.0 {
+                let key = path.push(Key::Hash(*hash));
+                match b.0.iter().find(|(h, _)| h == hash) {
+                    Some((_, new)) => diff_into(&key, old, new, key_fields, changes),
+                    None => changes.push(Change {
+                        path: key,
+                        old: Some(old.clone()),
+                        new: None,
+                    }),
+                }
+            }
+            for (hash, new) in &b.0 {
+                if !a.0.iter().any(|(h, _)| h == hash) {
+                    changes.push(Change {
+                        path: path.push(Key::Hash(*hash)),
+                        old: None,
+                        new: Some(new.clone()),
+                    });
+                }
+            }
+        }
+        _ if same_value(a, b) => {}
+        _ => changes.push(Change {
+            path: path.clone(),
+            old: Some(a.clone()),
+            new: Some(b.clone()),
+        }),
+    }
+}
+
+/// Elements which moved are removed and added again at their new index, keeping as many as
+/// possible in place
+fn diff_keyed(
+    path: &ParamPath,
+    field: Hash40,
+    a: &ParamList,
+    b: &ParamList,
+    key_fields: &[Hash40],
+    changes: &mut Vec<Change>,
+) {
+    let keys = |list: &ParamList| {
+        list.0
+            .iter()
+            .map(|elem| key_value(elem, field).expect("every element has the key field"))
+            .collect::<Vec<_>>()
+    };
+    let (a_keys, b_keys) = (keys(a), keys(b));
+    let b_positions = b_keys
+        .iter()
+        .enumerate()
+        .map(|(index, key)| (key, index))
+        .collect::<HashMap<_, _>>();
+    let positions = a_keys
+        .iter()
+        .map(|key| b_positions.get(key).copied())
+        .collect::<Vec<_>>();
+    let kept = longest_increasing(&positions);
+
+    for (index, old) in a.0.iter().enumerate() {
+        let key = path.push(Key::Field(field, a_keys[index].clone()));
+        match positions[index] {
+            Some(position) if kept[index] => {
+                diff_into(&key, old, &b.0[position], key_fields, changes)
+            }
+            _ => changes.push(Change {
+                path: key,
+                old: Some(old.clone()),
+                new: None,
+            }),
+        }
+    }
+    let in_place = (0..a.0.len())
+        .filter(|&index| kept[index])
+        .filter_map(|index| positions[index])
+        .collect::<HashSet<_>>();
+    for (index, new) in b.0.iter().enumerate() {
+        if !in_place.contains(&index) {
+            changes.push(Change {
+                path: path.push(Key::Index(index)),
+                old: None,
+                new: Some(new.clone()),
+            });
+        }
+    }
+}
+
+fn longest_increasing(positions: &[Option<usize>]) -> Vec<bool> {
+    // the last index of the best sequence of each length found so far
+    let mut tails: Vec<usize> = vec![];
+    let mut previous = vec![None; positions.len()];
+    for (index, position) in positions.iter().enumerate() {
+        let Some(position) = position else {
+            continue;
+        };
+        let length = tails.partition_point(|&tail| positions[tail] < Some(*position));
+        if length > 0 {
+            previous[index] = Some(tails[length - 1]);
+        }
+        match tails.get_mut(length) {
+            Some(tail) => *tail = index,
+            None => tails.push(index),
+        }
+    }
+    let mut kept = vec![false; positions.len()];
+    let mut next = tails.last().copied();
+    while let Some(index) = next {
+        kept[index] = true;
+        next = previous[index];
+    }
+    kept
+}
+
+/// NaN floats with the same bits are equal
+pub(crate) fn same_value(a: &ParamKind, b: &ParamKind) -> bool {
+    match (a, b) {
+        (ParamKind::Float(a), ParamKind::Float(b)) => a == b || a.to_bits() == b.to_bits(),
+        (ParamKind::List(a), ParamKind::List(b)) => {
+            a.0.len() == b.0.len() && a.0.iter().zip(&b.0).all(|(a, b)| same_value(a, b))
+        }
+        (ParamKind::Struct(a), ParamKind::Struct(b)) => {
+            a.0.len() == b.0.len()
+                && a.0
+                    .iter()
+                    .zip(&b.0)
+                    .all(|((ha, a), (hb, b))| ha == hb && same_value(a, b))
+        }
+        _ => a == b,
+    }
+}
+
+/// Every element has to be a struct with the field, and no two elements can have the same value
+/// for it
+pub(crate) fn key_field(lists: &[&ParamList], fields: &[Hash40]) -> Option<Hash40> {
+    if lists.iter().all(|list| list.0.is_empty()) {
+        return None;
+    }
+    fields.iter().copied().find(|field| {
+        lists.iter().all(|list| {
+            let mut seen = HashSet::new();
+            list.0
+                .iter()
+                .all(|elem| key_value(elem, *field).is_some_and(|key| seen.insert(key)))
+        })
+    })
+}
+
+pub(crate) fn key_value(elem: &ParamKind, field: Hash40) -> Option<KeyValue> {
+    match elem {
+        ParamKind::Struct(s) => {
+            s.0.iter()
+                .find(|(h, _)| *h == field)
+                .and_then(|(_, v)| KeyValue::of(v))
+        }
+        _ => None,
+    }
+}
+
+impl Param {
+    pub(crate) fn apply_change(&self, change: &Change, labels: &Labels) -> PyResult<()> {
+        let Some((last, parents)) = change.path.0.split_last() else {
+            return match &change.new {
+                Some(new) => self.set_draft(Draft::Kind(new.clone())),
+                None => Err(PyValueError::new_err("Cannot remove the root param")),
+            };
+        };
+        let parent = self.get_path(&ParamPath(parents.to_vec()), labels)?;
+        match (&change.old, &change.new) {
+            (Some(_), Some(new)) => {
+                if parent.replace_child(last, Draft::Kind(new.clone()), labels)? {
+                    Ok(())
+                } else {
+                    Err(missing(&change.path, parents.len(), labels))
+                }
+            }
+            (None, Some(new)) => parent
+                .insert_child(last, Draft::Kind(new.clone()))
+                .map(|_| ()),
+            (Some(_), None) => {
+                if parent.remove_child(last, labels)? {
+                    Ok(())
+                } else {
+                    Err(missing(&change.path, parents.len(), labels))
+                }
+            }
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Hashes are written using the global labels, the same as in prc-rs
+pub(crate) fn changes_to_json(changes: &[Change]) -> PyResult<String> {
+    let records = changes
+        .iter()
+        .map(|change| {
+            Ok(json!({
+                "path": change.path.to_string(),
+                "old": serde_json::to_value(&change.old).map_err(json_error)?,
+                "new": serde_json::to_value(&change.new).map_err(json_error)?,
+            }))
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    serde_json::to_string_pretty(&records).map_err(json_error)
+}
+
+pub(crate) fn changes_from_json(text: &str) -> PyResult<Vec<Change>> {
+    let records: Vec<Value> = serde_json::from_str(text).map_err(json_error)?;
+    records
+        .into_iter()
+        .map(|mut record| {
+            let path = record["path"]
+                .as_str()
+                .ok_or_else(|| PyKeyError::new_err("Each change needs a 'path' string"))
+                .and_then(|path| ParamPath::parse(path, &Labels::global()))?;
+            Ok(Change {
+                path,
+                old: serde_json::from_value(record["old"].take()).map_err(json_error)?,
+                new: serde_json::from_value(record["new"].take()).map_err(json_error)?,
+            })
+        })
+        .collect()
+}
+
+fn json_error(e: serde_json::Error) -> PyErr {
+    PyValueError::new_err(format!("Invalid patch: {}", e))
+}
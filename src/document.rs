@@ -0,0 +1,441 @@
+use crate::convert::{infer_int, parse_value, Number};
+use crate::kind::{kind_name, KIND_NAMES};
+use crate::labels::format_label;
+use crate::search::kind_number;
+use crate::tree::Arena;
+use prc::hash40::label_map::LabelMap;
+use prc::hash40::Hash40;
+use prc::{ParamKind, ParamList, ParamStruct};
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use serde_yaml::value::{Tag, TaggedValue};
+
+/// A param tree as it's written in a text format such as YAML. Values only carry their param
+/// type when it can't be inferred
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Doc {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Tagged(u8, Box<Doc>),
+    List(Vec<Doc>),
+    Map(Vec<(String, Doc)>),
+}
+
+impl Doc {
+    pub(crate) fn describe(&self) -> &'static str {
+        match self {
+            Doc::Bool(_) => "a bool",
+            Doc::Int(_) => "an integer",
+            Doc::Float(_) => "a float",
+            Doc::Str(_) => "a string",
+            Doc::Tagged(..) => "a tagged value",
+            Doc::List(_) => "a list",
+            Doc::Map(_) => "a map",
+        }
+    }
+
+    /// Integers use the narrowest type which fits them, and strings are never hashes
+    fn inferred_kind(&self) -> PyResult<u8> {
+        match self {
+            Doc::Bool(_) => Ok(1),
+            Doc::Int(v) => infer_int(*v).map(|int| kind_number(&int)),
+            Doc::Float(_) => Ok(8),
+            Doc::Str(_) => Ok(10),
+            Doc::Tagged(kind, _) => Ok(*kind),
+            Doc::List(_) => Ok(11),
+            Doc::Map(_) => Ok(12),
+        }
+    }
+}
+
+fn tag_kind(tag: &str) -> PyResult<u8> {
+    KIND_NAMES[..10]
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(tag))
+        .map(|index| index as u8 + 1)
+        .ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "Unknown tag '!{}'; expected a scalar param type such as '!u8' or '!hash'",
+                tag
+            ))
+        })
+}
+
+/// Elements past the end of the template use its first element
+fn element_template(template: Option<&ParamKind>, index: usize) -> Option<&ParamKind> {
+    match template {
+        Some(ParamKind::List(v)) => v.0.get(index).or(v.0.first()),
+        _ => None,
+    }
+}
+
+fn field_template(template: Option<&ParamKind>, hash: Hash40) -> Option<&ParamKind> {
+    match template {
+        Some(ParamKind::Struct(v)) => v.0.iter().find(|(h, _)| *h == hash).map(|(_, p)| p),
+        _ => None,
+    }
+}
+
+fn expected_kind(doc: &Doc, template: Option<&ParamKind>) -> PyResult<u8> {
+    match template.map(kind_number) {
+        Some(kind) if kind <= 10 => Ok(kind),
+        _ => doc.inferred_kind(),
+    }
+}
+
+pub(crate) fn to_doc(
+    param: &ParamKind,
+    template: Option<&ParamKind>,
+    labels: &LabelMap,
+) -> PyResult<Doc> {
+    let doc = match param {
+        ParamKind::Bool(v) => Doc::Bool(*v),
+        ParamKind::I8(v) => Doc::Int(*v as i64),
+        ParamKind::U8(v) => Doc::Int(*v as i64),
+        ParamKind::I16(v) => Doc::Int(*v as i64),
+        ParamKind::U16(v) => Doc::Int(*v as i64),
+        ParamKind::I32(v) => Doc::Int(*v as i64),
+        ParamKind::U32(v) => Doc::Int(*v as i64),
+        // go through the shortest text of the float, so 1.1 isn't written as 1.100000023841858
+        ParamKind::Float(v) => Doc::Float(v.to_string().parse().expect("floats parse as f64")),
+        ParamKind::Hash(v) => Doc::Str(format_label(labels, *v)),
+        ParamKind::Str(v) => Doc::Str(v.clone()),
+        ParamKind::List(v) => {
+            let elems =
+                v.0.iter()
+                    .enumerate()
+                    .map(|(index, elem)| to_doc(elem, element_template(template, index), labels))
+                    .collect::<PyResult<_>>()?;
+            return Ok(Doc::List(elems));
+        }
+        ParamKind::Struct(v) => {
+            let mut fields: Vec<(String, Doc)> = Vec::with_capacity(v.0.len());
+            for (hash, field) in &v.0 {
+                let key = format_label(labels, *hash);
+                if fields.iter().any(|(k, _)| *k == key) {
+                    return Err(PyValueError::new_err(format!(
+                        "Cannot write a struct with more than one '{}' key",
+                        key
+                    )));
+                }
+                let value = to_doc(field, field_template(template, *hash), labels)?;
+                fields.push((key, value));
+            }
+            return Ok(Doc::Map(fields));
+        }
+    };
+    let kind = kind_number(param);
+    match expected_kind(&doc, template) {
+        Ok(expected) if expected == kind => Ok(doc),
+        _ => Ok(Doc::Tagged(kind, Box::new(doc))),
+    }
+}
+
+pub(crate) fn from_doc(
+    doc: &Doc,
+    template: Option<&ParamKind>,
+    labels: &LabelMap,
+) -> PyResult<ParamKind> {
+    let mut reader = DocReader {
+        labels,
+        path: vec![],
+    };
+    reader
+        .read(doc, template)
+        .map_err(|e| locate(e, &reader.path))
+}
+
+pub(crate) fn locate(e: PyErr, path: &[String]) -> PyErr {
+    if path.is_empty() {
+        return e;
+    }
+    Python::with_gil(|py| {
+        let message = format!("{} (at '{}')", e.value(py), path.join("/"));
+        PyErr::from_type(e.get_type(py), message)
+    })
+}
+
+pub(crate) fn key_hash(key: &str, labels: &LabelMap) -> PyResult<Hash40> {
+    Hash40::from_hex_str(key)
+        .ok()
+        .or_else(|| labels.hash_of(key))
+        .ok_or_else(|| PyValueError::new_err(format!("Could not convert '{}' into a hash", key)))
+}
+
+/// Numbers are converted the same way as `convert`, without rounding
+pub(crate) fn scalar_param(kind: u8, doc: &Doc, labels: &LabelMap) -> PyResult<ParamKind> {
+    let number = match (kind, doc) {
+        (1, Doc::Bool(v)) => return Ok(ParamKind::Bool(*v)),
+        (1..=8, Doc::Int(v)) => Number::Int(*v),
+        (1..=8, Doc::Float(v)) => Number::Float(*v),
+        (9 | 10, Doc::Str(v)) => {
+            return parse_value(kind, v, labels).map_err(PyValueError::new_err)
+        }
+        _ => {
+            return Err(PyTypeError::new_err(format!(
+                "Expected a {} value, found {}",
+                kind_name(kind),
+                doc.describe()
+            )))
+        }
+    };
+    let value = number.to_param(kind, None)?;
+    Arena::default().param_kind(&value)
+}
+
+/// The path is left as it was when an error happens
+struct DocReader<'a> {
+    labels: &'a LabelMap,
+    path: Vec<String>,
+}
+
+impl DocReader<'_> {
+    fn read(&mut self, doc: &Doc, template: Option<&ParamKind>) -> PyResult<ParamKind> {
+        match doc {
+            Doc::Tagged(kind, value) => scalar_param(*kind, value, self.labels),
+            Doc::List(elems) => {
+                let elems = elems
+                    .iter()
+                    .enumerate()
+                    .map(|(index, elem)| {
+                        self.path.push(index.to_string());
+                        let param = self.read(elem, element_template(template, index))?;
+                        self.path.pop();
+                        Ok(param)
+                    })
+                    .collect::<PyResult<_>>()?;
+                Ok(ParamKind::List(ParamList(elems)))
+            }
+            Doc::Map(fields) => {
+                let mut fields = fields
+                    .iter()
+                    .map(|(key, value)| {
+                        self.path.push(key.clone());
+                        let hash = key_hash(key, self.labels)?;
+                        let param = self.read(value, field_template(template, hash))?;
+                        self.path.pop();
+                        Ok((hash, param))
+                    })
+                    .collect::<PyResult<Vec<_>>>()?;
+                // fields follow the order of the template, since TOML written by hand puts
+                // tables after the plain values
+                if let Some(ParamKind::Struct(v)) = template {
+                    fields.sort_by_key(|(hash, _)| {
+                        v.0.iter().position(|(h, _)| h == hash).unwrap_or(v.0.len())
+                    });
+                }
+                Ok(ParamKind::Struct(ParamStruct(fields)))
+            }
+            scalar => scalar_param(expected_kind(scalar, template)?, scalar, self.labels),
+        }
+    }
+}
+
+pub(crate) fn to_yaml(
+    param: &ParamKind,
+    template: Option<&ParamKind>,
+    labels: &LabelMap,
+) -> PyResult<String> {
+    let value = yaml_value(to_doc(param, template, labels)?);
+    serde_yaml::to_string(&value).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+pub(crate) fn from_yaml(
+    text: &str,
+    template: Option<&ParamKind>,
+    labels: &LabelMap,
+) -> PyResult<ParamKind> {
+    from_doc(&parse_yaml(text)?, template, labels)
+}
+
+pub(crate) fn parse_yaml(text: &str) -> PyResult<Doc> {
+    let value = serde_yaml::from_str(text)
+        .map_err(|e| PyValueError::new_err(format!("Invalid YAML: {}", e)))?;
+    yaml_doc(value)
+}
+
+fn yaml_value(doc: Doc) -> serde_yaml::Value {
+    use serde_yaml::Value;
+    match doc {
+        Doc::Bool(v) => Value::Bool(v),
+        Doc::Int(v) => Value::Number(v.into()),
+        Doc::Float(v) => Value::Number(v.into()),
+        Doc::Str(v) => Value::String(v),
+        Doc::Tagged(kind, value) => Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new(kind_name(kind)),
+            value: yaml_value(*value),
+        })),
+        Doc::List(v) => Value::Sequence(v.into_iter().map(yaml_value).collect()),
+        Doc::Map(v) => Value::Mapping(
+            v.into_iter()
+                .map(|(key, value)| (Value::String(key), yaml_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Numbers and bools are allowed as keys, so that list indices can be written without quotes
+fn yaml_doc(value: serde_yaml::Value) -> PyResult<Doc> {
+    use serde_yaml::Value;
+    match value {
+        Value::Null => Err(PyValueError::new_err(
+            "YAML null values can't be made into params",
+        )),
+        Value::Bool(v) => Ok(Doc::Bool(v)),
+        Value::Number(v) => match v.as_i64() {
+            Some(int) => Ok(Doc::Int(int)),
+            None if v.is_u64() => Err(PyValueError::new_err(format!(
+                "Integer {} is too large for any param type",
+                v
+            ))),
+            None => Ok(Doc::Float(v.as_f64().expect("the number is a float"))),
+        },
+        Value::String(v) => Ok(Doc::Str(v)),
+        Value::Sequence(v) => Ok(Doc::List(
+            v.into_iter().map(yaml_doc).collect::<PyResult<_>>()?,
+        )),
+        Value::Mapping(v) => {
+            let fields = v
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        Value::String(key) => key,
+                        Value::Number(key) => key.to_string(),
+                        Value::Bool(key) => key.to_string(),
+                        _ => {
+                            return Err(PyValueError::new_err(
+                                "YAML keys must be strings or numbers",
+                            ))
+                        }
+                    };
+                    Ok((key, yaml_doc(value)?))
+                })
+                .collect::<PyResult<_>>()?;
+            Ok(Doc::Map(fields))
+        }
+        Value::Tagged(v) => {
+            let tag = v.tag.to_string();
+            let kind = tag_kind(tag.trim_start_matches('!'))?;
+            Ok(Doc::Tagged(kind, Box::new(yaml_doc(v.value)?)))
+        }
+    }
+}
+
+/// TOML has no tags, so tagged values are written as a table with a single key
+pub(crate) fn to_toml(
+    param: &ParamKind,
+    template: Option<&ParamKind>,
+    labels: &LabelMap,
+) -> PyResult<String> {
+    let Doc::Map(fields) = to_doc(param, template, labels)? else {
+        return Err(PyTypeError::new_err(
+            "Only struct-type params can be written as TOML",
+        ));
+    };
+    let mut document = toml_edit::DocumentMut::new();
+    *document.as_table_mut() = toml_table(fields);
+    Ok(document.to_string())
+}
+
+pub(crate) fn from_toml(
+    text: &str,
+    template: Option<&ParamKind>,
+    labels: &LabelMap,
+) -> PyResult<ParamKind> {
+    let table = text
+        .parse::<toml::Table>()
+        .map_err(|e| PyValueError::new_err(format!("Invalid TOML: {}", e)))?;
+    from_doc(&toml_doc(toml::Value::Table(table))?, template, labels)
+}
+
+fn is_table(doc: &Doc) -> bool {
+    match doc {
+        Doc::Map(_) => true,
+        Doc::List(v) => !v.is_empty() && v.iter().all(|elem| matches!(elem, Doc::Map(_))),
+        _ => false,
+    }
+}
+
+/// The values of a table are always written before its sub-tables, so sub-tables which come
+/// before a value are written inline instead
+fn toml_table(fields: Vec<(String, Doc)>) -> toml_edit::Table {
+    use toml_edit::{ArrayOfTables, Item};
+    let first_table = fields
+        .iter()
+        .rposition(|(_, doc)| !is_table(doc))
+        .map_or(0, |index| index + 1);
+    let mut table = toml_edit::Table::new();
+    for (index, (key, doc)) in fields.into_iter().enumerate() {
+        let item = match doc {
+            doc if index < first_table || !is_table(&doc) => Item::Value(toml_value(doc)),
+            Doc::Map(fields) => Item::Table(toml_table(fields)),
+            Doc::List(elems) => {
+                let mut tables = ArrayOfTables::new();
+                for elem in elems {
+                    let Doc::Map(fields) = elem else {
+                        unreachable!("every element is a table")
+                    };
+                    tables.push(toml_table(fields));
+                }
+                Item::ArrayOfTables(tables)
+            }
+            _ => unreachable!("only maps and lists are tables"),
+        };
+        table.insert(&key, item);
+    }
+    table
+}
+
+fn toml_value(doc: Doc) -> toml_edit::Value {
+    use toml_edit::{InlineTable, Value};
+    match doc {
+        Doc::Bool(v) => Value::from(v),
+        Doc::Int(v) => Value::from(v),
+        Doc::Float(v) => Value::from(v),
+        Doc::Str(v) => Value::from(v),
+        Doc::Tagged(kind, value) => {
+            let mut table = InlineTable::new();
+            table.insert(format!("!{}", kind_name(kind)), toml_value(*value));
+            Value::InlineTable(table)
+        }
+        Doc::List(v) => Value::Array(v.into_iter().map(toml_value).collect()),
+        Doc::Map(v) => Value::InlineTable(
+            v.into_iter()
+                .map(|(key, value)| (key, toml_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn toml_doc(value: toml::Value) -> PyResult<Doc> {
+    use toml::Value;
+    match value {
+        Value::Boolean(v) => Ok(Doc::Bool(v)),
+        Value::Integer(v) => Ok(Doc::Int(v)),
+        Value::Float(v) => Ok(Doc::Float(v)),
+        Value::String(v) => Ok(Doc::Str(v)),
+        Value::Datetime(v) => Err(PyValueError::new_err(format!(
+            "TOML dates can't be made into params, found {}",
+            v
+        ))),
+        Value::Array(v) => Ok(Doc::List(
+            v.into_iter().map(toml_doc).collect::<PyResult<_>>()?,
+        )),
+        Value::Table(v) => {
+            if v.len() == 1 {
+                let (key, value) = v.iter().next().expect("the table has one key");
+                if let Some(tag) = key.strip_prefix('!') {
+                    let kind = tag_kind(tag)?;
+                    return Ok(Doc::Tagged(kind, Box::new(toml_doc(value.clone())?)));
+                }
+            }
+            let fields = v
+                .into_iter()
+                .map(|(key, value)| Ok((key, toml_doc(value)?)))
+                .collect::<PyResult<_>>()?;
+            Ok(Doc::Map(fields))
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{Hash, ParamList2, ParamStruct2, ParamType};
+use crate::tree::Arena;
+use crate::{Hash, Param, ParamType};
 
 /// A fake clone implementation. PyO3 uses blanket implementations of FromPyObject for pyclasses
 /// when they implement Clone. I need to have custom implementations for some types
@@ -28,8 +29,9 @@ impl Duplicate for ParamType {
             ParamType::Float(v) => ParamType::Float(*v),
             ParamType::Hash(v) => ParamType::Hash(v.duplicate()),
             ParamType::Str(v) => ParamType::Str(v.clone()),
-            ParamType::List(v) => ParamType::List(v.duplicate()),
-            ParamType::Struct(v) => ParamType::Struct(v.duplicate()),
+            ParamType::List(v) => ParamType::List(v.clone()),
+            ParamType::Struct(v) => ParamType::Struct(v.clone()),
+            ParamType::Lazy(kind, offset) => ParamType::Lazy(*kind, *offset),
         }
     }
 }
@@ -40,19 +42,14 @@ impl Duplicate for Hash {
     }
 }
 
-impl Duplicate for ParamList2 {
+/// Params which haven't been read from their file yet are left to be read by the copy
+impl Duplicate for Param {
     fn duplicate(&self) -> Self {
-        ParamList2(self.0.duplicate())
-    }
-}
-
-impl Duplicate for ParamStruct2 {
-    fn duplicate(&self) -> Self {
-        ParamStruct2(
-            self.0
-                .iter()
-                .map(|(h, p)| (h.duplicate(), p.duplicate()))
-                .collect(),
-        )
+        let source = self.lock_unread();
+        let mut arena = Arena::default();
+        arena.file = source.file.clone();
+        let id = source.copy_to(self.id(), &mut arena);
+        drop(source);
+        Param::new_tree(arena, id)
     }
 }
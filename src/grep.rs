@@ -0,0 +1,116 @@
+use crate::kind::kind_object;
+use crate::labels::Labels;
+use crate::lazy::read_kind;
+use crate::path::ParamPath;
+use crate::search::Query;
+use pyo3::conversion::IntoPyObjectExt;
+use pyo3::prelude::*;
+use rayon::prelude::*;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use walkdir::WalkDir;
+
+fn prc_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| match entry {
+            Ok(entry) => {
+                let is_prc = entry.file_type().is_file()
+                    && entry
+                        .path()
+                        .extension()
+                        .is_some_and(|ext| ext.eq_ignore_ascii_case("prc"));
+                is_prc.then(|| Ok(entry.into_path()))
+            }
+            Err(e) => Some(Err(e)),
+        })
+        .collect::<Result<_, _>>()
+        .map_err(io::Error::from)
+}
+
+/// Files are searched in parallel a batch at a time, as the matches are needed
+#[pyclass(module = "pyprc")]
+pub(crate) struct GrepIter {
+    query: Query,
+    files: std::vec::IntoIter<PathBuf>,
+    /// Kept in reverse, so that they can be popped off the end
+    found: Vec<(Arc<PathBuf>, ParamPath, u8)>,
+}
+
+impl GrepIter {
+    /// Files which can't be read as param files are skipped
+    fn search_batch(&mut self, py: Python) -> bool {
+        let batch = self
+            .files
+            .by_ref()
+            .take(rayon::current_num_threads() * 4)
+            .collect::<Vec<_>>();
+        if batch.is_empty() {
+            return false;
+        }
+        let query = &self.query;
+        let found = py.allow_threads(|| {
+            batch
+                .into_par_iter()
+                .filter_map(|file| {
+                    let root = read_kind(&file).ok()?;
+                    Some((Arc::new(file), query.search(&root)))
+                })
+                .collect::<Vec<_>>()
+        });
+        self.found = found
+            .into_iter()
+            .flat_map(|(file, found)| {
+                found
+                    .into_iter()
+                    .map(move |(path, kind)| (file.clone(), path, kind))
+            })
+            .collect();
+        self.found.reverse();
+        true
+    }
+}
+
+#[pymethods]
+impl GrepIter {
+    fn __iter__(this: PyRef<Self>) -> PyRef<Self> {
+        this
+    }
+
+    fn __next__(mut this: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        let py = this.py();
+        loop {
+            if let Some((file, path, kind)) = this.found.pop() {
+                let found = (
+                    file.as_os_str(),
+                    path.to_object(py)?,
+                    kind_object(py, kind)?,
+                );
+                return Ok(Some(found.into_py_any(py)?));
+            }
+            if !this.search_batch(py) {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// Only matching params are turned into python objects, so files can be searched in parallel
+#[pyfunction]
+#[pyo3(signature = (dir, value, kind=None, labels=None))]
+pub(crate) fn grep(
+    dir: PathBuf,
+    value: &Bound<'_, PyAny>,
+    kind: Option<&Bound<'_, PyAny>>,
+    labels: Option<Labels>,
+) -> PyResult<GrepIter> {
+    let query = Query::new(Some(value), kind, None, &Labels::or_global(labels.as_ref()))?;
+    let files = value.py().allow_threads(|| prc_files(&dir))?;
+    Ok(GrepIter {
+        query,
+        files: files.into_iter(),
+        found: vec![],
+    })
+}
@@ -0,0 +1,250 @@
+use crate::duplicate::Duplicate;
+use crate::observe;
+use crate::tree::{Arena, NodeId};
+use crate::{Param, ParamType};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use std::collections::VecDeque;
+
+#[derive(Debug)]
+struct Edit {
+    id: NodeId,
+    old: ParamType,
+    new: ParamType,
+}
+
+/// Each step is a group of edits which are undone together. The edits in an open group are
+/// always kept, so that they can be rolled back
+#[derive(Debug, Default)]
+pub(crate) struct History {
+    limit: Option<usize>,
+    undo: VecDeque<Vec<Edit>>,
+    redo: Vec<Vec<Edit>>,
+    pending: Vec<Edit>,
+    depth: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Savepoint {
+    owner: usize,
+    pending: usize,
+}
+
+impl Edit {
+    fn moved(self, moves: &[NodeId]) -> Edit {
+        Edit {
+            id: moves[self.id as usize],
+            old: self.old.moved(moves),
+            new: self.new.moved(moves),
+        }
+    }
+}
+
+impl History {
+    pub(crate) fn is_open(&self) -> bool {
+        self.depth > 0
+    }
+
+    pub(crate) fn roots(&self, roots: &mut Vec<NodeId>) {
+        let steps = self.undo.iter().chain(&self.redo);
+        for edit in steps.flatten().chain(&self.pending) {
+            roots.push(edit.id);
+            roots.extend(edit.old.children());
+            roots.extend(edit.new.children());
+        }
+    }
+
+    pub(crate) fn moved(self, moves: &[NodeId]) -> History {
+        let shift = |step: Vec<Edit>| step.into_iter().map(|e| e.moved(moves)).collect();
+        History {
+            limit: self.limit,
+            undo: self.undo.into_iter().map(shift).collect(),
+            redo: self.redo.into_iter().map(shift).collect(),
+            pending: shift(self.pending),
+            depth: self.depth,
+        }
+    }
+
+    pub(crate) fn keep(&mut self, limit: usize) {
+        self.limit = Some(limit);
+        self.trim();
+    }
+
+    fn end_step(&mut self) {
+        let step = std::mem::take(&mut self.pending);
+        if !step.is_empty() && self.limit.is_some() {
+            self.undo.push_back(step);
+            self.trim();
+        }
+    }
+
+    fn trim(&mut self) {
+        let limit = self.limit.unwrap_or(0);
+        while self.undo.len() > limit {
+            self.undo.pop_front();
+        }
+        self.redo.truncate(limit);
+    }
+}
+
+impl Arena {
+    /// Changes are also added to every open group, so that rolling a group back reverts them
+    pub(crate) fn record(&mut self, owner: usize, id: NodeId, old: ParamType) {
+        self.owner(owner);
+        let keeps = |index: usize, history: &History| {
+            history.depth > 0 || (index == owner && history.limit.is_some())
+        };
+        if !self
+            .owners
+            .iter()
+            .enumerate()
+            .any(|(i, o)| keeps(i, &o.history))
+        {
+            return;
+        }
+        let new = self[id].duplicate();
+        for (index, o) in self.owners.iter_mut().enumerate() {
+            let history = &mut o.history;
+            if keeps(index, history) {
+                history.redo.clear();
+                history.pending.push(Edit {
+                    id,
+                    old: old.duplicate(),
+                    new: new.duplicate(),
+                });
+                if history.depth == 0 {
+                    history.end_step();
+                }
+            }
+        }
+    }
+
+    /// Only the outermost group becomes a step in the undo history
+    pub(crate) fn begin(&mut self, owner: usize) -> Savepoint {
+        let history = &mut self.owner(owner).history;
+        history.depth += 1;
+        Savepoint {
+            owner,
+            pending: history.pending.len(),
+        }
+    }
+
+    pub(crate) fn end(&mut self, owner: usize) {
+        let history = &mut self.owner(owner).history;
+        history.depth -= 1;
+        if history.depth == 0 {
+            history.end_step();
+        }
+    }
+
+    /// The callbacks aren't told, since each param holds what it did before
+    pub(crate) fn rollback(&mut self, savepoint: Savepoint) {
+        let history = &mut self.owner(savepoint.owner).history;
+        let edits = history.pending.split_off(savepoint.pending);
+        for edit in edits.into_iter().rev() {
+            self.replace(edit.id, edit.old);
+        }
+    }
+
+    fn undo(&mut self, owner: usize) -> bool {
+        let Some(step) = self.owner(owner).history.undo.pop_back() else {
+            return false;
+        };
+        for edit in step.iter().rev() {
+            self.replace(edit.id, edit.old.duplicate());
+        }
+        self.owner(owner).history.redo.push(step);
+        true
+    }
+
+    fn redo(&mut self, owner: usize) -> bool {
+        let Some(step) = self.owner(owner).history.redo.pop() else {
+            return false;
+        };
+        for edit in &step {
+            self.replace(edit.id, edit.new.duplicate());
+        }
+        self.owner(owner).history.undo.push_back(step);
+        true
+    }
+}
+
+impl Param {
+    pub(crate) fn grouped<T>(&self, f: impl FnOnce() -> PyResult<T>) -> PyResult<T> {
+        self.tree().lock().begin(self.owner());
+        let result = f();
+        let mut arena = self.tree().lock();
+        arena.end(self.owner());
+        let events = arena.take_events();
+        drop(arena);
+        observe::fire(events);
+        result
+    }
+
+    pub(crate) fn step(&self, redo: bool) -> PyResult<bool> {
+        let owner = self.owner();
+        let mut arena = self.lock_mut()?;
+        let history = &arena.owner(owner).history;
+        if history.limit.is_none() {
+            return Err(PyValueError::new_err(
+                "The undo history isn't being kept; call track_history first",
+            ));
+        }
+        if history.depth > 0 {
+            return Err(PyRuntimeError::new_err(
+                "Cannot undo or redo while a transaction is open",
+            ));
+        }
+        Ok(if redo {
+            arena.redo(owner)
+        } else {
+            arena.undo(owner)
+        })
+    }
+}
+
+#[pyclass(module = "pyprc")]
+pub(crate) struct Transaction {
+    param: Param,
+    savepoint: Option<Savepoint>,
+}
+
+impl Transaction {
+    pub(crate) fn new(param: Param) -> Self {
+        Transaction {
+            param,
+            savepoint: None,
+        }
+    }
+}
+
+#[pymethods]
+impl Transaction {
+    fn __enter__(&mut self) -> PyResult<Param> {
+        if self.savepoint.is_some() {
+            return Err(PyRuntimeError::new_err("The transaction is already open"));
+        }
+        self.savepoint = Some(self.param.tree().lock().begin(self.param.owner()));
+        Ok(self.param.clone_ref())
+    }
+
+    fn __exit__(
+        &mut self,
+        exc_type: &Bound<'_, PyAny>,
+        _exc_value: &Bound<'_, PyAny>,
+        _traceback: &Bound<'_, PyAny>,
+    ) -> bool {
+        if let Some(savepoint) = self.savepoint.take() {
+            let mut arena = self.param.tree().lock();
+            if !exc_type.is_none() {
+                arena.rollback(savepoint);
+            }
+            arena.end(self.param.owner());
+            let events = arena.take_events();
+            drop(arena);
+            observe::fire(events);
+        }
+        // exceptions are never suppressed
+        false
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{Param, ParamType};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{PyDict, PyString};
+
+pub(crate) const KIND_NAMES: [&str; 12] = [
+    "BOOL", "I8", "U8", "I16", "U16", "I32", "U32", "FLOAT", "HASH", "STR", "LIST", "STRUCT",
+];
+
+static PARAM_TYPE_KIND: GILOnceCell<PyObject> = GILOnceCell::new();
+
+/// The older `PARAM_TYPE_*` constants are now members of the enum
+pub(crate) fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    let members = PyDict::new(py);
+    for (index, name) in KIND_NAMES.iter().enumerate() {
+        members.set_item(name, index + 1)?;
+    }
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("module", "pyprc")?;
+    let enum_module = py.import("enum")?;
+    let enum_class = enum_module
+        .getattr("IntEnum")?
+        .call(("ParamTypeKind", members), Some(&kwargs))?;
+    // IntEnum prints as a bare number from python 3.11 onwards, so use the names instead
+    enum_class.setattr("__str__", enum_module.getattr("Enum")?.getattr("__str__")?)?;
+
+    for name in KIND_NAMES {
+        m.add(format!("PARAM_TYPE_{}", name), enum_class.getattr(name)?)?;
+    }
+    m.add("ParamTypeKind", &enum_class)?;
+    let _ = PARAM_TYPE_KIND.set(py, enum_class.unbind());
+    Ok(())
+}
+
+pub(crate) fn kind_object(py: Python, number: u8) -> PyResult<PyObject> {
+    let enum_class = PARAM_TYPE_KIND
+        .get(py)
+        .expect("ParamTypeKind is created with the module");
+    enum_class.call1(py, (number,))
+}
+
+pub(crate) fn is_kind_name(ob: &Bound<'_, PyAny>) -> bool {
+    let py = ob.py();
+    ob.is_instance_of::<PyString>()
+        || PARAM_TYPE_KIND
+            .get(py)
+            .is_some_and(|class| ob.is_instance(class.bind(py)).unwrap_or(false))
+}
+
+impl ParamType {
+    /// The same as in the file format
+    pub(crate) fn type_number(&self) -> u8 {
+        match self {
+            ParamType::Bool(_) => 1,
+            ParamType::I8(_) => 2,
+            ParamType::U8(_) => 3,
+            ParamType::I16(_) => 4,
+            ParamType::U16(_) => 5,
+            ParamType::I32(_) => 6,
+            ParamType::U32(_) => 7,
+            ParamType::Float(_) => 8,
+            ParamType::Hash(_) => 9,
+            ParamType::Str(_) => 10,
+            ParamType::List(_) => 11,
+            ParamType::Struct(_) => 12,
+            ParamType::Lazy(kind, _) => *kind,
+        }
+    }
+}
+
+impl Param {
+    pub(crate) fn type_number(&self) -> u8 {
+        self.lock_unread()[self.id()].type_number()
+    }
+}
+
+pub(crate) fn extract_kind(ob: &Bound<'_, PyAny>) -> PyResult<u8> {
+    if let Ok(name) = ob.extract::<String>() {
+        KIND_NAMES
+            .iter()
+            .position(|kind| kind.eq_ignore_ascii_case(&name))
+            .map(|index| index as u8 + 1)
+            .ok_or_else(|| PyValueError::new_err(format!("Unknown param type '{}'", name)))
+    } else {
+        let number: u8 = ob.extract()?;
+        if (1..=12).contains(&number) {
+            Ok(number)
+        } else {
+            Err(PyValueError::new_err(format!(
+                "Param type numbers range from 1 to 12, found {}",
+                number
+            )))
+        }
+    }
+}
+
+pub(crate) fn kind_name(number: u8) -> String {
+    KIND_NAMES[number as usize - 1].to_lowercase()
+}
@@ -0,0 +1,130 @@
+use crate::Hash;
+use prc::hash40::label_map::LabelMap;
+use prc::hash40::Hash40;
+use pyo3::exceptions::{PyIOError, PyLookupError};
+use pyo3::prelude::*;
+use std::sync::{Arc, Mutex};
+
+/// Separate label maps keep tools in the same process from interfering with each other
+#[pyclass(name = "LabelMap")]
+#[derive(Debug, Clone)]
+pub(crate) struct Labels {
+    pub(crate) inner: Arc<Mutex<LabelMap>>,
+}
+
+impl Labels {
+    pub(crate) fn global() -> Self {
+        Labels {
+            inner: Hash40::label_map(),
+        }
+    }
+
+    pub(crate) fn or_global(labels: Option<&Labels>) -> Self {
+        labels.cloned().unwrap_or_else(Labels::global)
+    }
+
+    pub(crate) fn label(&self, hash: Hash40) -> String {
+        format_label(&self.inner.lock().unwrap(), hash)
+    }
+
+    pub(crate) fn hash(&self, label: &str) -> PyResult<Hash> {
+        let lock = self.inner.lock().unwrap();
+        lock.hash_of(label).map(|hash| hash.into()).ok_or_else(|| {
+            PyLookupError::new_err(
+                "Could not convert this string into a hash. The label map does not contain the string, and is using strict conversion"
+            )
+        })
+    }
+}
+
+pub(crate) fn format_label(labels: &LabelMap, hash: Hash40) -> String {
+    labels
+        .label_of(hash)
+        .unwrap_or_else(|| format!("0x{:010x}", hash.0))
+}
+
+#[pymethods]
+impl Labels {
+    #[new]
+    fn new() -> Self {
+        Labels {
+            inner: Arc::new(Mutex::new(LabelMap::default())),
+        }
+    }
+
+    #[staticmethod]
+    fn global_map() -> Self {
+        Labels::global()
+    }
+
+    fn load_labels(&self, filepath: &str) -> PyResult<()> {
+        self.inner
+            .lock()
+            .unwrap()
+            .add_custom_labels_from_path(filepath)
+            .map_err(|e| PyIOError::new_err(format!("Could not load labels: {:?}", e)))
+    }
+
+    fn add_labels(&self, labels: Vec<String>) {
+        self.inner.lock().unwrap().add_labels(labels)
+    }
+
+    fn add_custom_labels(&self, labels: Vec<(Hash, String)>) {
+        self.inner
+            .lock()
+            .unwrap()
+            .add_custom_labels(labels.into_iter().map(|(h, l)| (h.inner, l)))
+    }
+
+    /// Conflicting labels are replaced by those from the other map
+    fn merge(&self, other: &Labels) {
+        if Arc::ptr_eq(&self.inner, &other.inner) {
+            return;
+        }
+        let other = other.inner.lock().unwrap().map.clone();
+        self.inner
+            .lock()
+            .unwrap()
+            .add_custom_labels(other.into_iter())
+    }
+
+    fn copy(&self) -> Self {
+        Labels {
+            inner: Arc::new(Mutex::new(self.inner.lock().unwrap().clone())),
+        }
+    }
+
+    fn clear(&self) {
+        self.inner.lock().unwrap().clear()
+    }
+
+    #[getter]
+    fn get_strict(&self) -> bool {
+        self.inner.lock().unwrap().strict
+    }
+
+    #[setter]
+    fn set_strict(&self, strict: bool) {
+        self.inner.lock().unwrap().strict = strict;
+    }
+
+    fn label_of(&self, hash: Hash) -> Option<String> {
+        self.inner.lock().unwrap().label_of(hash.inner)
+    }
+
+    fn hash_of(&self, label: &str) -> PyResult<Hash> {
+        self.hash(label)
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.lock().unwrap().map.len()
+    }
+
+    fn __contains__(&self, hash: Hash) -> bool {
+        self.inner.lock().unwrap().map.contains_left(&hash.inner)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("LabelMap (len = {})", self.__len__())
+    }
+}
@@ -0,0 +1,235 @@
+use crate::tree::{Arena, NodeId};
+use crate::{Param, ParamList2, ParamStruct2, ParamType};
+use memmap2::Mmap;
+use prc::hash40::Hash40;
+use prc::{ParamKind, ParamList, ParamStruct, MAGIC};
+use pyo3::exceptions::PyValueError;
+use pyo3::{PyErr, PyResult};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+
+/// Deeper params are treated as a broken file, rather than overflowing the stack
+pub(crate) const MAX_DEPTH: usize = 256;
+
+/// Every file mapped by a lazy tree, so that saving over one can leave the trees intact
+static MAPPED: Mutex<Vec<Weak<LazyFile>>> = Mutex::new(Vec::new());
+
+#[derive(Debug)]
+pub(crate) struct LazyFile {
+    data: Mmap,
+    path: PathBuf,
+    hashes: Vec<Hash40>,
+    ref_start: usize,
+}
+
+enum Node {
+    Value(ParamKind),
+    List(Vec<usize>),
+    Struct(Vec<(Hash40, usize)>),
+}
+
+/// Each param is only read and checked the first time it's locked
+pub(crate) fn open_lazy(filename: &str) -> PyResult<Param> {
+    let (file, param_start) = map(filename.as_ref())?;
+    let file = Arc::new(file);
+    let mut mapped = MAPPED.lock().unwrap();
+    mapped.retain(|file| file.strong_count() > 0);
+    mapped.push(Arc::downgrade(&file));
+    drop(mapped);
+    let mut arena = Arena::default();
+    arena.file = Some(file);
+    let root = arena.push(ParamType::Lazy(12, param_start));
+    Ok(Param::new_tree(arena, root))
+}
+
+/// Writing over a mapped file would change it under any lazy tree still reading from it, so
+/// the file is replaced instead
+pub(crate) fn write(filename: &str, bytes: &[u8]) -> io::Result<()> {
+    let mapped = fs::canonicalize(filename).ok().filter(|path| {
+        MAPPED
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .any(|file| file.path == *path)
+    });
+    let Some(path) = mapped else {
+        return fs::write(filename, bytes);
+    };
+    let temp = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&temp, bytes)?;
+    fs::set_permissions(&temp, fs::metadata(&path)?.permissions())?;
+    fs::rename(&temp, &path)
+}
+
+pub(crate) fn read_kind(filename: &Path) -> PyResult<ParamKind> {
+    let (file, param_start) = map(filename)?;
+    file.to_kind(param_start)
+}
+
+fn invalid(e: String) -> PyErr {
+    PyValueError::new_err(format!("Invalid param file: {}", e))
+}
+
+/// Checks only the header, returning the position of the root struct
+fn map(filename: &Path) -> PyResult<(LazyFile, usize)> {
+    let file = File::open(filename)?;
+    // safety: the file must not be changed by another process while params are read from it
+    let data = unsafe { Mmap::map(&file)? };
+    if data.get(..8) != Some(MAGIC) {
+        return Err(invalid("invalid file magic".into()));
+    }
+    let mut file = LazyFile {
+        data,
+        path: fs::canonicalize(filename)?,
+        hashes: vec![],
+        ref_start: 0,
+    };
+    let hash_size = file.u32_at(8).map_err(invalid)?;
+    let ref_size = file.u32_at(0xc).map_err(invalid)?;
+    file.hashes = (0..hash_size / 8)
+        .map(|index| file.bytes(0x10 + 8 * index).map(u64::from_le_bytes))
+        .map(|hash| hash.map(Hash40))
+        .collect::<Result<_, _>>()
+        .map_err(invalid)?;
+    file.ref_start = 0x10 + hash_size;
+    let param_start = file.ref_start + ref_size;
+    if file.bytes(param_start).map_err(invalid)? != [12] {
+        return Err(invalid("the file does not contain a root struct".into()));
+    }
+    Ok((file, param_start))
+}
+
+impl LazyFile {
+    pub(crate) fn is(&self, path: &Path) -> bool {
+        fs::canonicalize(path).is_ok_and(|path| path == self.path)
+    }
+
+    fn bytes<const N: usize>(&self, pos: usize) -> Result<[u8; N], String> {
+        self.data
+            .get(pos..pos + N)
+            .map(|bytes| bytes.try_into().unwrap())
+            .ok_or_else(|| format!("position {} is past the end of the file", pos))
+    }
+
+    fn u32_at(&self, pos: usize) -> Result<usize, String> {
+        self.bytes(pos).map(|b| u32::from_le_bytes(b) as usize)
+    }
+
+    fn hash(&self, index: usize) -> Result<Hash40, String> {
+        self.hashes
+            .get(index)
+            .copied()
+            .ok_or_else(|| format!("hash index {} is outside of the hash table", index))
+    }
+
+    fn node(&self, pos: usize) -> Result<Node, String> {
+        let [kind] = self.bytes(pos)?;
+        let value = pos + 1;
+        let param = match kind {
+            1 => ParamKind::Bool(self.bytes::<1>(value)?[0] != 0),
+            2 => ParamKind::I8(i8::from_le_bytes(self.bytes(value)?)),
+            3 => ParamKind::U8(u8::from_le_bytes(self.bytes(value)?)),
+            4 => ParamKind::I16(i16::from_le_bytes(self.bytes(value)?)),
+            5 => ParamKind::U16(u16::from_le_bytes(self.bytes(value)?)),
+            6 => ParamKind::I32(i32::from_le_bytes(self.bytes(value)?)),
+            7 => ParamKind::U32(u32::from_le_bytes(self.bytes(value)?)),
+            8 => ParamKind::Float(f32::from_le_bytes(self.bytes(value)?)),
+            9 => ParamKind::Hash(self.hash(self.u32_at(value)?)?),
+            10 => {
+                let start = self.ref_start + self.u32_at(value)?;
+                let rest = self
+                    .data
+                    .get(start..)
+                    .ok_or_else(|| format!("string at {} is past the end of the file", start))?;
+                let len = rest
+                    .iter()
+                    .position(|&b| b == 0)
+                    .ok_or_else(|| format!("string at {} has no end", start))?;
+                // the same as prc-rs, each byte is read as a character
+                ParamKind::Str(rest[..len].iter().map(|&b| b as char).collect())
+            }
+            11 => {
+                let len = self.u32_at(value)?;
+                let children = (0..len)
+                    .map(|index| Ok(pos + self.u32_at(value + 4 + 4 * index)?))
+                    .collect::<Result<_, String>>()?;
+                return Ok(Node::List(children));
+            }
+            12 => {
+                let len = self.u32_at(value)?;
+                let table = self.ref_start + self.u32_at(value + 4)?;
+                let mut fields = (0..len)
+                    .map(|index| {
+                        let entry = table + 8 * index;
+                        Ok((self.u32_at(entry)?, self.u32_at(entry + 4)?))
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                // the same as prc-rs, fields are ordered by their index in the hash table
+                fields.sort_by_key(|(index, _)| *index);
+                let fields = fields
+                    .into_iter()
+                    .map(|(index, offset)| Ok((self.hash(index)?, pos + offset)))
+                    .collect::<Result<_, String>>()?;
+                return Ok(Node::Struct(fields));
+            }
+            other => return Err(format!("invalid param type {} at position {}", other, pos)),
+        };
+        Ok(Node::Value(param))
+    }
+
+    /// Lists and structs are read one level at a time
+    pub(crate) fn load(&self, pos: usize, arena: &mut Arena) -> PyResult<ParamType> {
+        Ok(match self.node(pos).map_err(invalid)? {
+            Node::Value(kind) => arena.node_from_kind(kind),
+            Node::List(children) => ParamType::List(ParamList2(
+                children
+                    .into_iter()
+                    .map(|offset| self.child(offset, arena))
+                    .collect::<PyResult<_>>()?,
+            )),
+            Node::Struct(fields) => ParamType::Struct(ParamStruct2(
+                fields
+                    .into_iter()
+                    .map(|(hash, offset)| Ok((hash, self.child(offset, arena)?)))
+                    .collect::<PyResult<_>>()?,
+            )),
+        })
+    }
+
+    fn child(&self, offset: usize, arena: &mut Arena) -> PyResult<NodeId> {
+        // values take no more space than a lazy param, so they're read straight away
+        let param = match self.bytes(offset).map_err(invalid)? {
+            [kind @ (11 | 12)] => ParamType::Lazy(kind, offset),
+            _ => self.load(offset, arena)?,
+        };
+        Ok(arena.push(param))
+    }
+
+    pub(crate) fn to_kind(&self, pos: usize) -> PyResult<ParamKind> {
+        self.read_kind(pos, 0).map_err(invalid)
+    }
+
+    fn read_kind(&self, pos: usize, depth: usize) -> Result<ParamKind, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("params are nested too deeply at position {}", pos));
+        }
+        Ok(match self.node(pos)? {
+            Node::Value(kind) => kind,
+            Node::List(children) => ParamKind::List(ParamList(
+                children
+                    .into_iter()
+                    .map(|c| self.read_kind(c, depth + 1))
+                    .collect::<Result<_, _>>()?,
+            )),
+            Node::Struct(fields) => ParamKind::Struct(ParamStruct(
+                fields
+                    .into_iter()
+                    .map(|(hash, c)| Ok((hash, self.read_kind(c, depth + 1)?)))
+                    .collect::<Result<_, String>>()?,
+            )),
+        })
+    }
+}
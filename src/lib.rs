@@ -1,23 +1,60 @@
+use asm::{HashOrder, SaveOptions, StringOrder};
+use convert::Rounding;
 use duplicate::Duplicate;
+use labels::Labels;
 use prc::hash40::*;
 use prc::*;
+use pretty::{LabelsArg, TreeFormat};
 use pyo3::class::basic::CompareOp;
 use pyo3::conversion::IntoPyObjectExt;
-use pyo3::exceptions::{PyIndexError, PyLookupError, PyTypeError};
+use pyo3::exceptions::{PyIndexError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
-use std::sync::{Arc, Mutex};
+use pyo3::types::PyDict;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::sync::Arc;
 use std::vec::IntoIter;
-
+use tree::{Draft, NodeId, Tree};
+
+mod array;
+mod asm;
+mod build;
+mod cache;
+mod cli;
+mod convert;
+mod diff;
+mod document;
 mod duplicate;
+mod grep;
+mod history;
+mod kind;
+mod labels;
+mod lazy;
+mod merge;
+mod observe;
+mod overlay;
+mod path;
+mod pretty;
+mod search;
+mod sort;
+mod strings;
+mod table;
+mod track;
+mod tree;
+mod xml;
 
 #[pyclass(name = "param")]
 #[derive(Debug)]
 struct Param {
-    inner: Arc<Mutex<ParamType>>,
+    /// May have since been moved into another tree
+    origin: Tree,
+    node: NodeId,
+    owner: usize,
+    pin: tree::Pin,
+    readonly: bool,
 }
 
-#[derive(Debug, PartialEq)]
-/// an enum designed to store sub-params as reference counted structs to allow python interaction
+#[derive(Debug)]
 enum ParamType {
     Bool(bool),
     I8(i8),
@@ -28,16 +65,18 @@ enum ParamType {
     U32(u32),
     Float(f32),
     Hash(Hash),
-    Str(String),
+    Str(Arc<str>),
     List(ParamList2),
     Struct(ParamStruct2),
+    /// Read from the file the first time it's locked, by type number and position
+    Lazy(u8, usize),
 }
 
-#[derive(Debug, PartialEq)]
-struct ParamList2(Vec<Param>);
+#[derive(Debug, Clone)]
+struct ParamList2(Vec<NodeId>);
 
-#[derive(Debug, PartialEq)]
-struct ParamStruct2(Vec<(Hash, Param)>);
+#[derive(Debug, Clone)]
+struct ParamStruct2(Vec<(Hash40, NodeId)>);
 
 #[pyclass(name = "hash")]
 #[derive(Debug, Hash, PartialEq)]
@@ -45,99 +84,18 @@ struct Hash {
     inner: Hash40,
 }
 
-impl PartialEq for Param {
-    fn eq(&self, other: &Self) -> bool {
-        // if the two objects being compared are the same object, locking both will deadlock
-        Arc::ptr_eq(&self.inner, &other.inner)
-            || *self.inner.lock().unwrap() == *other.inner.lock().unwrap()
-    }
-}
-
-macro_rules! conversions {
-    ($($id:ident),*) => {
-        impl From<ParamKind> for ParamType {
-            fn from(f: ParamKind) -> Self {
-                match f {
-                    $(ParamKind::$id(v) => ParamType::$id(v.into())),*
-                }
-            }
-        }
-    }
-}
-
-conversions!(Bool, I8, U8, I16, U16, I32, U32, Float, Hash, Str, List, Struct);
-
-impl From<ParamKind> for Param {
-    fn from(f: ParamKind) -> Self {
-        Param {
-            inner: Arc::new(Mutex::new(f.into())),
-        }
-    }
-}
-
 impl From<Hash40> for Hash {
     fn from(f: Hash40) -> Self {
         Hash { inner: f }
     }
 }
 
-impl From<ParamList> for ParamList2 {
-    fn from(mut f: ParamList) -> Self {
-        ParamList2(f.0.drain(..).map(From::from).collect())
-    }
-}
-
-impl From<ParamStruct> for ParamStruct2 {
-    fn from(mut f: ParamStruct) -> Self {
-        ParamStruct2(f.0.drain(..).map(|(h, p)| (h.into(), p.into())).collect())
-    }
-}
-
-impl From<&Param> for ParamKind {
-    fn from(f: &Param) -> Self {
-        let mutex = &*f.inner;
-        let param_type = &*mutex.lock().unwrap();
-        ParamKind::from(&(param_type.duplicate()))
-    }
-}
-
-impl From<&ParamType> for ParamKind {
-    fn from(f: &ParamType) -> Self {
-        match f {
-            ParamType::Bool(v) => ParamKind::Bool(*v),
-            ParamType::I8(v) => ParamKind::I8(*v),
-            ParamType::U8(v) => ParamKind::U8(*v),
-            ParamType::I16(v) => ParamKind::I16(*v),
-            ParamType::U16(v) => ParamKind::U16(*v),
-            ParamType::I32(v) => ParamKind::I32(*v),
-            ParamType::U32(v) => ParamKind::U32(*v),
-            ParamType::Float(v) => ParamKind::Float(*v),
-            ParamType::Hash(v) => ParamKind::Hash(v.into()),
-            ParamType::Str(v) => ParamKind::Str(v.into()),
-            ParamType::List(v) => ParamKind::List(v.into()),
-            ParamType::Struct(v) => ParamKind::Struct(v.into()),
-        }
-    }
-}
-
 impl From<&Hash> for Hash40 {
     fn from(f: &Hash) -> Self {
         f.inner
     }
 }
 
-impl From<&ParamList2> for ParamList {
-    fn from(f: &ParamList2) -> Self {
-        ParamList(f.0.iter().map(From::from).collect())
-    }
-}
-
-impl From<&ParamStruct2> for ParamStruct {
-    fn from(f: &ParamStruct2) -> Self {
-        ParamStruct(f.0.iter().map(|(h, p)| (h.into(), p.into())).collect())
-    }
-}
-
 // odd one out here
 impl From<Hash> for ParamKind {
     fn from(f: Hash) -> Self {
@@ -149,44 +107,68 @@ impl From<Hash> for ParamKind {
 fn pyprc(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Param>()?;
     m.add_class::<Hash>()?;
-
-    m.add("PARAM_TYPE_BOOL", 1)?;
-    m.add("PARAM_TYPE_I8", 2)?;
-    m.add("PARAM_TYPE_U8", 3)?;
-    m.add("PARAM_TYPE_I16", 4)?;
-    m.add("PARAM_TYPE_U16", 5)?;
-    m.add("PARAM_TYPE_I32", 6)?;
-    m.add("PARAM_TYPE_U32", 7)?;
-    m.add("PARAM_TYPE_FLOAT", 8)?;
-    m.add("PARAM_TYPE_HASH", 9)?;
-    m.add("PARAM_TYPE_STR", 10)?;
-    m.add("PARAM_TYPE_LIST", 11)?;
-    m.add("PARAM_TYPE_STRUCT", 12)?;
+    m.add_class::<Labels>()?;
+    m.add_class::<history::Transaction>()?;
+    m.add_class::<cache::Cache>()?;
+    m.add_function(wrap_pyfunction!(cli::main, m)?)?;
+    m.add_function(wrap_pyfunction!(merge::merge3, m)?)?;
+    m.add_function(wrap_pyfunction!(grep::grep, m)?)?;
+
+    kind::register(m)?;
     Ok(())
 }
 
 impl Param {
-    fn clone_ref(&self) -> Self {
-        Param {
-            inner: self.inner.clone(),
+    pub(crate) fn open(filename: &str) -> PyResult<Param> {
+        Ok(open(filename).map(ParamKind::from)?.into())
+    }
+
+    fn save_with(&self, filename: &str, options: &SaveOptions) -> PyResult<()> {
+        let bytes = self.assemble(options, false)?.bytes;
+        {
+            // a tree saved over the file it was opened from no longer needs the old one
+            let mut arena = self.tree().lock();
+            if arena
+                .file
+                .as_ref()
+                .is_some_and(|file| file.is(filename.as_ref()))
+            {
+                arena.load_all()?;
+            }
         }
+        lazy::write(filename, &bytes)?;
+        Ok(())
     }
-}
 
-impl Clone for Param {
-    fn clone(&self) -> Self {
-        Param {
-            inner: Arc::new(Mutex::new(self.inner.lock().unwrap().duplicate())),
+    fn assemble(&self, options: &SaveOptions, record: bool) -> PyResult<asm::Assembled> {
+        if let ParamKind::Struct(root) = ParamKind::try_from(self)? {
+            Ok(asm::assemble(&root, options, record))
+        } else {
+            Err(PyTypeError::new_err(
+                "Only struct-type Params can be saved to a file",
+            ))
         }
     }
 }
 
 #[pymethods]
 impl Param {
+    /// With a cache, files which haven't changed since they were last read aren't parsed again
     #[new]
-    fn new(filename: &str) -> PyResult<Self> {
-        let p = open(filename).map(ParamKind::from)?;
-        Ok(p.into())
+    #[pyo3(signature = (filename, readonly=false, cache=None))]
+    fn new(filename: &str, readonly: bool, cache: Option<PyRef<cache::Cache>>) -> PyResult<Self> {
+        let param = match cache {
+            Some(cache) => cache.load(filename)?,
+            None => Param::open(filename)?,
+        };
+        param.tree().lock().owner(param.owner()).frozen = readonly;
+        Ok(param)
+    }
+
+    /// Each list or struct is read from the mapped file the first time it's used
+    #[staticmethod]
+    fn open_lazy(filename: &str) -> PyResult<Self> {
+        lazy::open_lazy(filename)
     }
 
     #[staticmethod]
@@ -231,68 +213,147 @@ impl Param {
     }
 
     #[staticmethod]
-    fn list(mut value: Vec<PyRef<Self>>) -> Self {
-        Param {
-            inner: Arc::new(Mutex::new(ParamType::List(ParamList2(
-                value.drain(..).map(|p| p.clone_ref()).collect(),
-            )))),
-        }
+    fn list(value: Vec<PyRef<Self>>) -> PyResult<Self> {
+        Param::from_draft(Draft::List(
+            value.iter().map(|p| Draft::Shared(p.clone_ref())).collect(),
+        ))
     }
 
     #[staticmethod]
-    fn r#struct(mut value: Vec<(Hash, PyRef<Self>)>) -> Self {
-        Param {
-            inner: Arc::new(Mutex::new(ParamType::Struct(ParamStruct2(
-                value.drain(..).map(|(h, p)| (h, p.clone_ref())).collect(),
-            )))),
-        }
+    fn r#struct(value: Vec<(Hash, PyRef<Self>)>) -> PyResult<Self> {
+        Param::from_draft(Draft::Struct(
+            value
+                .iter()
+                .map(|(h, p)| (h.inner, Draft::Shared(p.clone_ref())))
+                .collect(),
+        ))
     }
 
-    fn set_bool(&mut self, value: bool) {
-        *self.inner.lock().unwrap() = ParamKind::from(value).into()
+    #[staticmethod]
+    fn of(kind: &Bound<'_, PyAny>, value: &Bound<'_, PyAny>) -> PyResult<Self> {
+        convert::from_value(kind::extract_kind(kind)?, value).and_then(Param::from_draft)
     }
-    fn set_i8(&mut self, value: i8) {
-        *self.inner.lock().unwrap() = ParamKind::from(value).into()
+
+    #[staticmethod]
+    fn infer(value: &Bound<'_, PyAny>) -> PyResult<Self> {
+        convert::infer(value).and_then(Param::from_draft)
     }
-    fn set_u8(&mut self, value: u8) {
-        *self.inner.lock().unwrap() = ParamKind::from(value).into()
+
+    /// Builds a param tree from nested dicts, lists, and `(type, value)` tuples
+    #[staticmethod]
+    #[pyo3(signature = (spec, template=None))]
+    fn build(spec: &Bound<'_, PyAny>, template: Option<PyRef<Self>>) -> PyResult<Self> {
+        build::build(spec, template.as_deref()).and_then(Param::from_draft)
     }
-    fn set_i16(&mut self, value: i16) {
-        *self.inner.lock().unwrap() = ParamKind::from(value).into()
+
+    fn set_bool(&mut self, value: bool) -> PyResult<()> {
+        self.set_draft(Draft::Kind(ParamKind::from(value)))
+    }
+    fn set_i8(&mut self, value: i8) -> PyResult<()> {
+        self.set_draft(Draft::Kind(ParamKind::from(value)))
+    }
+    fn set_u8(&mut self, value: u8) -> PyResult<()> {
+        self.set_draft(Draft::Kind(ParamKind::from(value)))
     }
-    fn set_u16(&mut self, value: u16) {
-        *self.inner.lock().unwrap() = ParamKind::from(value).into()
+    fn set_i16(&mut self, value: i16) -> PyResult<()> {
+        self.set_draft(Draft::Kind(ParamKind::from(value)))
     }
-    fn set_i32(&mut self, value: i32) {
-        *self.inner.lock().unwrap() = ParamKind::from(value).into()
+    fn set_u16(&mut self, value: u16) -> PyResult<()> {
+        self.set_draft(Draft::Kind(ParamKind::from(value)))
     }
-    fn set_u32(&mut self, value: u32) {
-        *self.inner.lock().unwrap() = ParamKind::from(value).into()
+    fn set_i32(&mut self, value: i32) -> PyResult<()> {
+        self.set_draft(Draft::Kind(ParamKind::from(value)))
     }
-    fn set_float(&mut self, value: f32) {
-        *self.inner.lock().unwrap() = ParamKind::from(value).into()
+    fn set_u32(&mut self, value: u32) -> PyResult<()> {
+        self.set_draft(Draft::Kind(ParamKind::from(value)))
     }
-    fn set_str(&mut self, value: String) {
-        *self.inner.lock().unwrap() = ParamKind::from(value).into()
+    fn set_float(&mut self, value: f32) -> PyResult<()> {
+        self.set_draft(Draft::Kind(ParamKind::from(value)))
     }
-    fn set_hash(&mut self, value: Hash) {
-        *self.inner.lock().unwrap() = ParamKind::from(value).into()
+    fn set_str(&mut self, value: String) -> PyResult<()> {
+        self.set_draft(Draft::Kind(ParamKind::from(value)))
+    }
+    fn set_hash(&mut self, value: Hash) -> PyResult<()> {
+        self.set_draft(Draft::Kind(ParamKind::from(value)))
     }
 
-    fn set_list(&mut self, mut value: Vec<PyRef<Self>>) {
-        *self.inner.lock().unwrap() =
-            ParamType::List(ParamList2(value.drain(..).map(|p| p.clone_ref()).collect()))
+    fn set_list(&mut self, value: Vec<PyRef<Self>>) -> PyResult<()> {
+        self.set_draft(Draft::List(
+            value.iter().map(|p| Draft::Shared(p.clone_ref())).collect(),
+        ))
     }
 
-    fn set_struct(&mut self, mut value: Vec<(Hash, PyRef<Self>)>) {
-        *self.inner.lock().unwrap() = ParamType::Struct(ParamStruct2(
-            value.drain(..).map(|(h, p)| (h, p.clone_ref())).collect(),
+    fn set_struct(&mut self, value: Vec<(Hash, PyRef<Self>)>) -> PyResult<()> {
+        self.set_draft(Draft::Struct(
+            value
+                .iter()
+                .map(|(h, p)| (h.inner, Draft::Shared(p.clone_ref())))
+                .collect(),
         ))
     }
 
-    fn save(&self, filename: &str) -> PyResult<()> {
-        if let ParamType::Struct(ps2) = &*self.inner.lock().unwrap() {
-            save(filename, &ps2.into())?;
+    #[pyo3(signature = (kind, rounding=None))]
+    fn convert(&mut self, kind: &Bound<'_, PyAny>, rounding: Option<Rounding>) -> PyResult<()> {
+        let mut arena = self.lock_mut()?;
+        let converted = arena[self.id()].convert(kind::extract_kind(kind)?, rounding)?;
+        arena.set(self.id(), converted);
+        Ok(())
+    }
+
+    /// The default options write the same bytes as prc-rs
+    #[pyo3(signature = (filename, hash_order=HashOrder::Traversal, string_order=StringOrder::Traversal))]
+    fn save(
+        &self,
+        filename: &str,
+        hash_order: HashOrder,
+        string_order: StringOrder,
+    ) -> PyResult<()> {
+        let options = SaveOptions {
+            hash_order,
+            string_order,
+        };
+        self.save_with(filename, &options)
+    }
+
+    /// Returns None if `save` would write the same bytes as the file, or else the offset of the
+    /// first difference, its section, and the path of the param responsible
+    #[pyo3(signature = (filename, hash_order=HashOrder::Traversal, string_order=StringOrder::Traversal))]
+    fn roundtrip_check(
+        &self,
+        py: Python,
+        filename: &str,
+        hash_order: HashOrder,
+        string_order: StringOrder,
+    ) -> PyResult<Option<(usize, &'static str, Option<PyObject>)>> {
+        let options = SaveOptions {
+            hash_order,
+            string_order,
+        };
+        let expected = std::fs::read(filename)?;
+        self.assemble(&options, true)?
+            .first_difference(&expected)
+            .map(|(offset, section, path)| {
+                Ok((offset, section, path.map(|p| p.to_object(py)).transpose()?))
+            })
+            .transpose()
+    }
+
+    /// Passing this as the `hash_order` keeps the hashes in the order of the original file
+    #[staticmethod]
+    fn hash_table(filename: &str) -> PyResult<Vec<Hash>> {
+        let bytes = std::fs::read(filename)?;
+        Ok(asm::read_hash_table(&bytes)?
+            .into_iter()
+            .map(Hash::from)
+            .collect())
+    }
+
+    #[pyo3(signature = (filename, labels=None))]
+    fn to_xml(&self, filename: &str, labels: Option<Labels>) -> PyResult<()> {
+        if let ParamKind::Struct(root) = ParamKind::try_from(self)? {
+            let labels = Labels::or_global(labels.as_ref());
+            let mut writer = BufWriter::new(File::create(filename)?);
+            xml::write_xml(&root, &labels.inner.lock().unwrap(), &mut writer)?;
             Ok(())
         } else {
             Err(PyTypeError::new_err(
@@ -301,31 +362,232 @@ impl Param {
         }
     }
 
+    #[staticmethod]
+    #[pyo3(signature = (filename, labels=None))]
+    fn from_xml(filename: &str, labels: Option<Labels>) -> PyResult<Self> {
+        let labels = Labels::or_global(labels.as_ref());
+        let reader = BufReader::new(File::open(filename)?);
+        let root = xml::read_xml(reader, &labels.inner.lock().unwrap())?;
+        Ok(ParamKind::Struct(root).into())
+    }
+
+    /// Values are only tagged with their type where it can't be inferred, like `!u8 3`
+    #[pyo3(signature = (template=None, labels=None))]
+    fn to_yaml(&self, template: Option<PyRef<Self>>, labels: Option<Labels>) -> PyResult<String> {
+        let template = template.map(|t| ParamKind::try_from(&*t)).transpose()?;
+        let labels = Labels::or_global(labels.as_ref());
+        let labels = labels.inner.lock().unwrap();
+        document::to_yaml(&ParamKind::try_from(self)?, template.as_ref(), &labels)
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (text, template=None, labels=None))]
+    fn from_yaml(
+        text: &str,
+        template: Option<PyRef<Self>>,
+        labels: Option<Labels>,
+    ) -> PyResult<Self> {
+        let template = template.map(|t| ParamKind::try_from(&*t)).transpose()?;
+        let labels = Labels::or_global(labels.as_ref());
+        let labels = labels.inner.lock().unwrap();
+        Ok(document::from_yaml(text, template.as_ref(), &labels)?.into())
+    }
+
+    /// Tagged values are written as a table with a single key, like `{ "!u8" = 3 }`
+    #[pyo3(signature = (template=None, labels=None))]
+    fn to_toml(&self, template: Option<PyRef<Self>>, labels: Option<Labels>) -> PyResult<String> {
+        let template = template.map(|t| ParamKind::try_from(&*t)).transpose()?;
+        let labels = Labels::or_global(labels.as_ref());
+        let labels = labels.inner.lock().unwrap();
+        document::to_toml(&ParamKind::try_from(self)?, template.as_ref(), &labels)
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (text, template=None, labels=None))]
+    fn from_toml(
+        text: &str,
+        template: Option<PyRef<Self>>,
+        labels: Option<Labels>,
+    ) -> PyResult<Self> {
+        let template = template.map(|t| ParamKind::try_from(&*t)).transpose()?;
+        let labels = Labels::or_global(labels.as_ref());
+        let labels = labels.inner.lock().unwrap();
+        Ok(document::from_toml(text, template.as_ref(), &labels)?.into())
+    }
+
     fn clone(&self) -> Self {
-        Clone::clone(self)
+        self.duplicate()
+    }
+
+    /// With `in_place`, the whole tree is frozen for every reference taken from it
+    #[pyo3(signature = (in_place=false))]
+    fn freeze(&self, in_place: bool) -> Self {
+        if in_place {
+            self.tree().lock().owner(self.owner()).frozen = true;
+            return self.clone_ref();
+        }
+        Param {
+            readonly: true,
+            ..self.clone_ref()
+        }
+    }
+
+    #[getter]
+    fn get_readonly(&self) -> bool {
+        self.readonly || self.tree().lock().frozen(self.owner())
+    }
+
+    /// Calling it again clears the changes recorded so far
+    fn track_changes(&self) {
+        self.tree().lock().owner(self.owner()).tracker = Some(track::Tracker::new(self.id()));
+    }
+
+    /// Added params have no old param, and removed params have no new param
+    fn changes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let arena = self.tree().lock();
+        let Some(tracker) = arena.tracker(self.owner()) else {
+            return Err(PyValueError::new_err(
+                "Changes aren't being tracked; call track_changes first",
+            ));
+        };
+        let changes = arena.changes(tracker)?;
+        drop(arena);
+        let dict = PyDict::new(py);
+        for (path, old, new) in changes {
+            dict.set_item(
+                path.to_object(py)?,
+                (old.map(Param::from), new.map(Param::from)),
+            )?;
+        }
+        Ok(dict)
     }
 
     #[getter]
-    fn get_type(&self) -> u8 {
-        match &*self.inner.lock().unwrap() {
-            ParamType::Bool(_) => 1,
-            ParamType::I8(_) => 2,
-            ParamType::U8(_) => 3,
-            ParamType::I16(_) => 4,
-            ParamType::U16(_) => 5,
-            ParamType::I32(_) => 6,
-            ParamType::U32(_) => 7,
-            ParamType::Float(_) => 8,
-            ParamType::Hash(_) => 9,
-            ParamType::Str(_) => 10,
-            ParamType::List(_) => 11,
-            ParamType::Struct(_) => 12,
+    fn get_is_dirty(&self) -> bool {
+        let arena = self.tree().lock();
+        arena
+            .tracker(self.owner())
+            .is_some_and(|tracker| arena.is_dirty(tracker))
+    }
+
+    /// Changes made in a transaction are passed once it ends, and not at all if it's rolled back
+    fn on_change(&self, callback: &Bound<'_, PyAny>) -> PyResult<()> {
+        if !callback.is_callable() {
+            return Err(PyTypeError::new_err("The callback must be callable"));
+        }
+        self.tree()
+            .lock()
+            .observe(self.id(), callback.clone().unbind());
+        Ok(())
+    }
+
+    /// Changes are rolled back if an exception escapes the `with` block
+    fn transaction(&self) -> history::Transaction {
+        history::Transaction::new(self.clone_ref())
+    }
+
+    /// Calling it again changes the limit, keeping the most recent steps
+    #[pyo3(signature = (limit=1000))]
+    fn track_history(&self, limit: usize) {
+        self.tree().lock().owner(self.owner()).history.keep(limit);
+    }
+
+    fn undo(&self) -> PyResult<bool> {
+        self.step(false)
+    }
+
+    fn redo(&self) -> PyResult<bool> {
+        self.step(true)
+    }
+
+    /// List elements are matched by the first of the key fields which every element has, or
+    /// else by index
+    #[pyo3(signature = (other, key_fields=None))]
+    fn diff(
+        &self,
+        py: Python,
+        other: PyRef<Self>,
+        key_fields: Option<Vec<Hash>>,
+    ) -> PyResult<Vec<PyObject>> {
+        let key_fields = key_fields
+            .unwrap_or_default()
+            .iter()
+            .map(Hash40::from)
+            .collect::<Vec<_>>();
+        let changes = diff::diff(
+            &ParamKind::try_from(self)?,
+            &ParamKind::try_from(&*other)?,
+            &key_fields,
+        );
+        changes
+            .into_iter()
+            .map(|change| {
+                (
+                    change.path.to_object(py)?,
+                    change.old.map(Param::from),
+                    change.new.map(Param::from),
+                )
+                    .into_py_any(py)
+            })
+            .collect()
+    }
+
+    #[pyo3(signature = (changes, labels=None))]
+    fn patch(&self, changes: Vec<diff::PyChange>, labels: Option<Labels>) -> PyResult<()> {
+        let labels = Labels::or_global(labels.as_ref());
+        self.grouped(|| {
+            for (path, old, new) in changes {
+                let change = diff::Change {
+                    path: path::ParamPath::extract_with(&path, &labels)?,
+                    old: old.map(|p| ParamKind::try_from(&*p)).transpose()?,
+                    new: new.map(|p| ParamKind::try_from(&*p)).transpose()?,
+                };
+                self.apply_change(&change, &labels)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Nothing is changed if any key doesn't match a param
+    #[pyo3(signature = (overlay, list_key_fields=None, labels=None))]
+    fn apply_overlay(
+        &self,
+        overlay: &Bound<'_, PyAny>,
+        list_key_fields: Option<Vec<Hash>>,
+        labels: Option<Labels>,
+    ) -> PyResult<()> {
+        let key_fields = list_key_fields
+            .unwrap_or_default()
+            .iter()
+            .map(Hash40::from)
+            .collect::<Vec<_>>();
+        self.overlay(overlay, &key_fields, &Labels::or_global(labels.as_ref()))
+    }
+
+    #[pyo3(signature = (overrides, create=false, labels=None))]
+    fn clone_with(
+        &self,
+        overrides: &Bound<'_, PyDict>,
+        create: bool,
+        labels: Option<Labels>,
+    ) -> PyResult<Self> {
+        let labels = Labels::or_global(labels.as_ref());
+        let clone = self.duplicate();
+        for (path, value) in overrides.iter() {
+            let path = path::ParamPath::extract_with(&path, &labels)?;
+            clone.set_path(&path, &value, create, &labels)?;
         }
+        Ok(clone)
+    }
+
+    #[getter]
+    fn get_type(&self, py: Python) -> PyResult<PyObject> {
+        kind::kind_object(py, self.type_number())
     }
 
     #[getter]
     fn get_value(&self, py: Python) -> PyResult<PyObject> {
-        match &*self.inner.lock().unwrap() {
+        match &self.lock()?[self.id()] {
             ParamType::Bool(v) => v.into_py_any(py),
             ParamType::I8(v) => v.into_py_any(py),
             ParamType::U8(v) => v.into_py_any(py),
@@ -339,7 +601,7 @@ impl Param {
             ParamType::List(_) => Err(PyTypeError::new_err(
                 "Cannot access value on a list-type param",
             )),
-            ParamType::Struct(_) => Err(PyTypeError::new_err(
+            ParamType::Struct(_) | ParamType::Lazy(..) => Err(PyTypeError::new_err(
                 "Cannot access value on a list-type param",
             )),
         }
@@ -347,33 +609,158 @@ impl Param {
 
     #[setter]
     fn set_value(&mut self, py: Python, value: PyObject) -> PyResult<()> {
-        match &mut *self.inner.lock().unwrap() {
-            ParamType::Bool(v) => *v = value.extract(py)?,
-            ParamType::I8(v) => *v = value.extract(py)?,
-            ParamType::U8(v) => *v = value.extract(py)?,
-            ParamType::I16(v) => *v = value.extract(py)?,
-            ParamType::U16(v) => *v = value.extract(py)?,
-            ParamType::I32(v) => *v = value.extract(py)?,
-            ParamType::U32(v) => *v = value.extract(py)?,
-            ParamType::Float(v) => *v = value.extract(py)?,
-            ParamType::Hash(v) => *v = value.extract(py)?,
-            ParamType::Str(v) => *v = value.extract(py)?,
+        let mut arena = self.lock_mut()?;
+        let param = match &arena[self.id()] {
+            ParamType::Bool(_) => ParamType::Bool(value.extract(py)?),
+            ParamType::I8(_) => ParamType::I8(value.extract(py)?),
+            ParamType::U8(_) => ParamType::U8(value.extract(py)?),
+            ParamType::I16(_) => ParamType::I16(value.extract(py)?),
+            ParamType::U16(_) => ParamType::U16(value.extract(py)?),
+            ParamType::I32(_) => ParamType::I32(value.extract(py)?),
+            ParamType::U32(_) => ParamType::U32(value.extract(py)?),
+            ParamType::Float(_) => ParamType::Float(value.extract(py)?),
+            ParamType::Hash(_) => ParamType::Hash(value.extract(py)?),
+            ParamType::Str(_) => ParamType::Str(value.extract::<String>(py)?.into()),
             ParamType::List(_) => {
                 return Err(PyTypeError::new_err(
                     "Cannot assign value on a list-type param",
                 ))
             }
-            ParamType::Struct(_) => {
+            ParamType::Struct(_) | ParamType::Lazy(..) => {
                 return Err(PyTypeError::new_err(
                     "Cannot assign value on a list-type param",
                 ))
             }
-        }
+        };
+        arena.set(self.id(), param);
         Ok(())
     }
 
+    /// The result can be passed straight to `pandas.DataFrame`
+    #[pyo3(signature = (labels=None))]
+    fn to_table<'py>(
+        &self,
+        py: Python<'py>,
+        labels: Option<Labels>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        self.table(py, &Labels::or_global(labels.as_ref()))
+    }
+
+    /// None values leave the field out of that row, and fields without a column are copied from
+    /// the template
+    #[staticmethod]
+    #[pyo3(signature = (rows, template, labels=None))]
+    fn from_table(
+        rows: &Bound<'_, PyAny>,
+        template: PyRef<Self>,
+        labels: Option<Labels>,
+    ) -> PyResult<Self> {
+        table::from_table(rows, &template, &Labels::or_global(labels.as_ref()))
+    }
+
+    /// Fields holding lists or structs are left out
+    #[pyo3(signature = (filename, labels=None))]
+    fn to_csv(&self, filename: &str, labels: Option<Labels>) -> PyResult<()> {
+        self.write_csv(filename, &Labels::or_global(labels.as_ref()))
+    }
+
+    /// Empty cells leave the field out of that row
+    #[staticmethod]
+    #[pyo3(signature = (filename, template, labels=None))]
+    fn from_csv(filename: &str, template: PyRef<Self>, labels: Option<Labels>) -> PyResult<Self> {
+        table::from_csv(filename, &template, &Labels::or_global(labels.as_ref()))
+    }
+
+    fn to_numpy<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        self.array(py)
+    }
+
+    /// Floats are only converted to integers when they're whole, or a rounding mode is given
+    #[staticmethod]
+    #[pyo3(signature = (array, kind, rounding=None))]
+    fn from_numpy(
+        array: &Bound<'_, PyAny>,
+        kind: &Bound<'_, PyAny>,
+        rounding: Option<Rounding>,
+    ) -> PyResult<Self> {
+        array::from_array(array, kind::extract_kind(kind)?, rounding)
+    }
+
+    #[pyo3(signature = (array, rounding=None))]
+    fn assign_array(&self, array: &Bound<'_, PyAny>, rounding: Option<Rounding>) -> PyResult<()> {
+        self.assign_numbers(array, rounding)
+    }
+
+    /// The key is either a path within each element or a function. Hashes are compared by value
+    #[pyo3(signature = (key=None, reverse=false, labels=None))]
+    fn sort(
+        &self,
+        py: Python,
+        key: Option<&Bound<'_, PyAny>>,
+        reverse: bool,
+        labels: Option<Labels>,
+    ) -> PyResult<()> {
+        self.sort_list(py, key, reverse, &Labels::or_global(labels.as_ref()))
+    }
+
+    #[pyo3(signature = (by=sort::KeyOrder::Label, labels=None))]
+    fn sort_keys(&self, by: sort::KeyOrder, labels: Option<Labels>) -> PyResult<()> {
+        self.sort_fields(by, &Labels::or_global(labels.as_ref()))
+    }
+
+    /// Strings match hashes of the same label
+    #[pyo3(signature = (field, value, labels=None))]
+    fn find_by(
+        &self,
+        field: &Bound<'_, PyAny>,
+        value: &Bound<'_, PyAny>,
+        labels: Option<Labels>,
+    ) -> PyResult<Option<usize>> {
+        let labels = Labels::or_global(labels.as_ref());
+        let field = Hash::extract_with(field, &labels)?;
+        let value = path::KeyValue::extract_with(value, &labels)?;
+        let mut arena = self.lock()?;
+        let labels = labels.inner.lock().unwrap();
+        match &arena[self.id()] {
+            ParamType::List(_) => arena.find_by(self.id(), field.inner, &value, &labels),
+            _ => Err(PyTypeError::new_err(
+                "Cannot search params other than list-type params",
+            )),
+        }
+    }
+
+    fn strings<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.string_counts(py)
+    }
+
+    /// The changes are undone together
+    fn replace_string(&self, old: &str, new: &str) -> PyResult<usize> {
+        self.rename_string(old, new)
+    }
+
+    /// Numbers match any numeric type with the same value, and strings match hashes of the same
+    /// label. Lists and structs are only found when the type asks for them
+    #[pyo3(signature = (value=None, kind=None, key=None, labels=None))]
+    fn find(
+        &self,
+        py: Python,
+        value: Option<&Bound<'_, PyAny>>,
+        kind: Option<&Bound<'_, PyAny>>,
+        key: Option<&Bound<'_, PyAny>>,
+        labels: Option<Labels>,
+    ) -> PyResult<Vec<PyObject>> {
+        let labels = Labels::or_global(labels.as_ref());
+        let query = search::Query::new(value, kind, key, &labels)?;
+        self.find_paths(py, &query)
+    }
+
+    /// `string_table_size` is the number of bytes the unique strings take up in a file
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.tree_stats(py)
+    }
+
     fn __len__(&self) -> PyResult<usize> {
-        match &*self.inner.lock().unwrap() {
+        match &self.lock()?[self.id()] {
             ParamType::List(v) => Ok(v.0.len()),
             ParamType::Struct(v) => Ok(v.0.len()),
             _ => Err(PyTypeError::new_err(
@@ -383,21 +770,22 @@ impl Param {
     }
 
     fn __getitem__(&self, py: Python, key: PyObject) -> PyResult<PyObject> {
-        match &*self.inner.lock().unwrap() {
+        let arena = self.lock()?;
+        match &arena[self.id()] {
             ParamType::List(v) => {
                 let index: usize = key.extract(py)?;
                 if index >= v.0.len() {
                     Err(PyIndexError::new_err("Index out of bounds"))
                 } else {
-                    v.0[index].clone_ref().into_py_any(py)
+                    arena.handle(self, v.0[index]).into_py_any(py)
                 }
             }
             ParamType::Struct(v) => {
                 let index: Hash = key.extract(py)?;
                 let mut col: Vec<Param> =
                     v.0.iter()
-                        .filter(|(hash, _)| hash.inner == index.inner)
-                        .map(|(_, p)| p.clone_ref())
+                        .filter(|(hash, _)| *hash == index.inner)
+                        .map(|(_, p)| arena.handle(self, *p))
                         .collect();
                 if col.is_empty() {
                     Err(PyIndexError::new_err("Hash not found in child params"))
@@ -414,67 +802,90 @@ impl Param {
     }
 
     fn __setitem__(&mut self, py: Python, key: PyObject, value: PyObject) -> PyResult<()> {
-        match &mut *self.inner.lock().unwrap() {
+        // the value is copied before locking, in case it belongs to the same tree
+        let set = ParamKind::try_from(&*value.extract::<PyRef<Param>>(py)?)?;
+        let mut arena = self.lock_mut()?;
+        let mut param = arena[self.id()].duplicate();
+        let slot = match &mut param {
             ParamType::List(v) => {
                 let index: usize = key.extract(py)?;
-                if index >= v.0.len() {
-                    Err(PyIndexError::new_err("Index out of bounds"))
-                } else {
-                    let set: Param = value.extract(py)?;
-                    v.0[index] = set;
-                    Ok(())
-                }
+                v.0.get_mut(index)
+                    .ok_or_else(|| PyIndexError::new_err("Index out of bounds"))?
             }
             ParamType::Struct(v) => {
                 let index: Hash = key.extract(py)?;
-                let mut col: Vec<&mut Param> =
+                let mut col: Vec<&mut NodeId> =
                     v.0.iter_mut()
-                        .filter(|(hash, _)| *hash == index)
+                        .filter(|(hash, _)| *hash == index.inner)
                         .map(|(_, p)| p)
                         .collect();
                 if col.is_empty() {
-                    Err(PyIndexError::new_err("Hash not found in child params"))
-                } else if col.len() == 1 {
-                    let set: Param = value.extract(py)?;
-                    *col[0] = set;
-                    Ok(())
-                } else {
-                    Err(PyTypeError::new_err(
+                    return Err(PyIndexError::new_err("Hash not found in child params"));
+                } else if col.len() > 1 {
+                    return Err(PyTypeError::new_err(
                         "Cannot assign param to this hash; more than one match was found",
-                    ))
+                    ));
                 }
+                col.remove(0)
             }
-            _ => Err(PyTypeError::new_err(
-                "Cannot index params other than list or struct-type params",
-            )),
-        }
+            _ => {
+                return Err(PyTypeError::new_err(
+                    "Cannot index params other than list or struct-type params",
+                ))
+            }
+        };
+        *slot = arena.push_kind(set);
+        arena.set(self.id(), param);
+        Ok(())
     }
 
-    fn __str__(&self) -> String {
-        match &*self.inner.lock().unwrap() {
-            ParamType::Bool(v) => format!("param bool ({})", v),
-            ParamType::I8(v) => format!("param i8 ({})", v),
-            ParamType::U8(v) => format!("param u8 ({})", v),
-            ParamType::I16(v) => format!("param i16 ({})", v),
-            ParamType::U16(v) => format!("param u16 ({})", v),
-            ParamType::I32(v) => format!("param i32 ({})", v),
-            ParamType::U32(v) => format!("param u32 ({})", v),
-            ParamType::Float(v) => format!("param float ({})", v),
-            ParamType::Hash(v) => format!("param hash ({})", v.inner),
-            ParamType::Str(v) => format!("param str ({})", v),
-            ParamType::List(v) => format!("param list (len = {})", v.0.len()),
-            ParamType::Struct(v) => format!("param struct (len = {})", v.0.len()),
-        }
+    fn __str__(&self) -> PyResult<String> {
+        let (name, text) = self.lock()?[self.id()].summary(|hash| hash.to_string());
+        Ok(format!("param {} ({})", name, text))
     }
 
-    fn __repr__(&self) -> String {
+    fn __repr__(&self) -> PyResult<String> {
         self.__str__()
     }
 
+    /// Hashes use the global labels, or the given label map, unless labels is False
+    #[pyo3(signature = (max_depth=None, max_items=None, labels=LabelsArg(Some(Labels::global()))))]
+    fn pformat(
+        &self,
+        max_depth: Option<usize>,
+        max_items: Option<usize>,
+        labels: LabelsArg,
+    ) -> PyResult<String> {
+        TreeFormat {
+            labels: labels.0,
+            max_depth,
+            max_items,
+        }
+        .text(self)
+    }
+
+    fn _repr_pretty_(&self, p: &Bound<'_, PyAny>, _cycle: bool) -> PyResult<()> {
+        p.call_method1(
+            "text",
+            (self.pformat(None, None, LabelsArg(Some(Labels::global())))?,),
+        )?;
+        Ok(())
+    }
+
+    /// Limited in depth and children so that large files still display quickly
+    fn _repr_html_(&self) -> PyResult<String> {
+        TreeFormat {
+            labels: Some(Labels::global()),
+            max_depth: Some(3),
+            max_items: Some(50),
+        }
+        .html(self)
+    }
+
     fn __richcmp__(&self, other: PyRef<Self>, co: CompareOp) -> PyResult<bool> {
         match co {
-            CompareOp::Eq => Ok(self == &*other),
-            CompareOp::Ne => Ok(self != &*other),
+            CompareOp::Eq => self.equals(&other),
+            CompareOp::Ne => Ok(!self.equals(&other)?),
             _ => Err(PyTypeError::new_err(
                 "Only == or != comparisons valid for param",
             )),
@@ -483,11 +894,12 @@ impl Param {
 
     fn __iter__(this: PyRef<Self>) -> PyResult<Py<ParamIter>> {
         let py = this.py();
-        match &*this.inner.lock().unwrap() {
+        let arena = this.lock()?;
+        match &arena[this.id()] {
             ParamType::List(v) => {
                 let refs: IntoIter<PyObject> =
                     v.0.iter()
-                        .map(|p| p.clone_ref().into_py_any(py))
+                        .map(|p| arena.handle(&this, *p).into_py_any(py))
                         .collect::<Result<Vec<_>, _>>()?
                         .into_iter();
                 Py::new(py, ParamIter { inner: refs })
@@ -495,7 +907,7 @@ impl Param {
             ParamType::Struct(v) => {
                 let refs: IntoIter<PyObject> =
                     v.0.iter()
-                        .map(|(h, p)| (h.duplicate(), p.clone_ref()).into_py_any(py))
+                        .map(|(h, p)| (Hash::from(*h), arena.handle(&this, *p)).into_py_any(py))
                         .collect::<Result<Vec<_>, _>>()?
                         .into_iter();
                 Py::new(py, ParamIter { inner: refs })
@@ -510,10 +922,12 @@ impl Param {
 #[pymethods]
 impl Hash {
     #[new]
-    fn new(py: Python, value: PyObject) -> PyResult<Hash> {
-        value
-            .extract::<Hash>(py)
-            .map(|wrapper| Into::<Hash>::into(wrapper))
+    #[pyo3(signature = (value, labels=None))]
+    fn new(value: &Bound<'_, PyAny>, labels: Option<Labels>) -> PyResult<Hash> {
+        match labels {
+            Some(labels) => Hash::extract_with(value, &labels),
+            None => value.extract(),
+        }
     }
 
     #[staticmethod]
@@ -546,6 +960,11 @@ impl Hash {
         format!("{}", self.inner)
     }
 
+    #[pyo3(signature = (labels=None))]
+    fn to_label(&self, labels: Option<Labels>) -> String {
+        Labels::or_global(labels.as_ref()).label(self.inner)
+    }
+
     fn __repr__(&self) -> String {
         format!("hash ({})", self.inner)
     }
@@ -565,18 +984,12 @@ impl Hash {
     }
 }
 
-impl<'py> FromPyObject<'py> for Hash {
-    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+impl Hash {
+    fn extract_with(ob: &Bound<'_, PyAny>, labels: &Labels) -> PyResult<Self> {
         if let Ok(v) = ob.downcast::<Hash>() {
             Ok(v.try_borrow()?.duplicate())
         } else if let Ok(v) = ob.extract::<String>() {
-            let labels = Hash40::label_map();
-            let lock = labels.lock().unwrap();
-            lock.hash_of(&v).map(|hash| hash.into()).ok_or_else(|| {
-                PyLookupError::new_err(
-                    "Could not convert this string into a hash. The label map does not contain the string, and is using strict conversion"
-                )
-            })
+            labels.hash(&v)
         } else if let Ok(v) = ob.extract::<u64>() {
             Ok(Hash { inner: Hash40(v) })
         } else {
@@ -587,6 +1000,12 @@ impl<'py> FromPyObject<'py> for Hash {
     }
 }
 
+impl<'py> FromPyObject<'py> for Hash {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        Hash::extract_with(ob, &Labels::global())
+    }
+}
+
 #[pyclass]
 struct ParamIter {
     inner: IntoIter<PyObject>,
@@ -0,0 +1,232 @@
+use crate::diff::{key_field, key_value, same_value};
+use crate::path::{Key, KeyValue, ParamPath};
+use crate::{Hash, Param};
+use prc::hash40::Hash40;
+use prc::{ParamKind, ParamList, ParamStruct};
+use pyo3::conversion::IntoPyObjectExt;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Strategy {
+    Ours,
+    Theirs,
+    Base,
+}
+
+impl<'py> FromPyObject<'py> for Strategy {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match &*ob.downcast::<PyString>()?.to_cow()? {
+            "ours" => Ok(Strategy::Ours),
+            "theirs" => Ok(Strategy::Theirs),
+            "base" => Ok(Strategy::Base),
+            other => Err(PyValueError::new_err(format!(
+                "Unknown strategy '{}'; expected 'ours', 'theirs', or 'base'",
+                other
+            ))),
+        }
+    }
+}
+
+/// A missing value means the param was removed, or was never there
+#[derive(Debug)]
+pub(crate) struct Conflict {
+    pub(crate) path: ParamPath,
+    pub(crate) base: Option<ParamKind>,
+    pub(crate) ours: Option<ParamKind>,
+    pub(crate) theirs: Option<ParamKind>,
+}
+
+struct Merger<'a> {
+    strategy: Strategy,
+    key_fields: &'a [Hash40],
+    conflicts: Vec<Conflict>,
+}
+
+pub(crate) fn merge_trees(
+    base: &ParamKind,
+    ours: &ParamKind,
+    theirs: &ParamKind,
+    strategy: Strategy,
+    key_fields: &[Hash40],
+) -> (ParamKind, Vec<Conflict>) {
+    let mut merger = Merger {
+        strategy,
+        key_fields,
+        conflicts: vec![],
+    };
+    let merged = merger
+        .merge(&ParamPath::default(), Some(base), Some(ours), Some(theirs))
+        .expect("every strategy keeps a param which all sides have");
+    (merged, merger.conflicts)
+}
+
+fn same(a: Option<&ParamKind>, b: Option<&ParamKind>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => same_value(a, b),
+        (a, b) => a.is_none() && b.is_none(),
+    }
+}
+
+fn get(s: &ParamStruct, hash: Hash40) -> Option<&ParamKind> {
+    s.0.iter().find(|(h, _)| *h == hash).map(|(_, v)| v)
+}
+
+impl Merger<'_> {
+    fn merge(
+        &mut self,
+        path: &ParamPath,
+        base: Option<&ParamKind>,
+        ours: Option<&ParamKind>,
+        theirs: Option<&ParamKind>,
+    ) -> Option<ParamKind> {
+        if same(ours, theirs) || same(base, theirs) {
+            return ours.cloned();
+        }
+        if same(base, ours) {
+            return theirs.cloned();
+        }
+        // both sides changed it, so look for changes to different children. A base which
+        // isn't the same type counts as empty, such as when both sides added it
+        let empty_list = ParamList(vec![]);
+        let empty_struct = ParamStruct(vec![]);
+        match (base, ours, theirs) {
+            (base, Some(ParamKind::Struct(o)), Some(ParamKind::Struct(t))) => {
+                let b = match base {
+                    Some(ParamKind::Struct(b)) => b,
+                    _ => &empty_struct,
+                };
+                return Some(ParamKind::Struct(self.merge_struct(path, b, o, t)));
+            }
+            (base, Some(ParamKind::List(o)), Some(ParamKind::List(t))) => {
+                let b = match base {
+                    Some(ParamKind::List(b)) => b,
+                    _ => &empty_list,
+                };
+                return Some(ParamKind::List(self.merge_list(path, b, o, t)));
+            }
+            _ => {}
+        }
+        self.conflicts.push(Conflict {
+            path: path.clone(),
+            base: base.cloned(),
+            ours: ours.cloned(),
+            theirs: theirs.cloned(),
+        });
+        match self.strategy {
+            Strategy::Ours => ours.cloned(),
+            Strategy::Theirs => theirs.cloned(),
+            Strategy::Base => base.cloned(),
+        }
+    }
+
+    /// Fields are in the order ours has them, followed by any only theirs has
+    fn merge_struct(
+        &mut self,
+        path: &ParamPath,
+        base: &ParamStruct,
+        ours: &ParamStruct,
+        theirs: &ParamStruct,
+    ) -> ParamStruct {
+        let hashes = ours.0.iter().map(|(h, _)| *h).chain(
+            theirs
+                .0
+                .iter()
+                .map(|(h, _)| *h)
+                .filter(|h| get(ours, *h).is_none()),
+        );
+        let mut fields = vec![];
+        for hash in hashes {
+            let merged = self.merge(
+                &path.push(Key::Hash(hash)),
+                get(base, hash),
+                get(ours, hash),
+                get(theirs, hash),
+            );
+            fields.extend(merged.map(|v| (hash, v)));
+        }
+        ParamStruct(fields)
+    }
+
+    fn merge_list(
+        &mut self,
+        path: &ParamPath,
+        base: &ParamList,
+        ours: &ParamList,
+        theirs: &ParamList,
+    ) -> ParamList {
+        let Some(field) = key_field(&[base, ours, theirs], self.key_fields) else {
+            let len = base.0.len().max(ours.0.len()).max(theirs.0.len());
+            let merged = (0..len).filter_map(|index| {
+                self.merge(
+                    &path.push(Key::Index(index)),
+                    base.0.get(index),
+                    ours.0.get(index),
+                    theirs.0.get(index),
+                )
+            });
+            return ParamList(merged.collect());
+        };
+
+        let find = |list: &ParamList, key: &KeyValue| {
+            list.0
+                .iter()
+                .position(|elem| key_value(elem, field).as_ref() == Some(key))
+        };
+        let theirs_only = theirs
+            .0
+            .iter()
+            .filter(|elem| find(ours, &key_value(elem, field).unwrap()).is_none());
+        let mut elems = vec![];
+        for elem in ours.0.iter().chain(theirs_only) {
+            let key = key_value(elem, field).unwrap();
+            let merged = self.merge(
+                &path.push(Key::Field(field, key.clone())),
+                find(base, &key).map(|i| &base.0[i]),
+                find(ours, &key).map(|i| &ours.0[i]),
+                find(theirs, &key).map(|i| &theirs.0[i]),
+            );
+            elems.extend(merged);
+        }
+        ParamList(elems)
+    }
+}
+
+/// Conflicting params are taken from the side named by the strategy
+#[pyfunction]
+#[pyo3(signature = (base, ours, theirs, strategy=Strategy::Ours, key_fields=None))]
+pub(crate) fn merge3(
+    py: Python,
+    base: PyRef<Param>,
+    ours: PyRef<Param>,
+    theirs: PyRef<Param>,
+    strategy: Strategy,
+    key_fields: Option<Vec<Hash>>,
+) -> PyResult<(Param, Vec<PyObject>)> {
+    let key_fields = key_fields
+        .unwrap_or_default()
+        .iter()
+        .map(Hash40::from)
+        .collect::<Vec<_>>();
+    let (merged, conflicts) = merge_trees(
+        &ParamKind::try_from(&*base)?,
+        &ParamKind::try_from(&*ours)?,
+        &ParamKind::try_from(&*theirs)?,
+        strategy,
+        &key_fields,
+    );
+    let conflicts = conflicts
+        .into_iter()
+        .map(|c| {
+            (
+                c.path.to_object(py)?,
+                c.base.map(Param::from),
+                c.ours.map(Param::from),
+                c.theirs.map(Param::from),
+            )
+                .into_py_any(py)
+        })
+        .collect::<PyResult<_>>()?;
+    Ok((Param::from(merged), conflicts))
+}
@@ -0,0 +1,180 @@
+use crate::duplicate::Duplicate;
+use crate::history::Savepoint;
+use crate::path::ParamPath;
+use crate::track::same;
+use crate::tree::{Arena, NodeId};
+use crate::{Param, ParamType};
+use prc::ParamKind;
+use pyo3::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::ops::{Deref, DerefMut};
+use std::sync::MutexGuard;
+
+#[derive(Debug, Default)]
+pub(crate) struct Observers {
+    callbacks: Vec<(NodeId, PyObject)>,
+    queue: Vec<(NodeId, ParamType)>,
+}
+
+pub(crate) struct Event {
+    callback: PyObject,
+    path: ParamPath,
+    old: Option<ParamKind>,
+    new: Option<ParamKind>,
+}
+
+impl Observers {
+    pub(crate) fn roots(&self, roots: &mut Vec<NodeId>) {
+        roots.extend(self.callbacks.iter().map(|(id, _)| *id));
+        for (id, old) in &self.queue {
+            roots.push(*id);
+            roots.extend(old.children());
+        }
+    }
+
+    pub(crate) fn absorb(&mut self, other: Observers, moves: &[NodeId]) {
+        self.callbacks.extend(
+            other
+                .callbacks
+                .into_iter()
+                .map(|(id, callback)| (moves[id as usize], callback)),
+        );
+        self.queue.extend(
+            other
+                .queue
+                .into_iter()
+                .map(|(id, old)| (moves[id as usize], old.moved(moves))),
+        );
+    }
+}
+
+impl Arena {
+    pub(crate) fn observe(&mut self, id: NodeId, callback: PyObject) {
+        self.observers.callbacks.push((id, callback));
+    }
+
+    pub(crate) fn notify(&mut self, id: NodeId, old: &ParamType) {
+        if !self.observers.callbacks.is_empty() {
+            self.observers.queue.push((id, old.duplicate()));
+        }
+    }
+
+    /// Each param is compared with what it held before its first change. While a group of
+    /// changes is open, nothing is taken until the outermost one ends
+    pub(crate) fn take_events(&mut self) -> Vec<Event> {
+        if self.in_group() || self.observers.queue.is_empty() {
+            return vec![];
+        }
+        let queue = std::mem::take(&mut self.observers.queue);
+        let mut seen = HashSet::new();
+        // the params below each watched param are only searched once for all of the changes
+        let mut parents = HashMap::new();
+        Python::with_gil(|py| {
+            let mut events = vec![];
+            for (id, old) in &queue {
+                if !seen.insert(*id) || same(old, &self[*id]) {
+                    continue;
+                }
+                for (root, callback) in &self.observers.callbacks {
+                    let parents = parents.entry(*root).or_insert_with(|| self.parents(*root));
+                    let Some(path) = parents.path_to(*id) else {
+                        continue;
+                    };
+                    let touched = self.touched(&path, old, &self[*id]).and_then(|touched| {
+                        touched
+                            .into_iter()
+                            .map(|(path, old)| {
+                                let new = self.find(*root, &path).map(|id| self.to_kind(id));
+                                Ok(Event {
+                                    callback: callback.clone_ref(py),
+                                    new: new.transpose()?,
+                                    path,
+                                    old,
+                                })
+                            })
+                            .collect::<PyResult<Vec<_>>>()
+                    });
+                    // like errors raised by the callbacks, these can't stop the change
+                    match touched {
+                        Ok(touched) => events.extend(touched),
+                        Err(e) => e.write_unraisable(py, Some(callback.bind(py))),
+                    }
+                }
+            }
+            events
+        })
+    }
+}
+
+/// Exceptions can't stop a change which has already been made, so they're reported as
+/// unraisable instead
+pub(crate) fn fire(events: Vec<Event>) {
+    if events.is_empty() {
+        return;
+    }
+    Python::with_gil(|py| {
+        for event in events {
+            let args = event
+                .path
+                .to_object(py)
+                .map(|path| (path, event.old.map(Param::from), event.new.map(Param::from)));
+            let callback = event.callback.bind(py);
+            if let Err(e) = args.and_then(|args| callback.call1(args)) {
+                e.write_unraisable(py, Some(callback));
+            }
+        }
+    });
+}
+
+/// Tells the callbacks watching the tree about the changes once it's released
+pub(crate) struct ArenaMut<'a> {
+    guard: Option<MutexGuard<'a, Arena>>,
+    owner: usize,
+}
+
+impl<'a> ArenaMut<'a> {
+    pub(crate) fn new(guard: MutexGuard<'a, Arena>, owner: usize) -> Self {
+        ArenaMut {
+            guard: Some(guard),
+            owner,
+        }
+    }
+
+    pub(crate) fn set(&mut self, id: NodeId, param: ParamType) {
+        let owner = self.owner;
+        self.deref_mut().set(owner, id, param);
+    }
+
+    pub(crate) fn begin(&mut self) -> Savepoint {
+        let owner = self.owner;
+        self.deref_mut().begin(owner)
+    }
+
+    pub(crate) fn end(&mut self) {
+        let owner = self.owner;
+        self.deref_mut().end(owner);
+    }
+}
+
+impl Deref for ArenaMut<'_> {
+    type Target = Arena;
+
+    fn deref(&self) -> &Arena {
+        self.guard.as_ref().expect("the lock is held until dropped")
+    }
+}
+
+impl DerefMut for ArenaMut<'_> {
+    fn deref_mut(&mut self) -> &mut Arena {
+        self.guard.as_mut().expect("the lock is held until dropped")
+    }
+}
+
+impl Drop for ArenaMut<'_> {
+    fn drop(&mut self) {
+        let events = self.take_events();
+        self.maybe_collect();
+        self.guard = None;
+        fire(events);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::diff::{key_field, key_value};
+use crate::document::{key_hash, locate, parse_yaml, scalar_param, to_doc, Doc};
+use crate::kind::kind_name;
+use crate::labels::Labels;
+use crate::path::{Key, KeyValue};
+use crate::tree::Draft;
+use crate::{Hash, Param};
+use prc::hash40::label_map::LabelMap;
+use prc::hash40::Hash40;
+use prc::ParamKind;
+use pyo3::exceptions::{PyKeyError, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple};
+
+/// Hashes are written as hexadecimal values, and params the same way as `to_yaml` writes them
+fn py_doc(ob: &Bound<'_, PyAny>, labels: &LabelMap) -> PyResult<Doc> {
+    if ob.is_instance_of::<PyBool>() {
+        Ok(Doc::Bool(ob.extract()?))
+    } else if ob.is_instance_of::<PyInt>() {
+        Ok(Doc::Int(ob.extract()?))
+    } else if ob.is_instance_of::<PyFloat>() {
+        Ok(Doc::Float(ob.extract()?))
+    } else if let Ok(s) = ob.downcast::<PyString>() {
+        Ok(Doc::Str(s.to_cow()?.into_owned()))
+    } else if let Ok(h) = ob.downcast::<Hash>() {
+        Ok(Doc::Tagged(
+            9,
+            Box::new(Doc::Str(hash_text(h.borrow().inner))),
+        ))
+    } else if let Ok(p) = ob.downcast::<Param>() {
+        to_doc(&ParamKind::try_from(&*p.borrow())?, None, labels)
+    } else if let Ok(dict) = ob.downcast::<PyDict>() {
+        let entries = dict
+            .iter()
+            .map(|(key, value)| Ok((key_text(&key)?, py_doc(&value, labels)?)))
+            .collect::<PyResult<_>>()?;
+        Ok(Doc::Map(entries))
+    } else if ob.is_instance_of::<PyList>() || ob.is_instance_of::<PyTuple>() {
+        let elems = ob
+            .try_iter()?
+            .map(|elem| py_doc(&elem?, labels))
+            .collect::<PyResult<_>>()?;
+        Ok(Doc::List(elems))
+    } else {
+        Err(PyTypeError::new_err(format!(
+            "Overlays can't hold values of type '{}'",
+            ob.get_type().name()?
+        )))
+    }
+}
+
+fn hash_text(hash: Hash40) -> String {
+    format!("0x{:010x}", hash.0)
+}
+
+fn key_text(key: &Bound<'_, PyAny>) -> PyResult<String> {
+    if let Ok(s) = key.downcast::<PyString>() {
+        Ok(s.to_cow()?.into_owned())
+    } else if key.is_instance_of::<PyInt>() && !key.is_instance_of::<PyBool>() {
+        Ok(key.extract::<i64>()?.to_string())
+    } else if let Ok(h) = key.downcast::<Hash>() {
+        Ok(hash_text(h.borrow().inner))
+    } else {
+        Err(PyTypeError::new_err(
+            "Overlay keys must be strings, integers, or hashes",
+        ))
+    }
+}
+
+fn key_matches(value: &KeyValue, key: &str, labels: &LabelMap) -> bool {
+    match value {
+        KeyValue::Bool(v) => key == v.to_string(),
+        KeyValue::Int(v) => key.parse() == Ok(*v),
+        KeyValue::Hash(v) => key_hash(key, labels).is_ok_and(|hash| hash == *v),
+        KeyValue::Str(v) => key == v,
+    }
+}
+
+/// Every change is found before any are made
+struct Overlay<'a> {
+    labels: &'a Labels,
+    key_fields: &'a [Hash40],
+    path: Vec<String>,
+    changes: Vec<(Param, ParamKind)>,
+    unmatched: Vec<String>,
+}
+
+impl Overlay<'_> {
+    fn visit(&mut self, param: &Param, doc: &Doc) -> PyResult<()> {
+        let kind = param.type_number();
+        match (kind, doc) {
+            (11, Doc::Map(entries)) => {
+                let ParamKind::List(list) = ParamKind::try_from(param)? else {
+                    unreachable!("the param is a list")
+                };
+                let field = key_field(&[&list], self.key_fields);
+                for (key, value) in entries {
+                    let index = match field {
+                        Some(field) => {
+                            let labels = self.labels.inner.lock().unwrap();
+                            list.0.iter().position(|elem| {
+                                key_value(elem, field)
+                                    .is_some_and(|v| key_matches(&v, key, &labels))
+                            })
+                        }
+                        None => key.parse().ok(),
+                    };
+                    self.visit_child(param, index.map(Key::Index), key, value)?;
+                }
+            }
+            (11, Doc::List(elems)) => {
+                for (index, value) in elems.iter().enumerate() {
+                    self.visit_child(param, Some(Key::Index(index)), &index.to_string(), value)?;
+                }
+            }
+            (12, Doc::Map(entries)) => {
+                for (key, value) in entries {
+                    let hash = key_hash(key, &self.labels.inner.lock().unwrap()).ok();
+                    self.visit_child(param, hash.map(Key::Hash), key, value)?;
+                }
+            }
+            (1..=10, Doc::Tagged(tag, _)) if *tag != kind => {
+                return Err(PyTypeError::new_err(format!(
+                    "Cannot set a {} param to a {} value",
+                    kind_name(kind),
+                    kind_name(*tag)
+                )))
+            }
+            (1..=10, Doc::Tagged(_, value)) => self.set(param, kind, value)?,
+            (1..=10, Doc::List(_) | Doc::Map(_)) | (11 | 12, _) => {
+                return Err(PyTypeError::new_err(format!(
+                    "Cannot set a {} param to {}",
+                    kind_name(kind),
+                    doc.describe()
+                )))
+            }
+            (_, value) => self.set(param, kind, value)?,
+        }
+        Ok(())
+    }
+
+    fn visit_child(
+        &mut self,
+        parent: &Param,
+        key: Option<Key>,
+        text: &str,
+        doc: &Doc,
+    ) -> PyResult<()> {
+        self.path.push(text.to_string());
+        let child = match key {
+            Some(key) => parent.child(&key, self.labels)?,
+            None => None,
+        };
+        match child {
+            Some(child) => self.visit(&child, doc)?,
+            None => self.unmatched.push(self.path.join("/")),
+        }
+        self.path.pop();
+        Ok(())
+    }
+
+    fn set(&mut self, param: &Param, kind: u8, doc: &Doc) -> PyResult<()> {
+        let value = scalar_param(kind, doc, &self.labels.inner.lock().unwrap())?;
+        if value != ParamKind::try_from(param)? {
+            self.changes.push((param.clone_ref(), value));
+        }
+        Ok(())
+    }
+}
+
+impl Param {
+    pub(crate) fn overlay(
+        &self,
+        overlay: &Bound<'_, PyAny>,
+        key_fields: &[Hash40],
+        labels: &Labels,
+    ) -> PyResult<()> {
+        // fail before doing any work if the param can't be changed
+        drop(self.lock_mut()?);
+        let changes = {
+            let doc = match overlay.downcast::<PyString>() {
+                Ok(text) => parse_yaml(&text.to_cow()?)?,
+                Err(_) => py_doc(overlay, &labels.inner.lock().unwrap())?,
+            };
+            let mut walk = Overlay {
+                labels,
+                key_fields,
+                path: vec![],
+                changes: vec![],
+                unmatched: vec![],
+            };
+            walk.visit(self, &doc).map_err(|e| locate(e, &walk.path))?;
+            if !walk.unmatched.is_empty() {
+                return Err(PyKeyError::new_err(format!(
+                    "Overlay keys don't match any param: {}",
+                    walk.unmatched.join(", ")
+                )));
+            }
+            walk.changes
+        };
+        if changes.is_empty() {
+            return Ok(());
+        }
+        self.grouped(|| {
+            for (param, value) in changes {
+                param.set_draft(Draft::Kind(value))?;
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,486 @@
+use crate::build::build;
+use crate::convert::from_value;
+use crate::duplicate::Duplicate;
+use crate::labels::{format_label, Labels};
+use crate::tree::{Arena, Draft, NodeId};
+use crate::{Hash, Param, ParamType};
+use prc::hash40::label_map::LabelMap;
+use prc::hash40::Hash40;
+use prc::ParamKind;
+use pyo3::conversion::IntoPyObjectExt;
+use pyo3::exceptions::{PyKeyError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyInt, PyList, PyString, PyTuple};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum Key {
+    Index(usize),
+    Hash(Hash40),
+    /// The first element of a list which is a struct with the field set to the value
+    Field(Hash40, KeyValue),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum KeyValue {
+    Bool(bool),
+    Int(i64),
+    Hash(Hash40),
+    Str(String),
+}
+
+/// From python, paths are tuples of keys, or strings which separate each key with a '/'
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub(crate) struct ParamPath(pub(crate) Vec<Key>);
+
+impl Key {
+    fn extract(ob: &Bound<'_, PyAny>, labels: &Labels) -> PyResult<Self> {
+        if ob.is_instance_of::<PyBool>() {
+            Err(PyTypeError::new_err(
+                "List indices must be integers, not bool",
+            ))
+        } else if ob.is_instance_of::<PyInt>() {
+            Ok(Key::Index(ob.extract()?))
+        } else if let Ok(t) = ob.downcast::<PyTuple>() {
+            let (field, value): (Bound<PyAny>, Bound<PyAny>) = t.extract()?;
+            Ok(Key::Field(
+                Hash::extract_with(&field, labels)?.inner,
+                KeyValue::extract_with(&value, labels)?,
+            ))
+        } else if let Ok(s) = ob.downcast::<PyString>() {
+            Key::parse(&s.to_cow()?, labels)
+        } else {
+            let hash: Hash = ob.extract()?;
+            Ok(Key::Hash(hash.inner))
+        }
+    }
+
+    fn parse(segment: &str, labels: &Labels) -> PyResult<Self> {
+        if let Some(field) = segment.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            let (field, value) = field.split_once('=').ok_or_else(|| {
+                PyValueError::new_err(format!(
+                    "Invalid key '{}'; expected '[field=value]'",
+                    segment
+                ))
+            })?;
+            match Key::parse(field, labels)? {
+                Key::Hash(field) => Ok(Key::Field(field, KeyValue::parse(value))),
+                _ => Err(PyValueError::new_err(format!(
+                    "Invalid key '{}'; the field must be a hash",
+                    segment
+                ))),
+            }
+        } else if !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit()) {
+            segment
+                .parse()
+                .map(Key::Index)
+                .map_err(|_| PyValueError::new_err(format!("Invalid index '{}'", segment)))
+        } else if let Ok(hash) = Hash40::from_hex_str(segment) {
+            Ok(Key::Hash(hash))
+        } else {
+            Ok(Key::Hash(labels.hash(segment)?.inner))
+        }
+    }
+
+    pub(crate) fn to_object(&self, py: Python) -> PyResult<PyObject> {
+        match self {
+            Key::Index(index) => index.into_py_any(py),
+            Key::Hash(hash) => Hash::from(*hash).into_py_any(py),
+            Key::Field(field, value) => (Hash::from(*field), value.to_object(py)?).into_py_any(py),
+        }
+    }
+
+    pub(crate) fn format(&self, labels: &LabelMap) -> String {
+        match self {
+            Key::Index(index) => index.to_string(),
+            Key::Hash(hash) => format_label(labels, *hash),
+            Key::Field(field, value) => format!(
+                "[{}={}]",
+                format_label(labels, *field),
+                value.format(labels)
+            ),
+        }
+    }
+}
+
+impl KeyValue {
+    /// Lists, structs, and floats can't be used as keys
+    pub(crate) fn of(param: &ParamKind) -> Option<Self> {
+        match param {
+            ParamKind::Bool(v) => Some(KeyValue::Bool(*v)),
+            ParamKind::I8(v) => Some(KeyValue::Int(*v as i64)),
+            ParamKind::U8(v) => Some(KeyValue::Int(*v as i64)),
+            ParamKind::I16(v) => Some(KeyValue::Int(*v as i64)),
+            ParamKind::U16(v) => Some(KeyValue::Int(*v as i64)),
+            ParamKind::I32(v) => Some(KeyValue::Int(*v as i64)),
+            ParamKind::U32(v) => Some(KeyValue::Int(*v as i64)),
+            ParamKind::Hash(v) => Some(KeyValue::Hash(*v)),
+            ParamKind::Str(v) => Some(KeyValue::Str(v.clone())),
+            _ => None,
+        }
+    }
+
+    fn of_node(param: &ParamType) -> Option<Self> {
+        match param {
+            ParamType::Bool(v) => Some(KeyValue::Bool(*v)),
+            ParamType::I8(v) => Some(KeyValue::Int(*v as i64)),
+            ParamType::U8(v) => Some(KeyValue::Int(*v as i64)),
+            ParamType::I16(v) => Some(KeyValue::Int(*v as i64)),
+            ParamType::U16(v) => Some(KeyValue::Int(*v as i64)),
+            ParamType::I32(v) => Some(KeyValue::Int(*v as i64)),
+            ParamType::U32(v) => Some(KeyValue::Int(*v as i64)),
+            ParamType::Hash(v) => Some(KeyValue::Hash(v.inner)),
+            ParamType::Str(v) => Some(KeyValue::Str(v.to_string())),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn extract_with(ob: &Bound<'_, PyAny>, labels: &Labels) -> PyResult<Self> {
+        if ob.is_instance_of::<PyBool>() {
+            Ok(KeyValue::Bool(ob.extract()?))
+        } else if ob.is_instance_of::<PyInt>() {
+            Ok(KeyValue::Int(ob.extract()?))
+        } else if let Ok(s) = ob.downcast::<PyString>() {
+            Ok(KeyValue::Str(s.to_cow()?.into_owned()))
+        } else if let Ok(p) = ob.downcast::<Param>() {
+            KeyValue::of(&ParamKind::try_from(&*p.borrow())?).ok_or_else(|| {
+                PyTypeError::new_err("Keys must be bool, integer, hash, or string params")
+            })
+        } else {
+            Ok(KeyValue::Hash(Hash::extract_with(ob, labels)?.inner))
+        }
+    }
+
+    /// Numbers are integers, hexadecimal values are hashes, and anything else is a string
+    fn parse(value: &str) -> Self {
+        match value {
+            "true" => KeyValue::Bool(true),
+            "false" => KeyValue::Bool(false),
+            _ => value
+                .parse()
+                .map(KeyValue::Int)
+                .or_else(|_| Hash40::from_hex_str(value).map(KeyValue::Hash))
+                .unwrap_or_else(|_| KeyValue::Str(value.to_string())),
+        }
+    }
+
+    fn to_object(&self, py: Python) -> PyResult<PyObject> {
+        match self {
+            KeyValue::Bool(v) => v.into_py_any(py),
+            KeyValue::Int(v) => v.into_py_any(py),
+            KeyValue::Hash(v) => Hash::from(*v).into_py_any(py),
+            KeyValue::Str(v) => v.into_py_any(py),
+        }
+    }
+
+    fn format(&self, labels: &LabelMap) -> String {
+        match self {
+            KeyValue::Bool(v) => v.to_string(),
+            KeyValue::Int(v) => v.to_string(),
+            KeyValue::Hash(v) => format_label(labels, *v),
+            KeyValue::Str(v) => v.clone(),
+        }
+    }
+
+    /// Strings are the same as hashes of their label
+    pub(crate) fn matches(&self, other: &KeyValue, labels: &LabelMap) -> bool {
+        match (self, other) {
+            (KeyValue::Str(s), KeyValue::Hash(h)) | (KeyValue::Hash(h), KeyValue::Str(s)) => {
+                labels.hash_of(s).is_some_and(|hash| hash == *h)
+            }
+            _ => self == other,
+        }
+    }
+}
+
+impl Arena {
+    pub(crate) fn find_by(
+        &mut self,
+        list: NodeId,
+        field: Hash40,
+        value: &KeyValue,
+        labels: &LabelMap,
+    ) -> PyResult<Option<usize>> {
+        let ParamType::List(elems) = &self[list] else {
+            return Ok(None);
+        };
+        for (index, elem) in elems.0.clone().into_iter().enumerate() {
+            if self
+                .field_value(elem, field)?
+                .is_some_and(|v| value.matches(&v, labels))
+            {
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+
+    fn field_value(&mut self, id: NodeId, field: Hash40) -> PyResult<Option<KeyValue>> {
+        self.load(id)?;
+        let child = match &self[id] {
+            ParamType::Struct(s) => s.0.iter().find(|(h, _)| *h == field).map(|(_, c)| *c),
+            _ => None,
+        };
+        let Some(child) = child else {
+            return Ok(None);
+        };
+        self.load(child)?;
+        Ok(KeyValue::of_node(&self[child]))
+    }
+}
+
+impl ParamPath {
+    pub(crate) fn parse(path: &str, labels: &Labels) -> PyResult<Self> {
+        path.split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| Key::parse(segment, labels))
+            .collect::<PyResult<_>>()
+            .map(ParamPath)
+    }
+
+    pub(crate) fn extract_with(ob: &Bound<'_, PyAny>, labels: &Labels) -> PyResult<Self> {
+        if let Ok(s) = ob.downcast::<PyString>() {
+            ParamPath::parse(&s.to_cow()?, labels)
+        } else if ob.is_instance_of::<PyTuple>() || ob.is_instance_of::<PyList>() {
+            ob.try_iter()?
+                .map(|key| Key::extract(&key?, labels))
+                .collect::<PyResult<_>>()
+                .map(ParamPath)
+        } else {
+            Key::extract(ob, labels).map(|key| ParamPath(vec![key]))
+        }
+    }
+
+    pub(crate) fn push(&self, key: Key) -> Self {
+        let mut keys = self.0.clone();
+        keys.push(key);
+        ParamPath(keys)
+    }
+
+    pub(crate) fn to_object(&self, py: Python) -> PyResult<PyObject> {
+        let keys = self
+            .0
+            .iter()
+            .map(|key| key.to_object(py))
+            .collect::<PyResult<Vec<_>>>()?;
+        PyTuple::new(py, keys)?.into_py_any(py)
+    }
+
+    pub(crate) fn format(&self, labels: &LabelMap) -> String {
+        self.0
+            .iter()
+            .map(|key| key.format(labels))
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+impl std::fmt::Display for ParamPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let labels = Labels::global();
+        let lock = labels.inner.lock().unwrap();
+        write!(f, "{}", self.format(&lock))
+    }
+}
+
+impl Param {
+    /// For structs with duplicate keys, the first match is used
+    pub(crate) fn child(&self, key: &Key, labels: &Labels) -> PyResult<Option<Param>> {
+        let mut arena = self.lock()?;
+        let Some(key) = self.resolve(&mut arena, key, labels)? else {
+            return Ok(None);
+        };
+        let id = match (&arena[self.id()], &key) {
+            (ParamType::List(v), Key::Index(index)) => v.0.get(*index).copied(),
+            (ParamType::Struct(v), Key::Hash(hash)) => {
+                v.0.iter().find(|(h, _)| h == hash).map(|(_, p)| *p)
+            }
+            _ => None,
+        };
+        Ok(id.map(|id| arena.handle(self, id)))
+    }
+
+    /// Only keys which find a list element by field need the labels
+    fn resolve(&self, arena: &mut Arena, key: &Key, labels: &Labels) -> PyResult<Option<Key>> {
+        Ok(match key {
+            Key::Field(field, value) => {
+                let labels = labels.inner.lock().unwrap();
+                arena
+                    .find_by(self.id(), *field, value, &labels)?
+                    .map(Key::Index)
+            }
+            key => Some(key.clone()),
+        })
+    }
+
+    pub(crate) fn get_path(&self, path: &ParamPath, labels: &Labels) -> PyResult<Param> {
+        path.0
+            .iter()
+            .enumerate()
+            .try_fold(self.clone_ref(), |param, (depth, key)| {
+                param
+                    .child(key, labels)?
+                    .ok_or_else(|| missing(path, depth, labels))
+            })
+    }
+
+    pub(crate) fn replace_child(&self, key: &Key, value: Draft, labels: &Labels) -> PyResult<bool> {
+        let mut arena = self.lock_mut()?;
+        let Some(key) = self.resolve(&mut arena, key, labels)? else {
+            return Ok(false);
+        };
+        let mut param = arena[self.id()].duplicate();
+        let slot = match (&mut param, &key) {
+            (ParamType::List(v), Key::Index(index)) => v.0.get_mut(*index),
+            (ParamType::Struct(v), Key::Hash(hash)) => {
+                v.0.iter_mut().find(|(h, _)| h == hash).map(|(_, p)| p)
+            }
+            (ParamType::List(_), _) => {
+                return Err(PyTypeError::new_err(
+                    "List params must be indexed by integer",
+                ))
+            }
+            (ParamType::Struct(_), _) => {
+                return Err(PyTypeError::new_err(
+                    "Struct params must be indexed by hash",
+                ))
+            }
+            _ => {
+                return Err(PyTypeError::new_err(
+                    "Cannot index params other than list or struct-type params",
+                ))
+            }
+        };
+        let Some(slot) = slot else {
+            return Ok(false);
+        };
+        *slot = arena.place(self.tree(), value)?;
+        arena.set(self.id(), param);
+        Ok(true)
+    }
+
+    /// List elements are inserted before the element at the index, or at the end
+    pub(crate) fn insert_child(&self, key: &Key, value: Draft) -> PyResult<Param> {
+        let mut arena = self.lock_mut()?;
+        let mut param = arena[self.id()].duplicate();
+        let id = match (&mut param, key) {
+            (ParamType::List(v), Key::Index(index)) if *index <= v.0.len() => {
+                let id = arena.place(self.tree(), value)?;
+                v.0.insert(*index, id);
+                id
+            }
+            (ParamType::List(v), Key::Index(index)) => {
+                return Err(PyKeyError::new_err(format!(
+                    "Cannot create index {} in a list of length {}",
+                    index,
+                    v.0.len()
+                )))
+            }
+            (ParamType::Struct(v), Key::Hash(hash)) => {
+                let id = arena.place(self.tree(), value)?;
+                v.0.push((*hash, id));
+                id
+            }
+            _ => {
+                return Err(PyTypeError::new_err(
+                    "Lists can only be extended by index, and structs by hash",
+                ))
+            }
+        };
+        arena.set(self.id(), param);
+        Ok(arena.handle(self, id))
+    }
+
+    pub(crate) fn remove_child(&self, key: &Key, labels: &Labels) -> PyResult<bool> {
+        let mut arena = self.lock_mut()?;
+        let Some(key) = self.resolve(&mut arena, key, labels)? else {
+            return Ok(false);
+        };
+        let mut param = arena[self.id()].duplicate();
+        let removed = match (&mut param, &key) {
+            (ParamType::List(v), Key::Index(index)) if *index < v.0.len() => {
+                v.0.remove(*index);
+                true
+            }
+            (ParamType::Struct(v), Key::Hash(hash)) => {
+                match v.0.iter().position(|(h, _)| h == hash) {
+                    Some(position) => {
+                        v.0.remove(position);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            (ParamType::List(_), Key::Index(_)) => false,
+            _ => {
+                return Err(PyTypeError::new_err(
+                    "Cannot index params other than list or struct-type params",
+                ))
+            }
+        };
+        if removed {
+            arena.set(self.id(), param);
+        }
+        Ok(removed)
+    }
+
+    /// Params are copied in as they are, the same as with indexing. With `create`, a missing
+    /// param is built from the value, along with structs for missing parents
+    pub(crate) fn set_path(
+        &self,
+        path: &ParamPath,
+        value: &Bound<'_, PyAny>,
+        create: bool,
+        labels: &Labels,
+    ) -> PyResult<()> {
+        let Some((last, parents)) = path.0.split_last() else {
+            return self.assign(value);
+        };
+        let mut parent = self.clone_ref();
+        for (depth, key) in parents.iter().enumerate() {
+            parent = match parent.child(key, labels)? {
+                Some(child) => child,
+                None if create => parent.insert_child(key, Draft::Struct(vec![]))?,
+                None => return Err(missing(path, depth, labels)),
+            };
+        }
+        match parent.child(last, labels)? {
+            Some(target) => match value.downcast::<Param>() {
+                Ok(p) => parent
+                    .replace_child(
+                        last,
+                        Draft::Kind(ParamKind::try_from(&*p.borrow())?),
+                        labels,
+                    )
+                    .map(|_| ()),
+                Err(_) => target.assign(value),
+            },
+            None if create => parent
+                .insert_child(last, build(value, None)?.copied()?)
+                .map(|_| ()),
+            None => Err(missing(path, parents.len(), labels)),
+        }
+    }
+
+    /// Lists and structs are rebuilt using their current contents as the template
+    pub(crate) fn assign(&self, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        let draft = match self.type_number() {
+            11 | 12 => build(value, Some(self))?,
+            kind => from_value(kind, value)?,
+        };
+        self.set_draft(draft)
+    }
+}
+
+pub(crate) fn missing(path: &ParamPath, depth: usize, labels: &Labels) -> PyErr {
+    let labels = labels.inner.lock().unwrap();
+    let parent = match depth {
+        0 => String::from("the root param"),
+        _ => format!("'{}'", ParamPath(path.0[..depth].to_vec()).format(&labels)),
+    };
+    PyKeyError::new_err(format!(
+        "Path '{}' does not exist; '{}' was not found in {}",
+        path.format(&labels),
+        path.0[depth].format(&labels),
+        parent
+    ))
+}
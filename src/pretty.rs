@@ -0,0 +1,197 @@
+use crate::labels::Labels;
+use crate::{Param, ParamType};
+use prc::hash40::Hash40;
+use pyo3::prelude::*;
+use pyo3::types::PyBool;
+use std::fmt::Write;
+
+/// Hashes are shown as hexadecimal values when there are no labels
+pub(crate) struct TreeFormat {
+    pub(crate) labels: Option<Labels>,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) max_items: Option<usize>,
+}
+
+pub(crate) struct LabelsArg(pub(crate) Option<Labels>);
+
+impl<'py> FromPyObject<'py> for LabelsArg {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if let Ok(b) = ob.downcast::<PyBool>() {
+            Ok(LabelsArg(b.is_true().then(Labels::global)))
+        } else {
+            Ok(LabelsArg(Some(ob.extract()?)))
+        }
+    }
+}
+
+/// A param as text, with its first children and their keys, and how many children it has
+type Description = (String, Vec<(String, Param)>, usize);
+
+impl TreeFormat {
+    fn hash(&self, hash: Hash40) -> String {
+        match &self.labels {
+            Some(labels) => labels.label(hash),
+            None => format!("0x{:010x}", hash.0),
+        }
+    }
+
+    pub(crate) fn text(&self, param: &Param) -> PyResult<String> {
+        let mut out = String::new();
+        self.write_text(&mut out, param, None, 0)?;
+        out.pop();
+        Ok(out)
+    }
+
+    fn write_text(
+        &self,
+        out: &mut String,
+        param: &Param,
+        key: Option<String>,
+        depth: usize,
+    ) -> PyResult<()> {
+        let indent = "  ".repeat(depth);
+        let prefix = key.map(|key| format!("{}: ", key)).unwrap_or_default();
+        let (description, children, len) = self.describe(param, self.limit(depth))?;
+        let hidden = self.max_depth.is_some_and(|max| depth >= max) && len > 0;
+        let _ = writeln!(
+            out,
+            "{}{}{}{}",
+            indent,
+            prefix,
+            description,
+            if hidden { " ..." } else { "" }
+        );
+        if hidden {
+            return Ok(());
+        }
+        let shown = children.len();
+        for (key, child) in children {
+            self.write_text(out, &child, Some(key), depth + 1)?;
+        }
+        if len > shown {
+            let _ = writeln!(out, "{}  ... {} more", indent, len - shown);
+        }
+        Ok(())
+    }
+
+    /// Lists and structs are collapsible, and only the root starts out expanded
+    pub(crate) fn html(&self, param: &Param) -> PyResult<String> {
+        let mut out = String::from(r#"<div style="font-family: monospace">"#);
+        self.write_html(&mut out, param, None, 0)?;
+        out.push_str("</div>");
+        Ok(out)
+    }
+
+    fn write_html(
+        &self,
+        out: &mut String,
+        param: &Param,
+        key: Option<String>,
+        depth: usize,
+    ) -> PyResult<()> {
+        let prefix = key
+            .map(|key| format!("<b>{}</b>: ", escape_html(&key)))
+            .unwrap_or_default();
+        let (description, children, len) = self.describe(param, self.limit(depth))?;
+        let description = escape_html(&description);
+        if len == 0 {
+            let _ = write!(out, "<div>{}{}</div>", prefix, description);
+            return Ok(());
+        }
+        if self.max_depth.is_some_and(|max| depth >= max) {
+            let _ = write!(out, "<div>{}{} ...</div>", prefix, description);
+            return Ok(());
+        }
+        let _ = write!(
+            out,
+            r#"<details{}><summary>{}{}</summary><div style="margin-left: 1.5em">"#,
+            if depth == 0 { " open" } else { "" },
+            prefix,
+            description
+        );
+        let shown = children.len();
+        for (key, child) in children {
+            self.write_html(out, &child, Some(key), depth + 1)?;
+        }
+        if len > shown {
+            let _ = write!(out, "<div>... {} more</div>", len - shown);
+        }
+        out.push_str("</div></details>");
+        Ok(())
+    }
+
+    fn limit(&self, depth: usize) -> usize {
+        match self.max_depth {
+            Some(max) if depth >= max => 0,
+            _ => self.max_items.unwrap_or(usize::MAX),
+        }
+    }
+
+    fn describe(&self, param: &Param, limit: usize) -> PyResult<Description> {
+        let arena = param.lock()?;
+        let value = &arena[param.id()];
+        let (name, text) = value.summary(|hash| self.hash(hash));
+        let header = format!("{} ({})", name, text);
+        Ok(match value {
+            ParamType::List(v) => (
+                header,
+                v.0.iter()
+                    .take(limit)
+                    .enumerate()
+                    .map(|(index, child)| (index.to_string(), arena.handle(param, *child)))
+                    .collect(),
+                v.0.len(),
+            ),
+            ParamType::Struct(v) => (
+                header,
+                v.0.iter()
+                    .take(limit)
+                    .map(|(hash, child)| (self.hash(*hash), arena.handle(param, *child)))
+                    .collect(),
+                v.0.len(),
+            ),
+            _ => (header, vec![], 0),
+        })
+    }
+
+    pub(crate) fn value_text(&self, param: &ParamType) -> String {
+        param.summary(|hash| self.hash(hash)).1
+    }
+}
+
+impl ParamType {
+    /// Lists and structs give their length instead of a value
+    pub(crate) fn summary(&self, hash: impl Fn(Hash40) -> String) -> (&'static str, String) {
+        match self {
+            ParamType::Bool(v) => ("bool", v.to_string()),
+            ParamType::I8(v) => ("i8", v.to_string()),
+            ParamType::U8(v) => ("u8", v.to_string()),
+            ParamType::I16(v) => ("i16", v.to_string()),
+            ParamType::U16(v) => ("u16", v.to_string()),
+            ParamType::I32(v) => ("i32", v.to_string()),
+            ParamType::U32(v) => ("u32", v.to_string()),
+            ParamType::Float(v) => ("float", v.to_string()),
+            ParamType::Hash(v) => ("hash", hash(v.inner)),
+            ParamType::Str(v) => ("str", v.to_string()),
+            ParamType::List(v) => ("list", format!("len = {}", v.0.len())),
+            ParamType::Struct(v) => ("struct", format!("len = {}", v.0.len())),
+            ParamType::Lazy(..) => {
+                unreachable!("handles refer to loaded params in their own tree")
+            }
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,233 @@
+use crate::kind::{extract_kind, kind_object};
+use crate::labels::Labels;
+use crate::path::{Key, ParamPath};
+use crate::{Hash, Param};
+use prc::hash40::Hash40;
+use prc::ParamKind;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyInt, PyString};
+use std::collections::HashSet;
+
+/// Numbers match any numeric type with the same value, and strings also match hashes of the
+/// same label
+#[derive(Debug, Clone)]
+enum Needle {
+    Bool(bool),
+    Number(f64),
+    Hash(Hash40),
+    Str(String, Option<Hash40>),
+}
+
+impl Needle {
+    fn extract(ob: &Bound<'_, PyAny>, labels: &Labels) -> PyResult<Self> {
+        if ob.is_instance_of::<PyBool>() {
+            Ok(Needle::Bool(ob.extract()?))
+        } else if ob.is_instance_of::<PyInt>() {
+            Ok(Needle::Number(ob.extract::<i64>()? as f64))
+        } else if ob.is_instance_of::<PyFloat>() {
+            Ok(Needle::Number(ob.extract()?))
+        } else if let Ok(s) = ob.downcast::<PyString>() {
+            let s = s.to_cow()?;
+            let hash = labels.hash(&s).ok().map(|hash| hash.inner);
+            Ok(Needle::Str(s.into_owned(), hash))
+        } else if let Ok(p) = ob.downcast::<Param>() {
+            match ParamKind::try_from(&*p.borrow())? {
+                ParamKind::Bool(v) => Ok(Needle::Bool(v)),
+                ParamKind::Float(v) => Ok(Needle::Number(v as f64)),
+                ParamKind::Hash(v) => Ok(Needle::Hash(v)),
+                ParamKind::Str(v) => Ok(Needle::Str(v, None)),
+                ParamKind::List(_) | ParamKind::Struct(_) => Err(PyTypeError::new_err(
+                    "Cannot search for list or struct-type params",
+                )),
+                int => Ok(Needle::Number(
+                    int_value(&int).expect("the param is an integer") as f64,
+                )),
+            }
+        } else {
+            Ok(Needle::Hash(Hash::extract_with(ob, labels)?.inner))
+        }
+    }
+
+    fn matches(&self, param: &ParamKind) -> bool {
+        match (self, param) {
+            (Needle::Bool(a), ParamKind::Bool(b)) => a == b,
+            (Needle::Number(a), ParamKind::Float(b)) => *a as f32 == *b,
+            (Needle::Number(a), param) => int_value(param).is_some_and(|b| *a == b as f64),
+            (Needle::Hash(a), ParamKind::Hash(b)) => a == b,
+            (Needle::Str(a, _), ParamKind::Str(b)) => a == b,
+            (Needle::Str(_, Some(a)), ParamKind::Hash(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+fn int_value(param: &ParamKind) -> Option<i64> {
+    match param {
+        ParamKind::I8(v) => Some(*v as i64),
+        ParamKind::U8(v) => Some(*v as i64),
+        ParamKind::I16(v) => Some(*v as i64),
+        ParamKind::U16(v) => Some(*v as i64),
+        ParamKind::I32(v) => Some(*v as i64),
+        ParamKind::U32(v) => Some(*v as i64),
+        _ => None,
+    }
+}
+
+pub(crate) fn kind_number(param: &ParamKind) -> u8 {
+    match param {
+        ParamKind::Bool(_) => 1,
+        ParamKind::I8(_) => 2,
+        ParamKind::U8(_) => 3,
+        ParamKind::I16(_) => 4,
+        ParamKind::U16(_) => 5,
+        ParamKind::I32(_) => 6,
+        ParamKind::U32(_) => 7,
+        ParamKind::Float(_) => 8,
+        ParamKind::Hash(_) => 9,
+        ParamKind::Str(_) => 10,
+        ParamKind::List(_) => 11,
+        ParamKind::Struct(_) => 12,
+    }
+}
+
+/// Lists and structs are only matched when the type asks for them
+#[derive(Debug, Clone)]
+pub(crate) struct Query {
+    value: Option<Needle>,
+    kind: Option<u8>,
+    key: Option<Hash40>,
+}
+
+impl Query {
+    pub(crate) fn new(
+        value: Option<&Bound<'_, PyAny>>,
+        kind: Option<&Bound<'_, PyAny>>,
+        key: Option<&Bound<'_, PyAny>>,
+        labels: &Labels,
+    ) -> PyResult<Self> {
+        Ok(Query {
+            value: value
+                .filter(|value| !value.is_none())
+                .map(|value| Needle::extract(value, labels))
+                .transpose()?,
+            kind: kind.map(extract_kind).transpose()?,
+            key: key
+                .map(|key| Hash::extract_with(key, labels).map(|hash| hash.inner))
+                .transpose()?,
+        })
+    }
+
+    fn matches(&self, key: Option<&Key>, param: &ParamKind) -> bool {
+        let kind = kind_number(param);
+        match self.kind {
+            Some(wanted) if wanted != kind => return false,
+            None if kind > 10 => return false,
+            _ => {}
+        }
+        if let Some(hash) = self.key {
+            if key != Some(&Key::Hash(hash)) {
+                return false;
+            }
+        }
+        self.value
+            .as_ref()
+            .is_none_or(|needle| needle.matches(param))
+    }
+
+    pub(crate) fn search(&self, root: &ParamKind) -> Vec<(ParamPath, u8)> {
+        let mut found = vec![];
+        self.visit(root, &mut vec![], &mut found);
+        found
+    }
+
+    fn visit(&self, param: &ParamKind, keys: &mut Vec<Key>, found: &mut Vec<(ParamPath, u8)>) {
+        if !keys.is_empty() && self.matches(keys.last(), param) {
+            found.push((ParamPath(keys.clone()), kind_number(param)));
+        }
+        let mut visit_child = |key, child| {
+            keys.push(key);
+            self.visit(child, keys, found);
+            keys.pop();
+        };
+        match param {
+            ParamKind::List(v) => {
+                for (index, child) in v.0.iter().enumerate() {
+                    visit_child(Key::Index(index), child);
+                }
+            }
+            ParamKind::Struct(v) => {
+                for (hash, child) in &v.0 {
+                    visit_child(Key::Hash(*hash), child);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    /// Ordered by param type number
+    counts: [usize; 12],
+    depth: usize,
+    hashes: HashSet<Hash40>,
+    strings: HashSet<String>,
+}
+
+impl Stats {
+    fn visit(&mut self, param: &ParamKind, depth: usize) {
+        self.counts[kind_number(param) as usize - 1] += 1;
+        self.depth = self.depth.max(depth);
+        match param {
+            ParamKind::Hash(v) => {
+                self.hashes.insert(*v);
+            }
+            ParamKind::Str(v) => {
+                self.strings.insert(v.clone());
+            }
+            ParamKind::List(v) => {
+                for child in &v.0 {
+                    self.visit(child, depth + 1);
+                }
+            }
+            ParamKind::Struct(v) => {
+                for (hash, child) in &v.0 {
+                    self.hashes.insert(*hash);
+                    self.visit(child, depth + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Param {
+    pub(crate) fn find_paths(&self, py: Python, query: &Query) -> PyResult<Vec<PyObject>> {
+        query
+            .search(&ParamKind::try_from(self)?)
+            .into_iter()
+            .map(|(path, _)| path.to_object(py))
+            .collect()
+    }
+
+    pub(crate) fn tree_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let mut stats = Stats::default();
+        stats.visit(&ParamKind::try_from(self)?, 0);
+        let types = PyDict::new(py);
+        for (index, count) in stats.counts.iter().enumerate() {
+            types.set_item(kind_object(py, index as u8 + 1)?, count)?;
+        }
+        let dict = PyDict::new(py);
+        dict.set_item("nodes", stats.counts.iter().sum::<usize>())?;
+        dict.set_item("types", types)?;
+        dict.set_item("depth", stats.depth)?;
+        dict.set_item("unique_hashes", stats.hashes.len())?;
+        dict.set_item("unique_strings", stats.strings.len())?;
+        dict.set_item(
+            "string_table_size",
+            stats.strings.iter().map(|s| s.len() + 1).sum::<usize>(),
+        )?;
+        Ok(dict)
+    }
+}
@@ -0,0 +1,142 @@
+use crate::labels::{format_label, Labels};
+use crate::path::ParamPath;
+use crate::{Param, ParamList2, ParamStruct2, ParamType};
+use pyo3::conversion::IntoPyObjectExt;
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum KeyOrder {
+    Label,
+    Hash,
+}
+
+impl<'py> FromPyObject<'py> for KeyOrder {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match &*ob.downcast::<PyString>()?.to_cow()? {
+            "label" => Ok(KeyOrder::Label),
+            "hash" => Ok(KeyOrder::Hash),
+            other => Err(PyValueError::new_err(format!(
+                "Unknown key order '{}'; expected 'label' or 'hash'",
+                other
+            ))),
+        }
+    }
+}
+
+impl Param {
+    /// Hashes sort by their value
+    fn sort_value(&self, py: Python) -> PyResult<PyObject> {
+        let hash = match &self.lock()?[self.id()] {
+            ParamType::Hash(v) => Some(v.inner.0),
+            ParamType::List(_) | ParamType::Struct(_) => {
+                return Err(PyTypeError::new_err(
+                    "Cannot sort by list or struct-type params",
+                ))
+            }
+            _ => None,
+        };
+        match hash {
+            Some(hash) => hash.into_py_any(py),
+            None => self.get_value(py),
+        }
+    }
+
+    /// Elements with equal keys keep their order
+    pub(crate) fn sort_list(
+        &self,
+        py: Python,
+        key: Option<&Bound<'_, PyAny>>,
+        reverse: bool,
+        labels: &Labels,
+    ) -> PyResult<()> {
+        let (ids, elems) = {
+            let arena = self.lock()?;
+            let ParamType::List(v) = &arena[self.id()] else {
+                return Err(PyTypeError::new_err(
+                    "Cannot sort params other than list-type params",
+                ));
+            };
+            let elems =
+                v.0.iter()
+                    .map(|id| arena.handle(self, *id))
+                    .collect::<Vec<_>>();
+            (v.0.clone(), elems)
+        };
+
+        let keys = match key {
+            None => elems
+                .iter()
+                .map(|elem| elem.sort_value(py))
+                .collect::<PyResult<Vec<_>>>()?,
+            Some(key) if key.is_callable() => elems
+                .into_iter()
+                .map(|elem| key.call1((elem,)).map(Bound::unbind))
+                .collect::<PyResult<Vec<_>>>()?,
+            Some(key) => {
+                let path = ParamPath::extract_with(key, labels)?;
+                elems
+                    .iter()
+                    .map(|elem| elem.get_path(&path, labels)?.sort_value(py))
+                    .collect::<PyResult<Vec<_>>>()?
+            }
+        };
+
+        let mut order = (0..ids.len()).collect::<Vec<_>>();
+        let mut error = None;
+        order.sort_by(|&a, &b| {
+            if error.is_some() {
+                return Ordering::Equal;
+            }
+            match keys[a].bind(py).compare(keys[b].bind(py)) {
+                Ok(ordering) if reverse => ordering.reverse(),
+                Ok(ordering) => ordering,
+                Err(e) => {
+                    error = Some(e);
+                    Ordering::Equal
+                }
+            }
+        });
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        let mut arena = self.lock_mut()?;
+        if !matches!(&arena[self.id()], ParamType::List(v) if v.0 == ids) {
+            return Err(PyRuntimeError::new_err(
+                "The list was changed while it was being sorted",
+            ));
+        }
+        let sorted = order
+            .into_iter()
+            .map(|index| ids[index])
+            .collect::<Vec<_>>();
+        if sorted != ids {
+            arena.set(self.id(), ParamType::List(ParamList2(sorted)));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn sort_fields(&self, by: KeyOrder, labels: &Labels) -> PyResult<()> {
+        let mut arena = self.lock_mut()?;
+        let ParamType::Struct(v) = &arena[self.id()] else {
+            return Err(PyTypeError::new_err(
+                "Cannot sort keys of params other than struct-type params",
+            ));
+        };
+        let mut fields = v.0.clone();
+        match by {
+            KeyOrder::Label => {
+                let labels = labels.inner.lock().unwrap();
+                fields.sort_by_cached_key(|(hash, _)| format_label(&labels, *hash));
+            }
+            KeyOrder::Hash => fields.sort_by_key(|(hash, _)| hash.0),
+        }
+        if fields != v.0 {
+            arena.set(self.id(), ParamType::Struct(ParamStruct2(fields)));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+use crate::{Param, ParamType};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+impl Param {
+    pub(crate) fn string_counts<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let mut arena = self.lock()?;
+        let mut counts: Vec<(Arc<str>, usize)> = vec![];
+        let mut index: HashMap<Arc<str>, usize> = HashMap::new();
+        let mut stack = vec![self.id()];
+        while let Some(id) = stack.pop() {
+            arena.load(id)?;
+            match &arena[id] {
+                ParamType::Str(v) => match index.get(v) {
+                    Some(&position) => counts[position].1 += 1,
+                    None => {
+                        index.insert(v.clone(), counts.len());
+                        counts.push((v.clone(), 1));
+                    }
+                },
+                ParamType::List(v) => stack.extend(v.0.iter().rev()),
+                ParamType::Struct(v) => stack.extend(v.0.iter().rev().map(|(_, c)| *c)),
+                _ => {}
+            }
+        }
+        drop(arena);
+        let dict = PyDict::new(py);
+        for (string, count) in counts {
+            dict.set_item(&*string, count)?;
+        }
+        Ok(dict)
+    }
+
+    /// The changes are undone together
+    pub(crate) fn rename_string(&self, old: &str, new: &str) -> PyResult<usize> {
+        let mut arena = self.lock_mut()?;
+        let mut found = vec![];
+        let mut stack = vec![self.id()];
+        while let Some(id) = stack.pop() {
+            arena.load(id)?;
+            match &arena[id] {
+                ParamType::Str(v) if &**v == old => found.push(id),
+                ParamType::List(v) => stack.extend(v.0.iter().rev()),
+                ParamType::Struct(v) => stack.extend(v.0.iter().rev().map(|(_, c)| *c)),
+                _ => {}
+            }
+        }
+        let count = found.len();
+        if count > 0 && old != new {
+            let new: Arc<str> = new.into();
+            arena.begin();
+            for id in found {
+                arena.set(id, ParamType::Str(new.clone()));
+            }
+            arena.end();
+        }
+        Ok(count)
+    }
+}
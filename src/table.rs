@@ -0,0 +1,306 @@
+use crate::build::{build, list_elements, struct_fields};
+use crate::convert::parse_value;
+use crate::document::locate;
+use crate::labels::Labels;
+use crate::pretty::TreeFormat;
+use crate::tree::Draft;
+use crate::{Param, ParamType};
+use prc::hash40::Hash40;
+use prc::ParamKind;
+use pyo3::conversion::IntoPyObjectExt;
+use pyo3::exceptions::{PyKeyError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyString};
+
+type Row = Vec<(Hash40, Param)>;
+
+impl Param {
+    fn rows(&self) -> PyResult<(Vec<Hash40>, Vec<Row>)> {
+        if self.type_number() != 11 {
+            return Err(PyTypeError::new_err(
+                "Tables can only be made from list-type params",
+            ));
+        }
+        let rows = list_elements(self)?
+            .iter()
+            .map(|elem| match elem.type_number() {
+                12 => struct_fields(elem),
+                _ => Err(PyTypeError::new_err(
+                    "Tables can only be made from lists of struct-type params",
+                )),
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        let mut columns = vec![];
+        for (hash, _) in rows.iter().flatten() {
+            if !columns.contains(hash) {
+                columns.push(*hash);
+            }
+        }
+        Ok((columns, rows))
+    }
+
+    /// Hashes become labels, so that columns of them are strings
+    fn cell(&self, py: Python, labels: &Labels) -> PyResult<PyObject> {
+        let hash = match &self.lock()?[self.id()] {
+            ParamType::Hash(v) => Some(v.inner),
+            ParamType::List(_) | ParamType::Struct(_) => return self.clone_ref().into_py_any(py),
+            _ => None,
+        };
+        match hash {
+            Some(hash) => labels.label(hash).into_py_any(py),
+            None => self.get_value(py),
+        }
+    }
+
+    pub(crate) fn table<'py>(
+        &self,
+        py: Python<'py>,
+        labels: &Labels,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let (columns, rows) = self.rows()?;
+        let table = PyDict::new(py);
+        for column in columns {
+            let values = rows
+                .iter()
+                .map(|row| match row.iter().find(|(h, _)| *h == column) {
+                    Some((_, param)) => param.cell(py, labels),
+                    None => Ok(py.None()),
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            table.set_item(labels.label(column), values)?;
+        }
+        Ok(table)
+    }
+
+    pub(crate) fn write_csv(&self, filename: &str, labels: &Labels) -> PyResult<()> {
+        let (columns, rows) = self.rows()?;
+        let format = TreeFormat {
+            labels: Some(labels.clone()),
+            max_depth: None,
+            max_items: None,
+        };
+        let mut texts = vec![vec![]; rows.len()];
+        let mut header = vec![];
+        for column in columns {
+            let cells = rows
+                .iter()
+                .map(|row| {
+                    let Some((_, param)) = row.iter().find(|(h, _)| *h == column) else {
+                        return Ok(Some(String::new()));
+                    };
+                    Ok(match &param.lock()?[param.id()] {
+                        ParamType::List(_) | ParamType::Struct(_) => None,
+                        value => Some(format.value_text(value)),
+                    })
+                })
+                .collect::<PyResult<Vec<_>>>()?
+                .into_iter()
+                .collect::<Option<Vec<_>>>();
+            if let Some(cells) = cells {
+                header.push(labels.label(column));
+                for (text, cell) in texts.iter_mut().zip(cells) {
+                    text.push(cell);
+                }
+            }
+        }
+        let mut writer = csv::Writer::from_path(filename).map_err(csv_error)?;
+        writer.write_record(&header).map_err(csv_error)?;
+        for text in texts {
+            writer.write_record(&text).map_err(csv_error)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// For a list, its first element is used
+fn row_template(template: &Param) -> PyResult<Param> {
+    let template = match template.type_number() {
+        11 => list_elements(template)?.into_iter().next(),
+        _ => Some(template.clone_ref()),
+    };
+    template
+        .filter(|template| template.type_number() == 12)
+        .ok_or_else(|| PyTypeError::new_err("The template must be a struct, or a list of structs"))
+}
+
+fn template_columns(
+    names: &[String],
+    fields: &Row,
+    labels: &Labels,
+) -> PyResult<Vec<(Hash40, Param)>> {
+    names
+        .iter()
+        .map(|name| {
+            let hash = match Hash40::from_hex_str(name) {
+                Ok(hash) => hash,
+                Err(_) => labels.hash(name)?.inner,
+            };
+            fields
+                .iter()
+                .find(|(h, _)| *h == hash)
+                .map(|(_, field)| (hash, field.clone_ref()))
+                .ok_or_else(|| {
+                    PyKeyError::new_err(format!("Column '{}' is not a field of the template", name))
+                })
+        })
+        .collect()
+}
+
+/// Strings in hash columns can be labels or hexadecimal hash values
+fn cell_draft(value: &Bound<'_, PyAny>, field: &Param, labels: &Labels) -> PyResult<Draft> {
+    match value.downcast::<PyString>() {
+        Ok(text) if field.type_number() == 9 => {
+            let text = text.to_cow()?;
+            let hash = match Hash40::from_hex_str(&text) {
+                Ok(hash) => hash,
+                Err(_) => labels.hash(&text)?.inner,
+            };
+            Ok(Draft::Kind(ParamKind::Hash(hash)))
+        }
+        _ => build(value, Some(field))?.copied(),
+    }
+}
+
+/// Fields given without a value are left out, and fields which aren't given at all are copied
+/// from the template
+fn row_from(fields: &Row, mut given: Vec<(Hash40, Option<Draft>)>) -> PyResult<Draft> {
+    let mut row = vec![];
+    for (hash, field) in fields {
+        let draft = match given.iter().position(|(h, _)| h == hash) {
+            Some(index) => given.swap_remove(index).1,
+            None => Some(Draft::Kind(ParamKind::try_from(field)?)),
+        };
+        row.extend(draft.map(|draft| (*hash, draft)));
+    }
+    Ok(Draft::Struct(row))
+}
+
+/// Rows are either a dict of columns such as `to_table` returns, or a list of dicts
+pub(crate) fn from_table(
+    rows: &Bound<'_, PyAny>,
+    template: &Param,
+    labels: &Labels,
+) -> PyResult<Param> {
+    let fields = struct_fields(&row_template(template)?)?;
+    let (names, records) = match rows.downcast::<PyDict>() {
+        Ok(columns) => {
+            let columns = columns
+                .iter()
+                .map(|(name, values)| {
+                    let values = values.try_iter()?.collect::<PyResult<Vec<_>>>()?;
+                    Ok((name.extract::<String>()?, values))
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            let len = columns.first().map_or(0, |(_, values)| values.len());
+            if columns.iter().any(|(_, values)| values.len() != len) {
+                return Err(PyValueError::new_err(
+                    "Every column of the table must be the same length",
+                ));
+            }
+            let records = (0..len)
+                .map(|index| {
+                    columns
+                        .iter()
+                        .map(|(_, values)| Some(values[index].clone()))
+                        .collect()
+                })
+                .collect::<Vec<Vec<_>>>();
+            (columns.into_iter().map(|(name, _)| name).collect(), records)
+        }
+        Err(_) => {
+            let rows = rows
+                .try_iter()?
+                .map(|row| {
+                    row?.downcast::<PyDict>()?
+                        .iter()
+                        .map(|(name, value)| Ok((name.extract::<String>()?, value)))
+                        .collect::<PyResult<Vec<_>>>()
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            // rows without a key someone else has are missing that field
+            let mut names = vec![];
+            for (name, _) in rows.iter().flatten() {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+            let records = rows
+                .into_iter()
+                .map(|row| {
+                    names
+                        .iter()
+                        .map(|name| row.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone()))
+                        .collect()
+                })
+                .collect();
+            (names, records)
+        }
+    };
+    let columns = template_columns(&names, &fields, labels)?;
+    let elems = records
+        .into_iter()
+        .enumerate()
+        .map(|(index, record)| {
+            let given = columns
+                .iter()
+                .zip(&names)
+                .zip(record)
+                .map(|(((hash, field), name), value)| {
+                    let draft = match value.filter(|value| !value.is_none()) {
+                        Some(value) => Some(
+                            cell_draft(&value, field, labels)
+                                .map_err(|e| locate(e, &[index.to_string(), name.clone()]))?,
+                        ),
+                        None => None,
+                    };
+                    Ok((*hash, draft))
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            row_from(&fields, given)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    Param::from_draft(Draft::List(elems))
+}
+
+pub(crate) fn from_csv(filename: &str, template: &Param, labels: &Labels) -> PyResult<Param> {
+    let fields = struct_fields(&row_template(template)?)?;
+    let mut reader = csv::Reader::from_path(filename).map_err(csv_error)?;
+    let names = reader
+        .headers()
+        .map_err(csv_error)?
+        .iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    let columns = template_columns(&names, &fields, labels)?;
+    let label_map = labels.inner.lock().unwrap();
+    let elems = reader
+        .records()
+        .enumerate()
+        .map(|(index, record)| {
+            let record = record.map_err(csv_error)?;
+            let given = columns
+                .iter()
+                .zip(&record)
+                .map(|((hash, field), text)| match text {
+                    "" => Ok((*hash, None)),
+                    _ => parse_value(field.type_number(), text, &label_map)
+                        .map(|value| (*hash, Some(Draft::Kind(value))))
+                        .map_err(|e| PyValueError::new_err(format!("Row {}: {}", index + 1, e))),
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            row_from(&fields, given)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    Param::from_draft(Draft::List(elems))
+}
+
+fn csv_error(e: csv::Error) -> PyErr {
+    if !e.is_io_error() {
+        return PyValueError::new_err(format!("Invalid CSV: {}", e));
+    }
+    match e.into_kind() {
+        csv::ErrorKind::Io(e) => e.into(),
+        _ => unreachable!("the error is an io error"),
+    }
+}
@@ -0,0 +1,229 @@
+use crate::path::{Key, ParamPath};
+use crate::tree::{Arena, NodeId};
+use crate::ParamType;
+use prc::ParamKind;
+use pyo3::PyResult;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug)]
+pub(crate) struct Tracker {
+    /// Paths in the change log are relative to this param
+    pub(crate) root: NodeId,
+    pub(crate) edits: Vec<(NodeId, ParamType)>,
+}
+
+/// Added params have no old value, and removed params have no new value
+pub(crate) type Tracked = (ParamPath, Option<ParamKind>, Option<ParamKind>);
+
+impl Tracker {
+    pub(crate) fn new(root: NodeId) -> Self {
+        Tracker {
+            root,
+            edits: vec![],
+        }
+    }
+
+    pub(crate) fn roots(&self, roots: &mut Vec<NodeId>) {
+        roots.push(self.root);
+        for (id, old) in &self.edits {
+            roots.push(*id);
+            roots.extend(old.children());
+        }
+    }
+
+    pub(crate) fn moved(self, moves: &[NodeId]) -> Self {
+        Tracker {
+            root: moves[self.root as usize],
+            edits: self
+                .edits
+                .into_iter()
+                .map(|(id, old)| (moves[id as usize], old.moved(moves)))
+                .collect(),
+        }
+    }
+}
+
+pub(crate) struct Parents(HashMap<NodeId, Option<(NodeId, Key)>>);
+
+impl Parents {
+    pub(crate) fn path_to(&self, mut id: NodeId) -> Option<ParamPath> {
+        let mut keys = vec![];
+        while let Some((parent, key)) = self.0.get(&id)? {
+            keys.push(key.clone());
+            id = *parent;
+        }
+        keys.reverse();
+        Some(ParamPath(keys))
+    }
+}
+
+pub(crate) fn same(a: &ParamType, b: &ParamType) -> bool {
+    match (a, b) {
+        (ParamType::Bool(a), ParamType::Bool(b)) => a == b,
+        (ParamType::I8(a), ParamType::I8(b)) => a == b,
+        (ParamType::U8(a), ParamType::U8(b)) => a == b,
+        (ParamType::I16(a), ParamType::I16(b)) => a == b,
+        (ParamType::U16(a), ParamType::U16(b)) => a == b,
+        (ParamType::I32(a), ParamType::I32(b)) => a == b,
+        (ParamType::U32(a), ParamType::U32(b)) => a == b,
+        (ParamType::Float(a), ParamType::Float(b)) => a.to_bits() == b.to_bits(),
+        (ParamType::Hash(a), ParamType::Hash(b)) => a == b,
+        (ParamType::Str(a), ParamType::Str(b)) => a == b,
+        (ParamType::List(a), ParamType::List(b)) => a.0 == b.0,
+        (ParamType::Struct(a), ParamType::Struct(b)) => a.0 == b.0,
+        (ParamType::Lazy(_, a), ParamType::Lazy(_, b)) => a == b,
+        _ => false,
+    }
+}
+
+impl Arena {
+    /// Changes which were undone or set back don't count
+    pub(crate) fn is_dirty(&self, tracker: &Tracker) -> bool {
+        let paths = self.paths(tracker.root);
+        let mut seen = HashSet::new();
+        tracker
+            .edits
+            .iter()
+            .any(|(id, old)| paths.contains_key(id) && seen.insert(*id) && !same(old, &self[*id]))
+    }
+
+    /// Params which hold the same value as they did before are left out
+    pub(crate) fn changes(&self, tracker: &Tracker) -> PyResult<Vec<Tracked>> {
+        let paths = self.paths(tracker.root);
+
+        // pair each edit with what the param contained straight after it
+        let mut current: HashMap<NodeId, &ParamType> = HashMap::new();
+        let mut edits = vec![];
+        for (id, old) in tracker.edits.iter().rev() {
+            let new = current.insert(*id, old).unwrap_or(&self[*id]);
+            edits.push((*id, old, new));
+        }
+        edits.reverse();
+
+        let mut seen = HashSet::new();
+        let mut changes = vec![];
+        for (id, old, new) in edits {
+            let Some(path) = paths.get(&id) else {
+                continue;
+            };
+            for (path, old) in self.touched(path, old, new)? {
+                if seen.insert(path.clone()) {
+                    let new = self
+                        .find(tracker.root, &path)
+                        .map(|id| self.to_kind(id))
+                        .transpose()?;
+                    if old != new {
+                        changes.push((path, old, new));
+                    }
+                }
+            }
+        }
+        Ok(changes)
+    }
+
+    /// When a list or struct only had some of its children replaced, just those children are
+    /// listed
+    pub(crate) fn touched(
+        &self,
+        path: &ParamPath,
+        old: &ParamType,
+        new: &ParamType,
+    ) -> PyResult<Vec<(ParamPath, Option<ParamKind>)>> {
+        Ok(match (old, new) {
+            (ParamType::List(old), ParamType::List(new)) => (0..old.0.len().max(new.0.len()))
+                .filter(|&index| old.0.get(index) != new.0.get(index))
+                .map(|index| {
+                    let old = old.0.get(index).map(|id| self.to_kind(*id)).transpose()?;
+                    Ok((path.push(Key::Index(index)), old))
+                })
+                .collect::<PyResult<_>>()?,
+            (ParamType::Struct(old), ParamType::Struct(new)) => {
+                let removed = old.0.iter().filter_map(|(hash, id)| {
+                    match new.0.iter().find(|(h, _)| h == hash) {
+                        Some((_, new_id)) if new_id == id => None,
+                        _ => Some(
+                            self.to_kind(*id)
+                                .map(|old| (path.push(Key::Hash(*hash)), Some(old))),
+                        ),
+                    }
+                });
+                let added = new
+                    .0
+                    .iter()
+                    .filter(|(hash, _)| !old.0.iter().any(|(h, _)| h == hash))
+                    .map(|(hash, _)| Ok((path.push(Key::Hash(*hash)), None)));
+                removed.chain(added).collect::<PyResult<_>>()?
+            }
+            _ => vec![(path.clone(), Some(self.param_kind(old)?))],
+        })
+    }
+
+    /// Params which appear more than once use the first path found. Lazy params aren't searched,
+    /// since nothing below them has changed
+    fn paths(&self, root: NodeId) -> HashMap<NodeId, ParamPath> {
+        let mut paths = HashMap::new();
+        let mut stack = vec![(root, ParamPath::default())];
+        while let Some((id, path)) = stack.pop() {
+            if paths.contains_key(&id) {
+                continue;
+            }
+            match &self[id] {
+                ParamType::List(v) => stack.extend(
+                    v.0.iter()
+                        .enumerate()
+                        .rev()
+                        .map(|(index, child)| (*child, path.push(Key::Index(index)))),
+                ),
+                ParamType::Struct(v) => stack.extend(
+                    v.0.iter()
+                        .rev()
+                        .map(|(hash, child)| (*child, path.push(Key::Hash(*hash)))),
+                ),
+                _ => {}
+            }
+            paths.insert(id, path);
+        }
+        paths
+    }
+
+    /// Paths to many params can be found with one search. Params which appear more than once use
+    /// the first path found
+    pub(crate) fn parents(&self, root: NodeId) -> Parents {
+        let mut parents = HashMap::new();
+        let mut stack = vec![(root, None)];
+        while let Some((id, parent)) = stack.pop() {
+            if parents.contains_key(&id) {
+                continue;
+            }
+            match &self[id] {
+                ParamType::List(v) => stack.extend(
+                    v.0.iter()
+                        .enumerate()
+                        .rev()
+                        .map(|(index, child)| (*child, Some((id, Key::Index(index))))),
+                ),
+                ParamType::Struct(v) => stack.extend(
+                    v.0.iter()
+                        .rev()
+                        .map(|(hash, child)| (*child, Some((id, Key::Hash(*hash))))),
+                ),
+                _ => {}
+            }
+            parents.insert(id, parent);
+        }
+        Parents(parents)
+    }
+
+    /// Lazy params aren't loaded
+    pub(crate) fn find(&self, root: NodeId, path: &ParamPath) -> Option<NodeId> {
+        path.0
+            .iter()
+            .try_fold(root, |id, key| match (&self[id], key) {
+                (ParamType::List(v), Key::Index(index)) => v.0.get(*index).copied(),
+                (ParamType::Struct(v), Key::Hash(hash)) => {
+                    v.0.iter().find(|(h, _)| h == hash).map(|(_, c)| *c)
+                }
+                _ => None,
+            })
+    }
+}
@@ -0,0 +1,626 @@
+use crate::duplicate::Duplicate;
+use crate::history::History;
+use crate::lazy::{LazyFile, MAX_DEPTH};
+use crate::observe::{ArenaMut, Observers};
+use crate::track::Tracker;
+use crate::{Hash, Param, ParamList2, ParamStruct2, ParamType};
+use prc::hash40::Hash40;
+use prc::{ParamKind, ParamList, ParamStruct};
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::{PyErr, PyResult};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ops::Index;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, Weak};
+
+pub(crate) type NodeId = u32;
+
+/// Held by each reference to a param, so that the param isn't freed while it can be used
+pub(crate) type Pin = Arc<()>;
+
+const MIN_COLLECT: usize = 4096;
+
+pub(crate) type Tree = Arc<TreeCell>;
+
+/// Once a tree is moved into another, params which referred to it are found in the other tree
+#[derive(Debug, Default)]
+pub(crate) struct TreeCell {
+    arena: Mutex<Arena>,
+    moved: OnceLock<(Tree, Vec<NodeId>, usize)>,
+}
+
+impl TreeCell {
+    fn new(arena: Arena) -> Tree {
+        Arc::new(TreeCell {
+            arena: Mutex::new(arena),
+            moved: OnceLock::new(),
+        })
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, Arena> {
+        self.arena.lock().unwrap()
+    }
+}
+
+/// Every param of a tree in one flat list, where lists and structs refer to their children by
+/// position. Trees shared into this one stay separate owners, and replaced params are freed
+/// once nothing can reach them
+#[derive(Debug, Default)]
+pub(crate) struct Arena {
+    nodes: Vec<ParamType>,
+    free: Vec<NodeId>,
+    collect_at: usize,
+    pins: RefCell<HashMap<NodeId, Weak<()>>>,
+    pub(crate) file: Option<Arc<LazyFile>>,
+    pub(crate) owners: Vec<Owner>,
+    pub(crate) observers: Observers,
+    /// Identical strings share their storage
+    strings: HashSet<Arc<str>>,
+}
+
+/// The parts of a tree which stay its own once it's moved into another
+#[derive(Debug, Default)]
+pub(crate) struct Owner {
+    pub(crate) frozen: bool,
+    pub(crate) tracker: Option<Tracker>,
+    pub(crate) history: History,
+}
+
+impl Owner {
+    fn moved(self, moves: &[NodeId]) -> Owner {
+        Owner {
+            frozen: self.frozen,
+            tracker: self.tracker.map(|t| t.moved(moves)),
+            history: self.history.moved(moves),
+        }
+    }
+}
+
+/// Params which haven't been placed into a tree yet. Existing params are shared rather than
+/// copied, unless they're read-only
+pub(crate) enum Draft {
+    Kind(ParamKind),
+    List(Vec<Draft>),
+    Struct(Vec<(Hash40, Draft)>),
+    Shared(Param),
+}
+
+impl Draft {
+    pub(crate) fn copied(self) -> PyResult<Draft> {
+        Ok(match self {
+            Draft::Shared(p) => Draft::Kind(ParamKind::try_from(&p)?),
+            Draft::List(items) => Draft::List(
+                items
+                    .into_iter()
+                    .map(Draft::copied)
+                    .collect::<PyResult<_>>()?,
+            ),
+            Draft::Struct(fields) => Draft::Struct(
+                fields
+                    .into_iter()
+                    .map(|(h, d)| Ok((h, d.copied()?)))
+                    .collect::<PyResult<_>>()?,
+            ),
+            draft => draft,
+        })
+    }
+}
+
+impl Index<NodeId> for Arena {
+    type Output = ParamType;
+
+    fn index(&self, id: NodeId) -> &ParamType {
+        &self.nodes[id as usize]
+    }
+}
+
+impl Arena {
+    pub(crate) fn push(&mut self, param: ParamType) -> NodeId {
+        let param = self.intern(param);
+        match self.free.pop() {
+            Some(id) => {
+                self.nodes[id as usize] = param;
+                id
+            }
+            None => {
+                self.nodes.push(param);
+                (self.nodes.len() - 1) as NodeId
+            }
+        }
+    }
+
+    fn pin(&self, id: NodeId) -> Pin {
+        let mut pins = self.pins.borrow_mut();
+        if let Some(pin) = pins.get(&id).and_then(Weak::upgrade) {
+            return pin;
+        }
+        let pin = Arc::new(());
+        pins.insert(id, Arc::downgrade(&pin));
+        pin
+    }
+
+    /// Positions held outside of the tree, such as while a group of changes is open, could be
+    /// reused, so this waits until they're released
+    pub(crate) fn maybe_collect(&mut self) {
+        let used = self.nodes.len() - self.free.len();
+        if used >= self.collect_at.max(MIN_COLLECT) && !self.in_group() {
+            self.collect();
+        }
+    }
+
+    pub(crate) fn collect(&mut self) {
+        let mut stack = vec![];
+        self.pins.get_mut().retain(|id, pin| {
+            let live = pin.strong_count() > 0;
+            if live {
+                stack.push(*id);
+            }
+            live
+        });
+        for owner in &self.owners {
+            if let Some(tracker) = &owner.tracker {
+                tracker.roots(&mut stack);
+            }
+            owner.history.roots(&mut stack);
+        }
+        self.observers.roots(&mut stack);
+
+        let mut reachable = vec![false; self.nodes.len()];
+        while let Some(id) = stack.pop() {
+            if !std::mem::replace(&mut reachable[id as usize], true) {
+                stack.extend(self[id].children());
+            }
+        }
+        let used = reachable
+            .iter()
+            .rposition(|r| *r)
+            .map_or(0, |last| last + 1);
+        self.nodes.truncate(used);
+        if self.nodes.len() < self.nodes.capacity() / 2 {
+            self.nodes.shrink_to_fit();
+        }
+        self.free.clear();
+        for id in (0..used).rev().filter(|id| !reachable[*id]) {
+            self.nodes[id] = ParamType::Bool(false);
+            self.free.push(id as NodeId);
+        }
+        self.strings.retain(|s| Arc::strong_count(s) > 1);
+        self.collect_at = 2 * (self.nodes.len() - self.free.len());
+    }
+
+    pub(crate) fn owner(&mut self, owner: usize) -> &mut Owner {
+        if self.owners.len() <= owner {
+            self.owners.resize_with(owner + 1, Owner::default);
+        }
+        &mut self.owners[owner]
+    }
+
+    pub(crate) fn frozen(&self, owner: usize) -> bool {
+        self.owners.get(owner).is_some_and(|o| o.frozen)
+    }
+
+    pub(crate) fn tracker(&self, owner: usize) -> Option<&Tracker> {
+        self.owners.get(owner)?.tracker.as_ref()
+    }
+
+    pub(crate) fn in_group(&self) -> bool {
+        self.owners.iter().any(|o| o.history.is_open())
+    }
+
+    /// Every change to the tree goes through here, so that it can be undone
+    pub(crate) fn set(&mut self, owner: usize, id: NodeId, param: ParamType) {
+        let old = self.replace(id, param);
+        self.record(owner, id, old);
+    }
+
+    /// Replaces a param without adding to the undo history
+    pub(crate) fn replace(&mut self, id: NodeId, param: ParamType) -> ParamType {
+        let param = self.intern(param);
+        let old = std::mem::replace(&mut self.nodes[id as usize], param);
+        for tracker in self.owners.iter_mut().filter_map(|o| o.tracker.as_mut()) {
+            tracker.edits.push((id, old.duplicate()));
+        }
+        self.notify(id, &old);
+        old
+    }
+
+    pub(crate) fn load(&mut self, id: NodeId) -> PyResult<()> {
+        if let ParamType::Lazy(_, offset) = self[id] {
+            let file = self.file.clone().expect("lazy params always have a file");
+            let param = file.load(offset, self)?;
+            self.nodes[id as usize] = self.intern(param);
+        }
+        Ok(())
+    }
+
+    fn intern(&mut self, param: ParamType) -> ParamType {
+        match param {
+            ParamType::Str(v) => match self.strings.get(&v) {
+                Some(shared) => ParamType::Str(shared.clone()),
+                None => {
+                    self.strings.insert(v.clone());
+                    ParamType::Str(v)
+                }
+            },
+            param => param,
+        }
+    }
+
+    /// Children of read-only params are read-only as well
+    pub(crate) fn handle(&self, parent: &Param, id: NodeId) -> Param {
+        Param {
+            origin: parent.tree().clone(),
+            node: id,
+            owner: parent.owner(),
+            pin: self.pin(id),
+            readonly: parent.readonly,
+        }
+    }
+
+    pub(crate) fn load_all(&mut self) -> PyResult<()> {
+        // the children of a loaded param can reuse free positions anywhere in the tree
+        let mut stack = (0..self.nodes.len() as NodeId)
+            .map(|id| (id, 0))
+            .collect::<Vec<_>>();
+        while let Some((id, depth)) = stack.pop() {
+            if let ParamType::Lazy(_, offset) = self[id] {
+                if depth > MAX_DEPTH {
+                    return Err(PyValueError::new_err(format!(
+                        "Invalid param file: params are nested too deeply at position {}",
+                        offset
+                    )));
+                }
+                self.load(id)?;
+                stack.extend(
+                    self[id]
+                        .children()
+                        .into_iter()
+                        .map(|child| (child, depth + 1)),
+                );
+            }
+        }
+        self.file = None;
+        Ok(())
+    }
+
+    /// The other tree is left empty
+    fn absorb(&mut self, other: &mut Arena) -> PyResult<(Vec<NodeId>, usize)> {
+        // only what can still be reached is moved
+        other.collect();
+        if other
+            .file
+            .as_ref()
+            .is_some_and(|file| !self.file.as_ref().is_some_and(|f| Arc::ptr_eq(f, file)))
+        {
+            if self.file.is_none() {
+                self.file = other.file.take();
+            } else {
+                other.load_all()?;
+            }
+        }
+        // freed params aren't moved, since nothing refers to them
+        let mut freed = vec![false; other.nodes.len()];
+        for id in &other.free {
+            freed[*id as usize] = true;
+        }
+        let moves = freed
+            .iter()
+            .map(|&freed| {
+                if freed {
+                    0
+                } else {
+                    self.push(ParamType::Bool(false))
+                }
+            })
+            .collect::<Vec<_>>();
+        for (id, param) in std::mem::take(&mut other.nodes).into_iter().enumerate() {
+            if !freed[id] {
+                self.nodes[moves[id] as usize] = self.intern(param.moved(&moves));
+            }
+        }
+        let pins = self.pins.get_mut();
+        for (id, pin) in other.pins.get_mut().drain() {
+            if pin.strong_count() > 0 {
+                pins.insert(moves[id as usize], pin);
+            }
+        }
+        // both trees always have their own owner, even if nothing has been set for it
+        let offset = self.owners.len().max(1);
+        self.owners.resize_with(offset, Owner::default);
+        other.owner(0);
+        self.owners
+            .extend(other.owners.drain(..).map(|o| o.moved(&moves)));
+        self.observers
+            .absorb(std::mem::take(&mut other.observers), &moves);
+        *other = Arena::default();
+        Ok((moves, offset))
+    }
+
+    pub(crate) fn to_kind(&self, id: NodeId) -> PyResult<ParamKind> {
+        self.param_kind(&self[id])
+    }
+
+    /// Copies a param even if it's no longer in the tree
+    pub(crate) fn param_kind(&self, param: &ParamType) -> PyResult<ParamKind> {
+        Ok(match param {
+            ParamType::Bool(v) => ParamKind::Bool(*v),
+            ParamType::I8(v) => ParamKind::I8(*v),
+            ParamType::U8(v) => ParamKind::U8(*v),
+            ParamType::I16(v) => ParamKind::I16(*v),
+            ParamType::U16(v) => ParamKind::U16(*v),
+            ParamType::I32(v) => ParamKind::I32(*v),
+            ParamType::U32(v) => ParamKind::U32(*v),
+            ParamType::Float(v) => ParamKind::Float(*v),
+            ParamType::Hash(v) => ParamKind::Hash(v.inner),
+            ParamType::Str(v) => ParamKind::Str(v.to_string()),
+            ParamType::List(v) => ParamKind::List(ParamList(
+                v.0.iter()
+                    .map(|c| self.to_kind(*c))
+                    .collect::<PyResult<_>>()?,
+            )),
+            ParamType::Struct(v) => ParamKind::Struct(ParamStruct(
+                v.0.iter()
+                    .map(|(h, c)| Ok((*h, self.to_kind(*c)?)))
+                    .collect::<PyResult<_>>()?,
+            )),
+            // read straight from the file, without loading it into the tree
+            ParamType::Lazy(_, offset) => self
+                .file
+                .as_ref()
+                .expect("lazy params always have a file")
+                .to_kind(*offset)?,
+        })
+    }
+
+    /// Lazy params stay lazy, so both trees must read from the same file
+    pub(crate) fn copy_to(&self, id: NodeId, dest: &mut Arena) -> NodeId {
+        let param = match &self[id] {
+            ParamType::List(v) => ParamType::List(ParamList2(
+                v.0.iter().map(|c| self.copy_to(*c, dest)).collect(),
+            )),
+            ParamType::Struct(v) => ParamType::Struct(ParamStruct2(
+                v.0.iter()
+                    .map(|(h, c)| (*h, self.copy_to(*c, dest)))
+                    .collect(),
+            )),
+            param => param.duplicate(),
+        };
+        dest.push(param)
+    }
+
+    pub(crate) fn push_kind(&mut self, kind: ParamKind) -> NodeId {
+        let param = self.node_from_kind(kind);
+        self.push(param)
+    }
+
+    pub(crate) fn node_from_kind(&mut self, kind: ParamKind) -> ParamType {
+        match kind {
+            ParamKind::Bool(v) => ParamType::Bool(v),
+            ParamKind::I8(v) => ParamType::I8(v),
+            ParamKind::U8(v) => ParamType::U8(v),
+            ParamKind::I16(v) => ParamType::I16(v),
+            ParamKind::U16(v) => ParamType::U16(v),
+            ParamKind::I32(v) => ParamType::I32(v),
+            ParamKind::U32(v) => ParamType::U32(v),
+            ParamKind::Float(v) => ParamType::Float(v),
+            ParamKind::Hash(v) => ParamType::Hash(Hash::from(v)),
+            ParamKind::Str(v) => ParamType::Str(v.into()),
+            ParamKind::List(v) => ParamType::List(ParamList2(
+                v.0.into_iter().map(|c| self.push_kind(c)).collect(),
+            )),
+            ParamKind::Struct(v) => ParamType::Struct(ParamStruct2(
+                v.0.into_iter()
+                    .map(|(h, c)| (h, self.push_kind(c)))
+                    .collect(),
+            )),
+        }
+    }
+
+    pub(crate) fn place(&mut self, tree: &Tree, draft: Draft) -> PyResult<NodeId> {
+        match draft {
+            Draft::Shared(p) if Arc::ptr_eq(p.tree(), tree) => Ok(p.id()),
+            Draft::Shared(p) => {
+                let mut other = p.lock()?;
+                if p.readonly || other.frozen(p.owner()) {
+                    let kind = other.to_kind(p.id())?;
+                    drop(other);
+                    return Ok(self.push_kind(kind));
+                }
+                if other.in_group() {
+                    return Err(PyRuntimeError::new_err(
+                        "Cannot share a param from a tree with an open transaction",
+                    ));
+                }
+                let id = p.id();
+                let (moves, offset) = self.absorb(&mut other)?;
+                let position = moves[id as usize];
+                // the other tree stays locked until it's marked as moved
+                p.tree()
+                    .moved
+                    .set((tree.clone(), moves, offset))
+                    .expect("trees are only moved once");
+                drop(other);
+                Ok(position)
+            }
+            draft => {
+                let param = self.node_from(tree, draft)?;
+                Ok(self.push(param))
+            }
+        }
+    }
+
+    pub(crate) fn node_from(&mut self, tree: &Tree, draft: Draft) -> PyResult<ParamType> {
+        Ok(match draft {
+            Draft::Kind(kind) => self.node_from_kind(kind),
+            Draft::List(items) => ParamType::List(ParamList2(
+                items
+                    .into_iter()
+                    .map(|d| self.place(tree, d))
+                    .collect::<PyResult<_>>()?,
+            )),
+            Draft::Struct(fields) => ParamType::Struct(ParamStruct2(
+                fields
+                    .into_iter()
+                    .map(|(h, d)| Ok((h, self.place(tree, d)?)))
+                    .collect::<PyResult<_>>()?,
+            )),
+            Draft::Shared(p) if Arc::ptr_eq(p.tree(), tree) => {
+                let kind = self.to_kind(p.id())?;
+                self.node_from_kind(kind)
+            }
+            Draft::Shared(p) => self.node_from_kind(ParamKind::try_from(&p)?),
+        })
+    }
+}
+
+impl ParamType {
+    pub(crate) fn moved(self, moves: &[NodeId]) -> ParamType {
+        match self {
+            ParamType::List(v) => ParamType::List(ParamList2(
+                v.0.into_iter().map(|c| moves[c as usize]).collect(),
+            )),
+            ParamType::Struct(v) => ParamType::Struct(ParamStruct2(
+                v.0.into_iter()
+                    .map(|(h, c)| (h, moves[c as usize]))
+                    .collect(),
+            )),
+            param => param,
+        }
+    }
+
+    pub(crate) fn children(&self) -> Vec<NodeId> {
+        match self {
+            ParamType::List(v) => v.0.clone(),
+            ParamType::Struct(v) => v.0.iter().map(|(_, c)| *c).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+impl Param {
+    pub(crate) fn new_tree(arena: Arena, id: NodeId) -> Param {
+        Param {
+            pin: arena.pin(id),
+            origin: TreeCell::new(arena),
+            node: id,
+            owner: 0,
+            readonly: false,
+        }
+    }
+
+    pub(crate) fn clone_ref(&self) -> Self {
+        Param {
+            origin: self.origin.clone(),
+            node: self.node,
+            owner: self.owner,
+            pin: self.pin.clone(),
+            readonly: self.readonly,
+        }
+    }
+
+    fn location(&self) -> (&Tree, NodeId, usize) {
+        let mut tree = &self.origin;
+        let mut id = self.node;
+        let mut owner = self.owner;
+        while let Some((next, moves, offset)) = tree.moved.get() {
+            tree = next;
+            id = moves[id as usize];
+            owner += offset;
+        }
+        (tree, id, owner)
+    }
+
+    pub(crate) fn tree(&self) -> &Tree {
+        self.location().0
+    }
+
+    pub(crate) fn id(&self) -> NodeId {
+        self.location().1
+    }
+
+    pub(crate) fn owner(&self) -> usize {
+        self.location().2
+    }
+
+    /// Reads the param first if it's lazy. Nothing else in the tree can be locked until it's released
+    pub(crate) fn lock(&self) -> PyResult<MutexGuard<'_, Arena>> {
+        let mut arena = self.lock_unread();
+        arena.load(self.id())?;
+        Ok(arena)
+    }
+
+    pub(crate) fn lock_unread(&self) -> MutexGuard<'_, Arena> {
+        loop {
+            let tree = self.tree();
+            let arena = tree.lock();
+            // the tree may have been moved while waiting for the lock
+            if tree.moved.get().is_none() {
+                return arena;
+            }
+        }
+    }
+
+    /// Callbacks watching the tree are told about the changes once it's released
+    pub(crate) fn lock_mut(&self) -> PyResult<ArenaMut<'_>> {
+        let arena = self.lock()?;
+        if self.readonly || arena.frozen(self.owner()) {
+            return Err(PyTypeError::new_err("Cannot modify a read-only param"));
+        }
+        Ok(ArenaMut::new(arena, self.owner()))
+    }
+
+    pub(crate) fn set_draft(&self, draft: Draft) -> PyResult<()> {
+        let mut arena = self.lock_mut()?;
+        let param = arena.node_from(self.tree(), draft)?;
+        arena.set(self.id(), param);
+        Ok(())
+    }
+
+    pub(crate) fn from_draft(draft: Draft) -> PyResult<Param> {
+        match draft {
+            Draft::Shared(p) => Ok(p),
+            Draft::Kind(kind) => Ok(Param::from(kind)),
+            draft => {
+                let tree = TreeCell::new(Arena::default());
+                let mut arena = tree.lock();
+                let node = arena.node_from(&tree, draft)?;
+                let id = arena.push(node);
+                let pin = arena.pin(id);
+                drop(arena);
+                Ok(Param {
+                    origin: tree,
+                    node: id,
+                    owner: 0,
+                    pin,
+                    readonly: false,
+                })
+            }
+        }
+    }
+}
+
+impl From<ParamKind> for Param {
+    fn from(f: ParamKind) -> Self {
+        let mut arena = Arena::default();
+        let id = arena.push_kind(f);
+        Param::new_tree(arena, id)
+    }
+}
+
+impl TryFrom<&Param> for ParamKind {
+    type Error = PyErr;
+
+    fn try_from(f: &Param) -> PyResult<Self> {
+        f.lock()?.to_kind(f.id())
+    }
+}
+
+impl Param {
+    pub(crate) fn equals(&self, other: &Param) -> PyResult<bool> {
+        // the two params may share a tree, so only one is locked at a time
+        Ok(
+            (Arc::ptr_eq(self.tree(), other.tree()) && self.id() == other.id())
+                || ParamKind::try_from(self)? == ParamKind::try_from(other)?,
+        )
+    }
+}
@@ -0,0 +1,276 @@
+use crate::convert::parse_value;
+use crate::labels::format_label;
+use prc::hash40::label_map::LabelMap;
+use prc::hash40::Hash40;
+use prc::{ParamKind, ParamList, ParamStruct};
+use pyo3::exceptions::PyValueError;
+use pyo3::PyResult;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::io::{BufRead, Result, Write};
+
+/// Uses the same layout as prc-rs
+pub(crate) fn write_xml<W: Write>(
+    param: &ParamStruct,
+    labels: &LabelMap,
+    writer: &mut W,
+) -> Result<()> {
+    let mut xml = XmlWriter {
+        writer,
+        labels,
+        depth: 0,
+    };
+    write!(xml.writer, r#"<?xml version="1.0" encoding="utf-8"?>"#)?;
+    xml.write_struct(param, None)
+}
+
+struct XmlWriter<'a, W: Write> {
+    writer: &'a mut W,
+    labels: &'a LabelMap,
+    depth: usize,
+}
+
+impl<W: Write> XmlWriter<'_, W> {
+    fn open_tag(&mut self, name: &str, attr: &Option<(&str, String)>) -> Result<()> {
+        write!(self.writer, "\n{}<{}", "  ".repeat(self.depth), name)?;
+        if let Some((key, value)) = attr {
+            write!(self.writer, r#" {}="{}""#, key, escape(value))?;
+        }
+        Ok(())
+    }
+
+    fn write_param(&mut self, param: &ParamKind, attr: Option<(&str, String)>) -> Result<()> {
+        macro_rules! write_constant {
+            ($tag_name:literal, $value:expr) => {{
+                self.open_tag($tag_name, &attr)?;
+                write!(self.writer, ">{}</{}>", escape(&$value), $tag_name)
+            }};
+        }
+        match param {
+            ParamKind::Bool(val) => write_constant!("bool", val.to_string()),
+            ParamKind::I8(val) => write_constant!("sbyte", val.to_string()),
+            ParamKind::U8(val) => write_constant!("byte", val.to_string()),
+            ParamKind::I16(val) => write_constant!("short", val.to_string()),
+            ParamKind::U16(val) => write_constant!("ushort", val.to_string()),
+            ParamKind::I32(val) => write_constant!("int", val.to_string()),
+            ParamKind::U32(val) => write_constant!("uint", val.to_string()),
+            ParamKind::Float(val) => write_constant!("float", val.to_string()),
+            ParamKind::Hash(val) => write_constant!("hash40", format_label(self.labels, *val)),
+            ParamKind::Str(val) => write_constant!("string", val),
+            ParamKind::List(val) => self.write_list(val, attr),
+            ParamKind::Struct(val) => self.write_struct(val, attr),
+        }
+    }
+
+    fn write_list(&mut self, param: &ParamList, attr: Option<(&str, String)>) -> Result<()> {
+        self.open_tag("list", &attr)?;
+        if param.0.is_empty() {
+            return write!(self.writer, "/>");
+        }
+        write!(self.writer, ">")?;
+        self.depth += 1;
+        for (index, child) in param.0.iter().enumerate() {
+            self.write_param(child, Some(("index", index.to_string())))?;
+        }
+        self.depth -= 1;
+        write!(self.writer, "\n{}</list>", "  ".repeat(self.depth))
+    }
+
+    fn write_struct(&mut self, param: &ParamStruct, attr: Option<(&str, String)>) -> Result<()> {
+        self.open_tag("struct", &attr)?;
+        if param.0.is_empty() {
+            return write!(self.writer, "/>");
+        }
+        write!(self.writer, ">")?;
+        self.depth += 1;
+        for (hash, child) in param.0.iter() {
+            let label = format_label(self.labels, *hash);
+            self.write_param(child, Some(("hash", label)))?;
+        }
+        self.depth -= 1;
+        write!(self.writer, "\n{}</struct>", "  ".repeat(self.depth))
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '\'' => escaped.push_str("&apos;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const TAG_NAMES: [&str; 12] = [
+    "bool", "sbyte", "byte", "short", "ushort", "int", "uint", "float", "hash40", "string", "list",
+    "struct",
+];
+
+pub(crate) fn read_xml<R: BufRead>(reader: R, labels: &LabelMap) -> PyResult<ParamStruct> {
+    let mut reader = Reader::from_reader(reader);
+    reader.config_mut().trim_text(true);
+    reader.config_mut().expand_empty_elements = true;
+
+    let mut buf = Vec::new();
+    let mut stack = XmlStack {
+        labels,
+        frames: Vec::new(),
+        value: None,
+    };
+    loop {
+        let position = reader.buffer_position();
+        let result = match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(start)) => stack.start(&start),
+            Ok(Event::Text(text)) => text
+                .unescape()
+                .map_err(|e| e.to_string())
+                .and_then(|text| stack.text(&text)),
+            Ok(Event::CData(text)) => stack.text(&String::from_utf8_lossy(&text)),
+            Ok(Event::End(_)) => match stack.end() {
+                Ok(Some(root)) => return Ok(root),
+                Ok(None) => Ok(()),
+                Err(e) => Err(e),
+            },
+            Ok(Event::Eof) => {
+                Err("Reached the end of the file before the root struct closed".into())
+            }
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            return Err(PyValueError::new_err(format!(
+                "Invalid param XML at position {}: {}",
+                position, e
+            )));
+        }
+        buf.clear();
+    }
+}
+
+struct Frame {
+    hash: Option<Hash40>,
+    param: ParamKind,
+}
+
+struct XmlStack<'a> {
+    labels: &'a LabelMap,
+    frames: Vec<Frame>,
+    value: Option<(u8, Option<Hash40>, String)>,
+}
+
+impl XmlStack<'_> {
+    fn start(&mut self, start: &BytesStart) -> std::result::Result<(), String> {
+        let name = String::from_utf8_lossy(start.name().as_ref()).to_string();
+        if self.value.is_some() {
+            return Err(format!("Unexpected tag '{}' inside a value", name));
+        }
+        let kind = TAG_NAMES
+            .iter()
+            .position(|tag| *tag == name)
+            .map(|index| index as u8 + 1)
+            .ok_or_else(|| format!("Unknown tag '{}'", name))?;
+        if self.frames.is_empty() && kind != 12 {
+            return Err("The root param must be a struct".into());
+        }
+
+        let hash = match self.frames.last() {
+            Some(Frame {
+                param: ParamKind::Struct(_),
+                ..
+            }) => Some(self.hash_attribute(start)?),
+            _ => None,
+        };
+        match kind {
+            11 => self.frames.push(Frame {
+                hash,
+                param: ParamKind::List(Default::default()),
+            }),
+            12 => self.frames.push(Frame {
+                hash,
+                param: ParamKind::Struct(Default::default()),
+            }),
+            _ => self.value = Some((kind, hash, String::new())),
+        }
+        Ok(())
+    }
+
+    fn hash_attribute(&self, start: &BytesStart) -> std::result::Result<Hash40, String> {
+        for attr in start.attributes() {
+            let attr = attr.map_err(|e| e.to_string())?;
+            if attr.key.as_ref() == b"hash" {
+                let value = attr.unescape_value().map_err(|e| e.to_string())?;
+                return match parse_value(9, &value, self.labels)? {
+                    ParamKind::Hash(hash) => Ok(hash),
+                    _ => unreachable!(),
+                };
+            }
+        }
+        Err("Params inside a struct need a 'hash' attribute".into())
+    }
+
+    fn text(&mut self, text: &str) -> std::result::Result<(), String> {
+        match &mut self.value {
+            Some((_, _, value)) => {
+                value.push_str(text);
+                Ok(())
+            }
+            None => Err(format!("Unexpected text '{}'", text)),
+        }
+    }
+
+    /// Returns the root struct once it closes
+    fn end(&mut self) -> std::result::Result<Option<ParamStruct>, String> {
+        let (hash, param) = match self.value.take() {
+            // empty values keep their default, the same as in prc-rs
+            Some((kind, hash, text)) if text.is_empty() && kind != 10 => {
+                (hash, default_value(kind))
+            }
+            Some((kind, hash, text)) => (hash, parse_value(kind, &text, self.labels)?),
+            None => {
+                let frame = self
+                    .frames
+                    .pop()
+                    .expect("end tags always match a start tag");
+                (frame.hash, frame.param)
+            }
+        };
+        match self.frames.last_mut() {
+            Some(Frame {
+                param: ParamKind::List(list),
+                ..
+            }) => list.0.push(param),
+            Some(Frame {
+                param: ParamKind::Struct(fields),
+                ..
+            }) => fields
+                .0
+                .push((hash.expect("struct children have a hash"), param)),
+            Some(_) => unreachable!(),
+            None => match param {
+                ParamKind::Struct(root) => return Ok(Some(root)),
+                _ => unreachable!(),
+            },
+        }
+        Ok(None)
+    }
+}
+
+fn default_value(kind: u8) -> ParamKind {
+    match kind {
+        1 => ParamKind::Bool(false),
+        2 => ParamKind::I8(0),
+        3 => ParamKind::U8(0),
+        4 => ParamKind::I16(0),
+        5 => ParamKind::U16(0),
+        6 => ParamKind::I32(0),
+        7 => ParamKind::U32(0),
+        8 => ParamKind::Float(0.0),
+        _ => ParamKind::Hash(Hash40(0)),
+    }
+}
@@ -0,0 +1,180 @@
+import os
+import tempfile
+import unittest
+
+from pyprc import hash, param
+
+H = hash(0x10)
+K = hash(0x11)
+
+
+class SharedParamTest(unittest.TestCase):
+    def test_shared_param_stays_the_same(self):
+        a = param.u8(1)
+        lst = param.list([a, param.u8(2)])
+        a.value = 5
+        self.assertEqual(lst[0].value, 5)
+        lst[0].value = 6
+        self.assertEqual(a.value, 6)
+
+    def test_param_shared_twice(self):
+        a = param.u8(1)
+        lst = param.list([a, a])
+        lst[1].value = 3
+        self.assertEqual([p.value for p in lst], [3, 3])
+        self.assertEqual(a.value, 3)
+
+    def test_children_of_moved_tree(self):
+        inner = param.struct([(H, param.u8(1)), (K, param.u8(2))])
+        child = inner[K]
+        root = param.struct([(H, inner)])
+        child.value = 9
+        self.assertEqual(root[H][K].value, 9)
+        self.assertEqual(inner, root[H])
+
+    def test_moved_into_two_trees(self):
+        a = param.u8(1)
+        first = param.list([a])
+        second = param.list([a])
+        a.value = 4
+        self.assertEqual(first[0].value, 4)
+        self.assertEqual(second[0].value, 4)
+
+    def test_changes_to_shared_child_are_tracked(self):
+        root = param.struct([(H, param.u8(1))])
+        root.track_changes()
+        root[H].value = 2
+        self.assertTrue(root.is_dirty)
+        changes = root.changes()
+        self.assertEqual(list(changes), [(H,)])
+        old, new = changes[(H,)]
+        self.assertEqual((old.value, new.value), (1, 2))
+
+    def test_read_only_params_are_copied(self):
+        a = param.u8(1)
+        lst = param.list([a.freeze()])
+        lst[0].value = 2
+        self.assertEqual(a.value, 1)
+
+    def test_sharing_during_transaction(self):
+        a = param.u8(1)
+        with a.transaction():
+            with self.assertRaises(RuntimeError):
+                param.list([a])
+
+    def test_set_list_with_existing_children(self):
+        lst = param.list([param.u32(1), param.u32(2)])
+        children = list(lst)
+        children.append(param.u32(3))
+        lst.set_list(children)
+        self.assertEqual([p.value for p in lst], [1, 2, 3])
+
+
+
+class SharedRootsTest(unittest.TestCase):
+    def test_child_shared_with_another_root(self):
+        r1 = param.struct([(H, param.u8(1))])
+        p = param.list([r1[H]])
+        p[0].value = 2
+        self.assertEqual(r1[H].value, 2)
+        r1[H].value = 3
+        self.assertEqual(p[0].value, 3)
+
+    def test_tracked_roots_keep_their_changes(self):
+        r1 = param.struct([(H, param.u8(1))])
+        r1.track_changes()
+        r2 = param.list([param.u8(2)])
+        r2.track_changes()
+        r2[0].value = 3
+        r2.set_list(list(r2) + [r1[H]])
+        self.assertFalse(r1.is_dirty)
+        self.assertEqual(dict(r1.changes()), {})
+        self.assertTrue(r2.is_dirty)
+        self.assertEqual(list(r2.changes()), [(0,), (1,)])
+        r1[H].value = 7
+        self.assertEqual(list(r1.changes()), [(H,)])
+        old, new = r2.changes()[(1,)]
+        self.assertIsNone(old)
+        self.assertEqual(new.value, 7)
+
+    def test_tracked_root_moved_into_tracked_root(self):
+        r1 = param.list([param.u8(1)])
+        r1.track_changes()
+        r2 = param.struct([(H, param.u8(2))])
+        r2.track_changes()
+        r2[H].value = 4
+        r1.set_list(list(r1) + [r2[H]])
+        self.assertTrue(r2.is_dirty)
+        self.assertEqual(list(r2.changes()), [(H,)])
+
+    def test_freezing_does_not_reach_other_roots(self):
+        r1 = param.struct([(H, param.u8(1))])
+        p = param.list([r1[H]])
+        r1.freeze(in_place=True)
+        self.assertTrue(r1.readonly)
+        self.assertFalse(p.readonly)
+        p[0].value = 2
+        self.assertEqual(r1[H].value, 2)
+        with self.assertRaises(TypeError):
+            r1[H].value = 3
+
+    def test_undo_does_not_reach_other_roots(self):
+        r1 = param.struct([(H, param.u8(1))])
+        r1.track_history()
+        p = param.list([r1[H]])
+        p.track_history()
+        r1[H].value = 2
+        self.assertFalse(p.undo())
+        self.assertEqual(r1[H].value, 2)
+
+
+class CollectTest(unittest.TestCase):
+    def replace_many(self, lst, times):
+        for i in range(times):
+            lst.set_list([param.u32(i * 100 + j) for j in range(100)])
+
+    def test_held_references_survive(self):
+        lst = param.list([param.u32(j) for j in range(100)])
+        held = lst[5]
+        self.replace_many(lst, 500)
+        self.assertEqual(held.value, 5)
+        held.value = 6
+        self.assertEqual(held.value, 6)
+        self.assertEqual(lst[5].value, 49905)
+
+    def test_tracked_changes_survive(self):
+        lst = param.list([param.u32(j) for j in range(100)])
+        lst.track_changes()
+        self.replace_many(lst, 500)
+        changes = lst.changes()
+        self.assertEqual(len(changes), 100)
+        old, new = changes[(5,)]
+        self.assertEqual((old.value, new.value), (5, 49905))
+
+    def test_strings_are_shared_after_collection(self):
+        lst = param.list([param.str("a")])
+        for i in range(5000):
+            lst.set_list([param.str("s%d" % (i % 10))])
+        self.assertEqual(lst.strings(), {"s9": 1})
+
+
+class LazyShareTest(unittest.TestCase):
+    def setUp(self):
+        fd, self.path = tempfile.mkstemp(suffix=".prc")
+        os.close(fd)
+        param.struct([(H, param.list([param.struct([(K, param.u8(7))])]))]).save(self.path)
+
+    def tearDown(self):
+        os.remove(self.path)
+
+    def test_lazy_param_shared_into_another_tree(self):
+        lazy = param.open_lazy(self.path)
+        entry = lazy[H][0]
+        root = param.struct([(K, entry)])
+        entry[K].value = 8
+        self.assertEqual(root[K][K].value, 8)
+        self.assertEqual(lazy[H][0][K].value, 8)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,59 @@
+import os
+import tempfile
+import unittest
+
+from pyprc import ParamTypeKind, hash, param
+
+try:
+    import numpy
+except ImportError:
+    numpy = None
+
+H = hash(0x10)
+K = hash(0x11)
+
+
+@unittest.skipUnless(numpy, "numpy isn't installed")
+class AssignArrayTest(unittest.TestCase):
+    def setUp(self):
+        fd, self.path = tempfile.mkstemp(suffix=".prc")
+        os.close(fd)
+        param.struct([
+            (H, param.list([param.struct([(K, param.u8(1))])])),
+            (K, param.list([param.float(1.0), param.i32(2)])),
+        ]).save(self.path)
+
+    def tearDown(self):
+        os.remove(self.path)
+
+    def test_assign_keeps_types(self):
+        lst = param.list([param.u8(1), param.float(2.0)])
+        lst.assign_array(numpy.asarray([3, 4]))
+        self.assertEqual(
+            [(p.type, p.value) for p in lst], [(ParamTypeKind.U8, 3), (ParamTypeKind.FLOAT, 4.0)]
+        )
+
+    def test_shared_elements_are_set(self):
+        a = param.float(1.0)
+        b = param.float(2.0)
+        lst = param.list([a, b])
+        lst.assign_array(numpy.asarray([5.0, 6.0]))
+        self.assertEqual((a.value, b.value), (5.0, 6.0))
+
+    def test_lazy_list(self):
+        root = param.open_lazy(self.path)
+        root[K].assign_array(numpy.asarray([5.0, 6.0]))
+        self.assertEqual([p.value for p in root[K]], [5.0, 6])
+
+    def test_lazy_list_of_structs(self):
+        root = param.open_lazy(self.path)
+        with self.assertRaises(TypeError):
+            root[H].assign_array(numpy.asarray([1]))
+
+    def test_wrong_length(self):
+        with self.assertRaises(ValueError):
+            param.list([param.u8(1)]).assign_array(numpy.asarray([1, 2]))
+
+
+if __name__ == "__main__":
+    unittest.main()
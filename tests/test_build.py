@@ -0,0 +1,62 @@
+import unittest
+
+from pyprc import ParamTypeKind, hash, param
+
+H = hash(0x10)
+K = hash(0x11)
+V = hash(0x12)
+
+
+def types(p):
+    return [child.type for child in p]
+
+
+class BuildTest(unittest.TestCase):
+    def test_nested_dicts_and_lists(self):
+        p = param.build({H: [1, 2], K: {V: "x"}})
+        self.assertEqual(p.type, ParamTypeKind.STRUCT)
+        self.assertEqual(p[H].type, ParamTypeKind.LIST)
+        self.assertEqual([c.value for c in p[H]], [1, 2])
+        self.assertEqual(p[K][V].value, "x")
+
+    def test_annotations(self):
+        p = param.build([("i16", -2), (ParamTypeKind.U32, 5), ("HASH", hash(0x20)), ("float", 1)])
+        self.assertEqual(
+            types(p),
+            [ParamTypeKind.I16, ParamTypeKind.U32, ParamTypeKind.HASH, ParamTypeKind.FLOAT],
+        )
+        self.assertEqual(p[3].value, 1.0)
+
+    def test_numbers_are_not_annotations(self):
+        p = param.build((3, 5))
+        self.assertEqual(p.type, ParamTypeKind.LIST)
+        self.assertEqual([c.value for c in p], [3, 5])
+        p = param.build({H: (12, 0)})
+        self.assertEqual(len(p[H]), 2)
+
+    def test_struct_with_duplicate_keys(self):
+        p = param.build(("struct", [(H, 1), (H, 2)]))
+        self.assertEqual(len(p), 2)
+
+    def test_template(self):
+        template = param.build({H: ("u16", 1), K: [("i8", 1)]})
+        p = param.build({H: 5, K: [7, 8]}, template)
+        self.assertEqual(p[H].type, ParamTypeKind.U16)
+        self.assertEqual(types(p[K]), [ParamTypeKind.I8, ParamTypeKind.I8])
+
+    def test_annotation_overrides_template(self):
+        template = param.build({H: ("u16", 1)})
+        p = param.build({H: ("u32", 5)}, template)
+        self.assertEqual(p[H].type, ParamTypeKind.U32)
+
+    def test_errors(self):
+        with self.assertRaises(OverflowError):
+            param.build(("u8", 300))
+        with self.assertRaises(TypeError):
+            param.build(("list", 5))
+        with self.assertRaises(TypeError):
+            param.build(("u8", "x"))
+
+
+if __name__ == "__main__":
+    unittest.main()
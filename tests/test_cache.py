@@ -0,0 +1,67 @@
+import os
+import shutil
+import tempfile
+import unittest
+
+import pyprc
+from pyprc import hash, param
+
+H = hash(0x10)
+
+
+def fnv1a(data):
+    value = 0xCBF29CE484222325
+    for byte in data:
+        value = ((value ^ byte) * 0x100000001B3) % (1 << 64)
+    return value
+
+
+class CacheTest(unittest.TestCase):
+    def setUp(self):
+        self.dir = tempfile.mkdtemp()
+        self.path = os.path.join(self.dir, "file.prc")
+        param.struct([(H, param.u8(1))]).save(self.path)
+        self.cache = pyprc.Cache(os.path.join(self.dir, "cache"))
+
+    def tearDown(self):
+        shutil.rmtree(self.dir)
+
+    def entry(self):
+        source = os.fsencode(os.path.realpath(self.path))
+        return os.path.join(self.cache.dir, "%016x.bin" % fnv1a(source))
+
+    def test_entry_is_written_and_used(self):
+        first = param(self.path, cache=self.cache)
+        self.assertTrue(os.path.exists(self.entry()))
+        second = param(self.path, cache=self.cache)
+        self.assertEqual(first, second)
+        self.assertEqual(second[H].value, 1)
+
+    def test_changed_file_is_read_again(self):
+        param(self.path, cache=self.cache)
+        param.struct([(H, param.u16(300))]).save(self.path)
+        self.assertEqual(param(self.path, cache=self.cache)[H].value, 300)
+
+    def test_touched_file_uses_entry(self):
+        param(self.path, cache=self.cache)
+        before = os.path.getmtime(self.entry())
+        stat = os.stat(self.path)
+        os.utime(self.path, ns=(stat.st_atime_ns, stat.st_mtime_ns + 10**9))
+        self.assertEqual(param(self.path, cache=self.cache)[H].value, 1)
+        self.assertGreaterEqual(os.path.getmtime(self.entry()), before)
+
+    def test_broken_entry_is_replaced(self):
+        param(self.path, cache=self.cache)
+        with open(self.entry(), "r+b") as f:
+            f.truncate(20)
+        self.assertEqual(param(self.path, cache=self.cache)[H].value, 1)
+        self.assertGreater(os.path.getsize(self.entry()), 20)
+
+    def test_clear(self):
+        param(self.path, cache=self.cache)
+        self.cache.clear()
+        self.assertEqual(os.listdir(self.cache.dir), [])
+
+
+if __name__ == "__main__":
+    unittest.main()
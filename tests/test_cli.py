@@ -0,0 +1,78 @@
+import os
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+
+from pyprc import hash, param
+
+H = hash(0x10)
+K = hash(0x11)
+
+
+def run(*args):
+    """Runs the command line tool in a new interpreter, since it writes to the real stdout"""
+    script = "import sys, pyprc; sys.argv = sys.argv[1:]; pyprc.main()"
+    return subprocess.run(
+        [sys.executable, "-c", script, "pyprc", *args], capture_output=True, text=True
+    )
+
+
+class CommandLineTest(unittest.TestCase):
+    def setUp(self):
+        self.dir = tempfile.mkdtemp()
+        self.a = self.path("a.prc")
+        self.b = self.path("b.prc")
+        param.struct([(H, param.u8(1)), (K, param.list([param.str("x")]))]).save(self.a)
+        param.struct([(H, param.u8(2)), (K, param.list([param.str("y")]))]).save(self.b)
+
+    def tearDown(self):
+        shutil.rmtree(self.dir)
+
+    def path(self, name):
+        return os.path.join(self.dir, name)
+
+    def test_dump(self):
+        result = run("dump", self.a)
+        self.assertEqual(result.returncode, 0, result.stderr)
+        self.assertIn("x", result.stdout)
+
+    def test_get(self):
+        result = run("get", self.a, "0x0000000010")
+        self.assertEqual(result.returncode, 0, result.stderr)
+        self.assertEqual(result.stdout.strip(), "1")
+
+    def test_set(self):
+        out = self.path("out.prc")
+        result = run("set", self.a, "0x0000000010", "7", "-o", out)
+        self.assertEqual(result.returncode, 0, result.stderr)
+        self.assertEqual(param(out)[H].value, 7)
+        self.assertEqual(param(self.a)[H].value, 1)
+
+    def test_xml_round_trip(self):
+        xml = self.path("a.xml")
+        out = self.path("out.prc")
+        self.assertEqual(run("to-xml", self.a, "-o", xml).returncode, 0)
+        result = run("from-xml", xml, "-o", out)
+        self.assertEqual(result.returncode, 0, result.stderr)
+        self.assertEqual(param(out).diff(param(self.a)), [])
+
+    def test_diff_and_patch(self):
+        patch = self.path("patch.json")
+        out = self.path("out.prc")
+        result = run("diff", self.a, self.b, "-o", patch)
+        self.assertEqual(result.returncode, 0, result.stderr)
+        result = run("patch", self.a, patch, "-o", out)
+        self.assertEqual(result.returncode, 0, result.stderr)
+        self.assertEqual(param(out).diff(param(self.b)), [])
+
+    def test_errors(self):
+        for args in [(), ("frobnicate",), ("get", self.a), ("dump", self.path("missing.prc"))]:
+            result = run(*args)
+            self.assertEqual(result.returncode, 1, args)
+            self.assertTrue(result.stderr.startswith("error:"), result.stderr)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,65 @@
+import unittest
+
+from pyprc import ParamTypeKind, hash, param
+
+H = hash(0x10)
+K = hash(0x11)
+V = hash(0x12)
+
+
+def entry():
+    return param.struct([
+        (H, param.u8(1)),
+        (K, param.float(0.5)),
+        (V, param.list([param.struct([(H, param.hash(0x20))])])),
+    ])
+
+
+class CloneWithTest(unittest.TestCase):
+    def test_overrides_keep_the_target_type(self):
+        clone = entry().clone_with({(H,): 7, (K,): 2, (V, 0, H): hash(0x30)})
+        self.assertEqual(clone[H].type, ParamTypeKind.U8)
+        self.assertEqual(clone[H].value, 7)
+        self.assertEqual(clone[K].type, ParamTypeKind.FLOAT)
+        self.assertEqual(clone[K].value, 2.0)
+        self.assertEqual(clone[V][0][H].value, hash(0x30))
+
+    def test_string_paths(self):
+        clone = entry().clone_with({"0x0000000012/0/0x0000000010": hash(0x31)})
+        self.assertEqual(clone[V][0][H].value, hash(0x31))
+
+    def test_out_of_range_override(self):
+        with self.assertRaises(OverflowError):
+            entry().clone_with({(H,): 300})
+
+    def test_missing_path(self):
+        with self.assertRaises(KeyError):
+            entry().clone_with({(hash(0x99),): 1})
+        with self.assertRaises(KeyError):
+            entry().clone_with({(V, 3, H): 1})
+
+    def test_create(self):
+        clone = entry().clone_with({(hash(0x99), hash(0x98)): 5}, create=True)
+        self.assertEqual(clone[hash(0x99)][hash(0x98)].value, 5)
+
+    def test_clone_is_independent(self):
+        source = entry()
+        clone = source.clone_with({(H,): 2})
+        clone[V][0][H].value = hash(0x40)
+        source[K].value = 1.5
+        self.assertEqual(source[H].value, 1)
+        self.assertEqual(source[V][0][H].value, hash(0x20))
+        self.assertEqual(clone[K].value, 0.5)
+
+    def test_param_overrides_are_copied(self):
+        value = param.list([param.u8(3)])
+        clone = entry().clone_with({(V,): value})
+        value[0].value = 4
+        self.assertEqual(clone[V][0].value, 3)
+        value.freeze(in_place=True)
+        clone[V][0].value = 5
+        self.assertEqual(value[0].value, 4)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,115 @@
+import unittest
+
+from pyprc import ParamTypeKind, hash, param
+
+
+class OfTest(unittest.TestCase):
+    def test_kind_forms(self):
+        for kind in ["u16", "U16", ParamTypeKind.U16, 5]:
+            p = param.of(kind, 300)
+            self.assertEqual((p.type, p.value), (ParamTypeKind.U16, 300))
+        self.assertEqual(param.of("hash", hash(0x10)).value, hash(0x10))
+        self.assertEqual(param.of("str", "x").value, "x")
+        self.assertEqual(len(param.of("list", [param.u8(1), param.u8(2)])), 2)
+
+    def test_unknown_kind(self):
+        with self.assertRaises(ValueError):
+            param.of("u64", 1)
+        with self.assertRaises(ValueError):
+            param.of(13, 1)
+
+    def test_range(self):
+        with self.assertRaises(OverflowError):
+            param.of("u8", 256)
+        with self.assertRaises(OverflowError):
+            param.of("i8", -129)
+
+    def test_float_precision(self):
+        self.assertEqual(param.of("float", 16777216).value, 16777216.0)
+        self.assertEqual(param.of("float", -3).value, -3.0)
+        with self.assertRaises(OverflowError):
+            param.of("float", 16777217)
+
+
+class InferTest(unittest.TestCase):
+    def test_narrowest_type(self):
+        cases = [
+            (True, ParamTypeKind.BOOL),
+            (255, ParamTypeKind.U8),
+            (-1, ParamTypeKind.I8),
+            (300, ParamTypeKind.U16),
+            (-200, ParamTypeKind.I16),
+            (70000, ParamTypeKind.U32),
+            (-70000, ParamTypeKind.I32),
+            (1.5, ParamTypeKind.FLOAT),
+            ("x", ParamTypeKind.STR),
+            (hash(0x10), ParamTypeKind.HASH),
+            ([1], ParamTypeKind.LIST),
+            ({hash(0x10): 1}, ParamTypeKind.STRUCT),
+        ]
+        for value, kind in cases:
+            self.assertEqual(param.infer(value).type, kind, value)
+
+    def test_too_large(self):
+        with self.assertRaises(OverflowError):
+            param.infer(2**32)
+        with self.assertRaises(OverflowError):
+            param.infer(-(2**31) - 1)
+
+    def test_uninferrable(self):
+        with self.assertRaises(TypeError):
+            param.infer(object())
+
+
+class ConvertTest(unittest.TestCase):
+    def converted(self, value, rounding):
+        p = param.float(value)
+        p.convert("i8", rounding=rounding)
+        return p.value
+
+    def test_rounding_modes(self):
+        values = [2.5, -2.5, 1.4, -1.6]
+        expected = {
+            "floor": [2, -3, 1, -2],
+            "ceil": [3, -2, 2, -1],
+            "round": [3, -3, 1, -2],
+            "trunc": [2, -2, 1, -1],
+        }
+        for rounding, results in expected.items():
+            self.assertEqual([self.converted(v, rounding) for v in values], results, rounding)
+
+    def test_whole_floats_need_no_rounding(self):
+        p = param.float(4.0)
+        p.convert("u8")
+        self.assertEqual((p.type, p.value), (ParamTypeKind.U8, 4))
+        with self.assertRaises(ValueError):
+            param.float(4.5).convert("u8")
+        with self.assertRaises(ValueError):
+            param.float(1.5).convert("u8", rounding="nearest")
+
+    def test_range(self):
+        with self.assertRaises(OverflowError):
+            param.u16(300).convert("u8")
+        with self.assertRaises(OverflowError):
+            param.i8(-1).convert("u32")
+        with self.assertRaises(OverflowError):
+            param.float(300.0).convert("i8", rounding="round")
+        with self.assertRaises(OverflowError):
+            param.u8(2).convert("bool")
+
+    def test_to_float(self):
+        p = param.u32(16777216)
+        p.convert("float")
+        self.assertEqual(p.value, 16777216.0)
+        p = param.u32(16777217)
+        with self.assertRaises(OverflowError):
+            p.convert("float")
+        self.assertEqual((p.type, p.value), (ParamTypeKind.U32, 16777217))
+
+    def test_non_numbers(self):
+        with self.assertRaises(TypeError):
+            param.str("1").convert("u8")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,57 @@
+import unittest
+
+from pyprc import hash, param
+
+H = hash(0x10)
+K = hash(0x11)
+V = hash(0x12)
+
+
+def entry(key, value):
+    return param.struct([(K, param.u8(key)), (V, param.u8(value))])
+
+
+def tree(value, entries):
+    return param.struct([(H, param.u8(value)), (V, param.list(entries))])
+
+
+class DiffTest(unittest.TestCase):
+    def test_same_trees(self):
+        self.assertEqual(tree(1, [entry(1, 1)]).diff(tree(1, [entry(1, 1)])), [])
+
+    def test_changed_values(self):
+        changes = tree(1, [entry(1, 1)]).diff(tree(2, [entry(1, 3)]))
+        self.assertEqual([path for path, _, _ in changes], [(H,), (V, 0, V)])
+        self.assertEqual([(old.value, new.value) for _, old, new in changes], [(1, 2), (1, 3)])
+
+    def test_added_and_removed(self):
+        a = tree(1, [entry(1, 1)])
+        b = tree(1, [entry(1, 1), entry(2, 2)])
+        [(path, old, new)] = a.diff(b)
+        self.assertEqual(path, (V, 1))
+        self.assertIsNone(old)
+        self.assertEqual(new[V].value, 2)
+        [(path, old, new)] = b.diff(a)
+        self.assertEqual(path, (V, 1))
+        self.assertIsNone(new)
+
+    def test_patch(self):
+        a = tree(1, [entry(1, 1), entry(2, 2)])
+        b = tree(2, [entry(1, 3)])
+        a.patch(a.diff(b))
+        self.assertEqual(a.diff(b), [])
+        c = tree(1, [entry(1, 1)])
+        c.patch(c.diff(tree(1, [entry(1, 1), entry(2, 2), entry(3, 3)])))
+        self.assertEqual([p[V].value for p in c[V]], [1, 2, 3])
+
+    def test_patch_is_one_step(self):
+        a = tree(1, [entry(1, 1)])
+        a.track_history()
+        a.patch(a.diff(tree(2, [entry(1, 3)])))
+        self.assertTrue(a.undo())
+        self.assertEqual((a[H].value, a[V][0][V].value), (1, 1))
+        self.assertFalse(a.undo())
+
+
+if __name__ == "__main__":
+    unittest.main()
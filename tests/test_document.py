@@ -0,0 +1,74 @@
+import unittest
+
+from pyprc import hash, param
+
+A = hash(0x10)
+B = hash(0x11)
+C = hash(0x12)
+
+
+def tree():
+    return param.struct([
+        (A, param.list([
+            param.struct([(B, param.u8(1)), (C, param.float(1.5))]),
+            param.struct([(B, param.i16(-2)), (C, param.float(2.0))]),
+        ])),
+        (B, param.hash(hash(0x99))),
+        (C, param.str("text: with 'quotes'")),
+        (hash(0x13), param.bool(True)),
+        (hash(0x14), param.u32(4000000000)),
+        (hash(0x15), param.list([])),
+    ])
+
+
+class YamlTest(unittest.TestCase):
+    def test_round_trip(self):
+        self.assertEqual(param.from_yaml(tree().to_yaml()), tree())
+
+    def test_round_trip_with_template(self):
+        text = tree().to_yaml(template=tree())
+        self.assertNotIn("!u8", text)
+        self.assertEqual(param.from_yaml(text, template=tree()), tree())
+
+    def test_tags(self):
+        text = tree().to_yaml()
+        self.assertIn("'0x0000000011': 1\n", text)
+        self.assertIn("!i16 -2", text)
+        self.assertIn("!hash '0x0000000099'", text)
+
+    def test_untagged_values_take_template_types(self):
+        template = param.struct([(A, param.u16(0))])
+        self.assertEqual(param.from_yaml("'0x0000000010': 7", template=template)[A].type, 5)
+
+    def test_invalid(self):
+        with self.assertRaises(ValueError):
+            param.from_yaml("0x0000000010: [1, 2")
+
+
+class TomlTest(unittest.TestCase):
+    def test_round_trip(self):
+        self.assertEqual(param.from_toml(tree().to_toml()), tree())
+
+    def test_round_trip_with_template(self):
+        text = tree().to_toml(template=tree())
+        self.assertEqual(param.from_toml(text, template=tree()), tree())
+
+    def test_tagged_value(self):
+        self.assertIn('0x0000000011 = { "!i16" = -2 }', tree().to_toml())
+
+    def test_field_order(self):
+        root = param.struct([
+            (A, param.struct([(B, param.u8(1))])),
+            (B, param.list([param.struct([(C, param.u8(2))])])),
+            (C, param.u8(3)),
+            (hash(0x13), param.list([param.struct([(C, param.u8(4))])])),
+        ])
+        self.assertEqual(param.from_toml(root.to_toml()), root)
+
+    def test_root_must_be_struct(self):
+        with self.assertRaises(TypeError):
+            param.list([param.u8(1)]).to_toml()
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,65 @@
+import os
+import shutil
+import sys
+import tempfile
+import unittest
+
+import pyprc
+from pyprc import LabelMap, ParamTypeKind, hash, param
+
+H = hash(0x10)
+K = hash(0x11)
+
+
+class GrepTest(unittest.TestCase):
+    def setUp(self):
+        self.dir = tempfile.mkdtemp()
+        os.mkdir(os.path.join(self.dir, "sub"))
+        param.struct([(H, param.u8(3))]).save(os.path.join(self.dir, "a.prc"))
+        param.struct([(K, param.list([param.u32(3), param.u8(4)]))]).save(
+            os.path.join(self.dir, "sub", "b.prc")
+        )
+        with open(os.path.join(self.dir, "broken.prc"), "wb") as f:
+            f.write(b"not a param file")
+
+    def tearDown(self):
+        shutil.rmtree(self.dir)
+
+    def test_matches_in_order(self):
+        found = pyprc.grep(self.dir, 3)
+        self.assertIs(iter(found), found)
+        self.assertEqual(
+            [(os.path.relpath(f, self.dir), p, k) for f, p, k in found],
+            [
+                ("a.prc", (H,), ParamTypeKind.U8),
+                (os.path.join("sub", "b.prc"), (K, 0), ParamTypeKind.U32),
+            ],
+        )
+
+    def test_kind(self):
+        found = list(pyprc.grep(self.dir, 3, kind="u32"))
+        self.assertEqual([p for _, p, _ in found], [(K, 0)])
+
+    def test_labels(self):
+        labels = LabelMap()
+        labels.add_custom_labels([(hash(0x99), "custom_name")])
+        param.struct([(H, param.hash(hash(0x99)))]).save(os.path.join(self.dir, "c.prc"))
+        self.assertEqual(list(pyprc.grep(self.dir, "custom_name")), [])
+        found = pyprc.grep(self.dir, "custom_name", labels=labels)
+        self.assertEqual([os.path.basename(f) for f, _, _ in found], ["c.prc"])
+
+    def test_stop_early(self):
+        found = pyprc.grep(self.dir, 3)
+        next(found)
+        del found
+
+    @unittest.skipIf(sys.platform == "win32", "paths are always unicode on windows")
+    def test_non_utf8_path(self):
+        name = os.fsdecode(b"\xff.prc")
+        shutil.copy(os.path.join(self.dir, "a.prc"), os.path.join(self.dir, name))
+        files = [f for f, _, _ in pyprc.grep(self.dir, 3)]
+        self.assertIn(os.path.join(self.dir, name), files)
+
+
+if __name__ == "__main__":
+    unittest.main()
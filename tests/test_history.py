@@ -0,0 +1,142 @@
+import unittest
+
+from pyprc import hash, param
+
+H = hash(0x10)
+K = hash(0x11)
+
+
+def tree():
+    return param.struct([(H, param.u8(1)), (K, param.list([param.u8(2), param.u8(3)]))])
+
+
+class UndoTest(unittest.TestCase):
+    def test_history_is_opt_in(self):
+        root = tree()
+        root[H].value = 5
+        with self.assertRaises(ValueError):
+            root.undo()
+        with self.assertRaises(ValueError):
+            root.redo()
+
+    def test_undo_and_redo(self):
+        root = tree()
+        root.track_history()
+        root[H].value = 5
+        root[K][0].value = 6
+        self.assertTrue(root.undo())
+        self.assertEqual(root[K][0].value, 2)
+        self.assertTrue(root.undo())
+        self.assertEqual(root[H].value, 1)
+        self.assertFalse(root.undo())
+        self.assertTrue(root.redo())
+        self.assertEqual(root[H].value, 5)
+        root[H].value = 7
+        self.assertFalse(root.redo())
+
+    def test_limit(self):
+        root = tree()
+        root.track_history(limit=2)
+        for value in range(10, 15):
+            root[H].value = value
+        self.assertTrue(root.undo())
+        self.assertTrue(root.undo())
+        self.assertFalse(root.undo())
+        self.assertEqual(root[H].value, 12)
+
+    def test_undo_structure(self):
+        root = tree()
+        root.track_history()
+        lst = root[K]
+        lst.set_list(list(lst) + [param.u8(4)])
+        self.assertEqual(len(root[K]), 3)
+        root.undo()
+        self.assertEqual([p.value for p in root[K]], [2, 3])
+
+    def test_history_of_moved_tree(self):
+        a = param.u8(1)
+        a.track_history()
+        a.value = 2
+        lst = param.list([a])
+        with self.assertRaises(ValueError):
+            lst.undo()
+        self.assertTrue(a.undo())
+        self.assertEqual(lst[0].value, 1)
+
+    def test_shared_roots_keep_their_history(self):
+        r1 = tree()
+        r1.track_history()
+        p = param.list([r1[H]])
+        p.track_history()
+        r1[H].value = 5
+        p[0].value = 6
+        self.assertTrue(p.undo())
+        self.assertEqual(r1[H].value, 5)
+        self.assertFalse(p.undo())
+        self.assertTrue(r1.undo())
+        self.assertEqual(p[0].value, 1)
+
+
+class TransactionTest(unittest.TestCase):
+    def test_rollback_without_history(self):
+        root = tree()
+        with self.assertRaises(KeyError):
+            with root.transaction():
+                root[H].value = 9
+                root[K][1].value = 9
+                raise KeyError("stop")
+        self.assertEqual(root[H].value, 1)
+        self.assertEqual(root[K][1].value, 3)
+        with self.assertRaises(ValueError):
+            root.undo()
+
+    def test_rollback_composed_children(self):
+        root = param.struct([(H, param.u8(1))])
+        with self.assertRaises(RuntimeError):
+            with root.transaction():
+                root[H].value = 2
+                raise RuntimeError("stop")
+        self.assertEqual(root[H].value, 1)
+
+    def test_rollback_shared_param(self):
+        a = param.u8(1)
+        root = param.struct([(H, a)])
+        with self.assertRaises(RuntimeError):
+            with root.transaction():
+                a.value = 2
+                raise RuntimeError("stop")
+        self.assertEqual(a.value, 1)
+
+    def test_transaction_is_one_step(self):
+        root = tree()
+        root.track_history()
+        with root.transaction():
+            root[H].value = 8
+            with root.transaction():
+                root[K][0].value = 8
+        self.assertTrue(root.undo())
+        self.assertEqual((root[H].value, root[K][0].value), (1, 2))
+        self.assertFalse(root.undo())
+
+    def test_inner_rollback(self):
+        root = tree()
+        with root.transaction():
+            root[H].value = 8
+            try:
+                with root.transaction():
+                    root[K][0].value = 8
+                    raise ValueError()
+            except ValueError:
+                pass
+        self.assertEqual((root[H].value, root[K][0].value), (8, 2))
+
+    def test_undo_inside_transaction(self):
+        root = tree()
+        root.track_history()
+        with root.transaction():
+            with self.assertRaises(RuntimeError):
+                root.undo()
+
+
+if __name__ == "__main__":
+    unittest.main()
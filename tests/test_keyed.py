@@ -0,0 +1,109 @@
+import unittest
+
+from pyprc import LabelMap, hash, merge3, param
+
+K = hash(0x11)
+V = hash(0x12)
+
+
+def entry(key, value):
+    return param.struct([(K, param.u8(key)), (V, param.u8(value))])
+
+
+def entries(*pairs):
+    return param.list([entry(key, value) for key, value in pairs])
+
+
+class KeyedDiffTest(unittest.TestCase):
+    def test_reordered_elements_match(self):
+        a = entries((1, 1), (2, 2))
+        b = entries((2, 3), (1, 1))
+        [(path, old, new)] = [
+            c for c in a.diff(b, key_fields=[K]) if c[1] is not None and c[2] is not None
+        ]
+        self.assertEqual(path, ((K, 2), V))
+        self.assertEqual((old.value, new.value), (2, 3))
+
+    def test_first_shared_key_field(self):
+        a = entries((1, 1), (2, 2))
+        b = entries((1, 1), (2, 3))
+        [(path, _, _)] = a.diff(b, key_fields=[hash(0x99), K])
+        self.assertEqual(path, ((K, 2), V))
+
+    def test_index_without_shared_key_field(self):
+        a = param.list([entry(1, 1), param.struct([(V, param.u8(2))])])
+        b = param.list([entry(1, 1), param.struct([(V, param.u8(3))])])
+        [(path, _, _)] = a.diff(b, key_fields=[K])
+        self.assertEqual(path, (1, V))
+
+    def test_keyed_patch(self):
+        a = entries((1, 1), (2, 2))
+        b = entries((2, 3), (1, 1), (3, 3))
+        a.patch(a.diff(b, key_fields=[K]))
+        self.assertEqual(a.diff(b, key_fields=[K]), [])
+
+
+class KeyedMergeTest(unittest.TestCase):
+    def test_reordered_and_changed(self):
+        base = entries((1, 1), (2, 2))
+        ours = entries((2, 2), (1, 5))
+        theirs = entries((1, 1), (2, 6), (3, 3))
+        merged, conflicts = merge3(base, ours, theirs, key_fields=[K])
+        self.assertEqual(conflicts, [])
+        self.assertEqual([(p[K].value, p[V].value) for p in merged], [(2, 6), (1, 5), (3, 3)])
+
+    def test_conflict_path(self):
+        merged, conflicts = merge3(
+            entries((1, 1)), entries((1, 2)), entries((1, 3)), key_fields=[K]
+        )
+        self.assertEqual([path for path, _, _, _ in conflicts], [((K, 1), V)])
+        self.assertEqual(merged[0][V].value, 2)
+
+
+class KeyedPathTest(unittest.TestCase):
+    def test_clone_with_keyed_paths(self):
+        root = param.struct([(V, entries((1, 1), (2, 2)))])
+        clone = root.clone_with({"0x0000000012/[0x0000000011=2]/0x0000000012": 9})
+        self.assertEqual(clone[V][1][V].value, 9)
+        clone = root.clone_with({(V, (K, 1), V): 8})
+        self.assertEqual(clone[V][0][V].value, 8)
+
+    def test_paths_use_the_given_labels(self):
+        labels = LabelMap()
+        labels.add_custom_labels([(V, "entries"), (K, "key"), (hash(0x20), "second")])
+        root = param.struct([(V, param.list([
+            param.struct([(K, param.hash(0x21)), (V, param.u8(1))]),
+            param.struct([(K, param.hash(0x20)), (V, param.u8(2))]),
+        ]))])
+        clone = root.clone_with({"entries/[key=second]/entries": 9}, labels=labels)
+        self.assertEqual(clone[V][1][V].value, 9)
+        clone = root.clone_with({(V, (K, "second"), V): 8}, labels=labels)
+        self.assertEqual(clone[V][1][V].value, 8)
+        with self.assertRaisesRegex(KeyError, r"'\[key=missing\]' was not found in 'entries'"):
+            root.clone_with({"entries/[key=missing]/entries": 7}, labels=labels)
+        # the global labels don't know these names
+        with self.assertRaises(KeyError):
+            root.clone_with({(V, (K, "second"), V): 8})
+
+    def test_sort_and_patch_use_the_given_labels(self):
+        labels = LabelMap()
+        labels.add_custom_labels([(K, "key"), (V, "value"), (hash(0x20), "second")])
+        lst = entries((2, 1), (1, 2))
+        lst.sort("value", labels=labels)
+        self.assertEqual([p[K].value for p in lst], [2, 1])
+        rows = param.list([param.struct([(K, param.hash(h)), (V, param.u8(0))]) for h in [0x21, 0x20]])
+        rows.patch([((("key", "second"), "value"), param.u8(0), param.u8(5))], labels=labels)
+        self.assertEqual(rows[1][V].value, 5)
+
+    def test_bools_are_not_indices(self):
+        lst = entries((1, 1), (2, 2))
+        with self.assertRaises(TypeError):
+            lst.clone_with({(True, V): 5})
+        with self.assertRaises(TypeError):
+            lst.sort((False, V))
+        clone = lst.clone_with({(1, V): 5})
+        self.assertEqual(clone[1][V].value, 5)
+
+
+if __name__ == "__main__":
+    unittest.main()
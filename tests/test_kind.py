@@ -0,0 +1,58 @@
+import enum
+import unittest
+
+import pyprc
+from pyprc import ParamTypeKind, hash, param
+
+
+class ParamTypeKindTest(unittest.TestCase):
+    def test_members(self):
+        self.assertTrue(issubclass(ParamTypeKind, enum.IntEnum))
+        names = [kind.name for kind in ParamTypeKind]
+        self.assertEqual(names, [
+            "BOOL", "I8", "U8", "I16", "U16", "I32", "U32", "FLOAT", "HASH", "STR", "LIST", "STRUCT",
+        ])
+        self.assertEqual([int(kind) for kind in ParamTypeKind], list(range(1, 13)))
+        self.assertEqual(ParamTypeKind.__module__, "pyprc")
+
+    def test_old_constants_are_members(self):
+        for kind in ParamTypeKind:
+            self.assertIs(getattr(pyprc, "PARAM_TYPE_" + kind.name), kind)
+        self.assertEqual(pyprc.PARAM_TYPE_U8, 3)
+
+    def test_param_type(self):
+        params = [
+            (param.bool(True), ParamTypeKind.BOOL),
+            (param.i8(-1), ParamTypeKind.I8),
+            (param.u8(1), ParamTypeKind.U8),
+            (param.i16(-1), ParamTypeKind.I16),
+            (param.u16(1), ParamTypeKind.U16),
+            (param.i32(-1), ParamTypeKind.I32),
+            (param.u32(1), ParamTypeKind.U32),
+            (param.float(1.0), ParamTypeKind.FLOAT),
+            (param.hash(hash(0x10)), ParamTypeKind.HASH),
+            (param.str("a"), ParamTypeKind.STR),
+            (param.list([]), ParamTypeKind.LIST),
+            (param.struct([]), ParamTypeKind.STRUCT),
+        ]
+        for p, kind in params:
+            with self.subTest(kind):
+                self.assertIs(p.type, kind)
+
+    def test_readable(self):
+        self.assertEqual(str(ParamTypeKind.U8), "ParamTypeKind.U8")
+        self.assertEqual(repr(param.u8(1).type), "<ParamTypeKind.U8: 3>")
+
+    def test_compares_as_a_number(self):
+        self.assertEqual(param.u8(1).type, 3)
+        self.assertLess(ParamTypeKind.U8, ParamTypeKind.STRUCT)
+        self.assertEqual(ParamTypeKind(12), ParamTypeKind.STRUCT)
+
+    def test_type_follows_set(self):
+        p = param.u8(1)
+        p.set_str("a")
+        self.assertIs(p.type, ParamTypeKind.STR)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,111 @@
+import os
+import tempfile
+import unittest
+
+from pyprc import LabelMap, hash, param
+
+H = hash(0x10)
+K = hash(0x11)
+
+
+class LabelMapTest(unittest.TestCase):
+    def test_custom_labels(self):
+        labels = LabelMap()
+        labels.add_custom_labels([(H, "first"), (K, "second")])
+        self.assertEqual(len(labels), 2)
+        self.assertIn(H, labels)
+        self.assertNotIn(hash(0x12), labels)
+        self.assertEqual(labels.label_of(H), "first")
+        self.assertIsNone(labels.label_of(hash(0x12)))
+        self.assertEqual(labels.hash_of("second"), K)
+
+    def test_added_labels_are_hashed(self):
+        labels = LabelMap()
+        labels.add_labels(["fighter_kind"])
+        self.assertEqual(labels.hash_of("fighter_kind"), hash("fighter_kind"))
+        self.assertEqual(labels.label_of(hash("fighter_kind")), "fighter_kind")
+
+    def test_load_labels(self):
+        fd, path = tempfile.mkstemp(suffix=".csv")
+        with os.fdopen(fd, "w") as f:
+            f.write("0x0000000010,first\n0x0000000011,second\n")
+        try:
+            labels = LabelMap()
+            labels.load_labels(path)
+            self.assertEqual(labels.label_of(K), "second")
+        finally:
+            os.remove(path)
+        with self.assertRaises(OSError):
+            LabelMap().load_labels(path)
+
+    def test_strict(self):
+        labels = LabelMap()
+        self.assertFalse(labels.strict)
+        self.assertEqual(labels.hash_of("unknown"), hash("unknown"))
+        labels.strict = True
+        with self.assertRaises(LookupError):
+            labels.hash_of("unknown")
+        with self.assertRaises(LookupError):
+            hash("unknown", labels)
+
+    def test_maps_are_separate(self):
+        a, b = LabelMap(), LabelMap()
+        a.add_custom_labels([(H, "first")])
+        self.assertEqual(len(b), 0)
+        self.assertIsNone(LabelMap.global_map().label_of(H))
+
+    def test_merge(self):
+        a, b = LabelMap(), LabelMap()
+        a.add_custom_labels([(H, "old"), (K, "kept")])
+        b.add_custom_labels([(H, "new")])
+        a.merge(b)
+        self.assertEqual((a.label_of(H), a.label_of(K)), ("new", "kept"))
+        self.assertEqual(len(b), 1)
+        a.merge(a)
+        self.assertEqual(len(a), 2)
+
+    def test_copy_and_clear(self):
+        labels = LabelMap()
+        labels.add_custom_labels([(H, "first")])
+        copy = labels.copy()
+        labels.clear()
+        self.assertEqual(len(labels), 0)
+        self.assertEqual(copy.label_of(H), "first")
+
+    def test_global_map_is_shared(self):
+        labels = LabelMap.global_map()
+        LabelMap.global_map().add_custom_labels([(hash(0x7F), "global_test_label")])
+        self.assertEqual(labels.label_of(hash(0x7F)), "global_test_label")
+        self.assertEqual(str(hash(0x7F)), "global_test_label")
+        self.assertEqual(repr(LabelMap()), "LabelMap (len = 0)")
+
+
+class LabelsArgumentTest(unittest.TestCase):
+    def setUp(self):
+        self.labels = LabelMap()
+        self.labels.add_custom_labels([(H, "first")])
+
+    def test_hash(self):
+        self.assertEqual(hash("first", self.labels), H)
+        self.assertEqual(H.to_label(self.labels), "first")
+        self.assertEqual(H.to_label(), "0x0000000010")
+
+    def test_xml(self):
+        fd, path = tempfile.mkstemp(suffix=".xml")
+        os.close(fd)
+        try:
+            root = param.struct([(H, param.hash(K))])
+            root.to_xml(path, self.labels)
+            with open(path) as f:
+                self.assertIn('hash="first"', f.read())
+            self.assertEqual(param.from_xml(path, self.labels), root)
+            strict = LabelMap()
+            strict.strict = True
+            with self.assertRaisesRegex(ValueError, "'first'"):
+                param.from_xml(path, strict)
+        finally:
+            os.remove(path)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,86 @@
+import os
+import tempfile
+import unittest
+
+from pyprc import hash, param
+
+H = hash(0x10)
+K = hash(0x11)
+
+
+def tree():
+    rows = [
+        param.struct([(H, param.list([param.str("s%d" % i)])), (K, param.u8(i))]) for i in range(3)
+    ]
+    return param.struct([(H, param.list(rows)), (K, param.float(0.5))])
+
+
+class LazyTest(unittest.TestCase):
+    def setUp(self):
+        fd, self.path = tempfile.mkstemp(suffix=".prc")
+        os.close(fd)
+        tree().save(self.path)
+
+    def tearDown(self):
+        os.remove(self.path)
+
+    def test_same_as_eager(self):
+        self.assertEqual(param.open_lazy(self.path), param(self.path))
+        self.assertEqual(param(self.path), tree())
+
+    def test_types_of_unread_params(self):
+        lazy = param.open_lazy(self.path)
+        self.assertEqual([p.type for _, p in lazy], [p.type for _, p in tree()])
+        self.assertEqual(len(lazy[H][2][H]), 1)
+
+    def test_save_over_source(self):
+        lazy = param.open_lazy(self.path)
+        lazy[K].value = 1.5
+        lazy.save(self.path)
+        self.assertEqual(lazy[H][1][H][0].value, "s1")
+        self.assertEqual(param(self.path), lazy)
+
+    def test_save_over_source_of_clone(self):
+        lazy = param.open_lazy(self.path)
+        clone = lazy.clone()
+        param.struct([(K, param.u8(1))]).save(self.path)
+        self.assertEqual(clone[H][2][K].value, 2)
+        self.assertEqual(clone, tree())
+        self.assertEqual(param(self.path), param.struct([(K, param.u8(1))]))
+
+    def test_invalid_file(self):
+        with open(self.path, "wb") as f:
+            f.write(b"not a param file")
+        with self.assertRaises(OSError):
+            param(self.path)
+        with self.assertRaises(ValueError):
+            param.open_lazy(self.path)
+
+    def test_invalid_param_inside(self):
+        inner = param.struct([(K, param.u32(0x01020304))])
+        param.struct([(H, param.list([inner])), (K, param.u8(7))]).save(self.path)
+        with open(self.path, "rb") as f:
+            data = f.read()
+        with open(self.path, "wb") as f:
+            f.write(data.replace(b"\x07\x04\x03\x02\x01", b"\x20\x04\x03\x02\x01"))
+        lazy = param.open_lazy(self.path)
+        self.assertEqual(lazy[K].value, 7)
+        with self.assertRaisesRegex(ValueError, "invalid param type 32"):
+            lazy[H][0][K]
+        with self.assertRaises(OSError):
+            param(self.path)
+
+    def test_file_changed_after_opening(self):
+        lazy = param.open_lazy(self.path)
+        with open(self.path, "rb") as f:
+            data = f.read()
+        # written over in place, so the mapped file changes under the tree
+        with open(self.path, "r+b") as f:
+            f.write(data[:-8] + b"\xff" * 8)
+        with self.assertRaises(ValueError):
+            lazy.clone().save(self.path + ".copy")
+        self.assertFalse(os.path.exists(self.path + ".copy"))
+
+
+if __name__ == "__main__":
+    unittest.main()
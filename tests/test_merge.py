@@ -0,0 +1,57 @@
+import unittest
+
+from pyprc import hash, merge3, param
+
+H = hash(0x10)
+K = hash(0x11)
+V = hash(0x12)
+
+
+def tree(a, b):
+    return param.struct([(H, param.u8(a)), (K, param.u8(b))])
+
+
+def entry(key, value):
+    return param.struct([(K, param.u8(key)), (V, param.u8(value))])
+
+
+class MergeTest(unittest.TestCase):
+    def test_clean_merge(self):
+        merged, conflicts = merge3(tree(1, 1), tree(2, 1), tree(1, 3))
+        self.assertEqual((merged[H].value, merged[K].value), (2, 3))
+        self.assertEqual(conflicts, [])
+
+    def test_same_change_on_both_sides(self):
+        merged, conflicts = merge3(tree(1, 1), tree(2, 1), tree(2, 1))
+        self.assertEqual(merged[H].value, 2)
+        self.assertEqual(conflicts, [])
+
+    def test_conflicts(self):
+        for strategy, value in [("ours", 2), ("theirs", 3), ("base", 1)]:
+            merged, conflicts = merge3(tree(1, 1), tree(2, 1), tree(3, 4), strategy=strategy)
+            self.assertEqual((merged[H].value, merged[K].value), (value, 4))
+            [(path, base, ours, theirs)] = conflicts
+            self.assertEqual(path, (H,))
+            self.assertEqual((base.value, ours.value, theirs.value), (1, 2, 3))
+
+    def test_unknown_strategy(self):
+        with self.assertRaises(ValueError):
+            merge3(tree(1, 1), tree(2, 1), tree(3, 1), strategy="mine")
+
+    def test_inputs_are_unchanged(self):
+        base, ours, theirs = tree(1, 1), tree(2, 1), tree(1, 3)
+        merge3(base, ours, theirs)
+        self.assertEqual((ours[H].value, ours[K].value), (2, 1))
+        self.assertEqual((theirs[H].value, theirs[K].value), (1, 3))
+
+    def test_list_elements_added_on_both_sides(self):
+        base = param.list([param.u8(1)])
+        ours = param.list([param.u8(1), param.u8(2)])
+        theirs = param.list([param.u8(1), param.u8(3)])
+        merged, conflicts = merge3(base, ours, theirs, strategy="theirs")
+        self.assertEqual([p.value for p in merged], [1, 3])
+        self.assertEqual([path for path, _, _, _ in conflicts], [(1,)])
+
+
+if __name__ == "__main__":
+    unittest.main()
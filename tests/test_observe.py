@@ -0,0 +1,169 @@
+import unittest
+
+from pyprc import hash, param
+
+H = hash(0x10)
+K = hash(0x11)
+
+
+def tree():
+    return param.struct([(H, param.u8(1)), (K, param.list([param.u8(2), param.u8(3)]))])
+
+
+def value(p):
+    return None if p is None else p.value
+
+
+def record(p):
+    events = []
+    p.on_change(lambda path, old, new: events.append((path, value(old), value(new))))
+    return events
+
+
+class OnChangeTest(unittest.TestCase):
+    def test_value_change(self):
+        root = tree()
+        events = record(root)
+        root[H].value = 5
+        self.assertEqual(events, [((H,), 1, 5)])
+
+    def test_relative_path(self):
+        root = tree()
+        events = record(root[K])
+        root[K][1].value = 7
+        root[H].value = 5
+        self.assertEqual(events, [((1,), 3, 7)])
+
+    def test_composed_children(self):
+        a = param.u8(1)
+        root = param.struct([(H, a)])
+        events = record(root)
+        a.value = 2
+        root[H].value = 3
+        self.assertEqual(events, [((H,), 1, 2), ((H,), 2, 3)])
+
+    def test_callback_of_moved_tree(self):
+        a = param.u8(1)
+        events = record(a)
+        root = param.list([a])
+        root[0].value = 2
+        self.assertEqual(events, [((), 1, 2)])
+
+    def test_added_child(self):
+        root = tree()
+        events = record(root)
+        lst = root[K]
+        lst.set_list(list(lst) + [param.u8(4)])
+        self.assertEqual(events, [((K, 2), None, 4)])
+
+    def test_undo_and_redo(self):
+        root = tree()
+        root.track_history()
+        events = record(root)
+        root[H].value = 5
+        root.undo()
+        root.redo()
+        self.assertEqual(events, [((H,), 1, 5), ((H,), 5, 1), ((H,), 1, 5)])
+
+
+class TransactionEventTest(unittest.TestCase):
+    def test_held_until_commit(self):
+        root = tree()
+        events = record(root)
+        with root.transaction():
+            root[H].value = 5
+            root[H].value = 6
+            root[K][0].value = 7
+            with root.transaction():
+                root[K][1].value = 8
+            self.assertEqual(events, [])
+        self.assertEqual(events, [((H,), 1, 6), ((K, 0), 2, 7), ((K, 1), 3, 8)])
+
+    def test_set_back_inside_transaction(self):
+        root = tree()
+        events = record(root)
+        with root.transaction():
+            root[H].value = 5
+            root[H].value = 1
+        self.assertEqual(events, [])
+
+    def test_rolled_back(self):
+        root = tree()
+        events = record(root)
+        with self.assertRaises(KeyError):
+            with root.transaction():
+                root[H].value = 5
+                raise KeyError("stop")
+        self.assertEqual(events, [])
+        self.assertEqual(root[H].value, 1)
+
+    def test_inner_rollback(self):
+        root = tree()
+        events = record(root)
+        with root.transaction():
+            root[H].value = 5
+            try:
+                with root.transaction():
+                    root[K][0].value = 9
+                    raise ValueError()
+            except ValueError:
+                pass
+        self.assertEqual(events, [((H,), 1, 5)])
+
+    def test_many_changes_in_one_commit(self):
+        rows = param.list([param.struct([(H, param.u16(i))]) for i in range(2000)])
+        root = param.struct([(K, rows)])
+        events = record(root)
+        rows_events = record(root[K])
+        with root.transaction():
+            for i, row in enumerate(root[K]):
+                row[H].value = i + 1
+        self.assertEqual(len(events), 2000)
+        self.assertEqual(events[1999], ((K, 1999, H), 1999, 2000))
+        self.assertEqual(rows_events[5], ((5, H), 5, 6))
+
+    def test_shared_param_uses_first_path(self):
+        a = param.u8(1)
+        root = param.struct([(H, a), (K, param.list([a]))])
+        events = record(root)
+        root[K][0].value = 2
+        self.assertEqual(events, [((H,), 1, 2)])
+
+    def test_overlay_is_one_change(self):
+        root = tree()
+        events = record(root)
+        root.apply_overlay({H: 4})
+        self.assertEqual(events, [((H,), 1, 4)])
+
+
+class CallbackErrorTest(unittest.TestCase):
+    def test_exception_is_reported(self):
+        import sys
+
+        root = tree()
+        root.on_change(lambda *args: 1 / 0)
+        reported = []
+        hook = sys.unraisablehook
+        sys.unraisablehook = reported.append
+        try:
+            root[H].value = 5
+        finally:
+            sys.unraisablehook = hook
+        self.assertEqual(root[H].value, 5)
+        self.assertEqual(len(reported), 1)
+        self.assertIs(reported[0].exc_type, ZeroDivisionError)
+
+    def test_callback_can_change_tree(self):
+        root = tree()
+
+        def copy(path, old, new):
+            if path == (H,):
+                root[K][0].value = new.value
+
+        root.on_change(copy)
+        root[H].value = 9
+        self.assertEqual(root[K][0].value, 9)
+
+
+if __name__ == "__main__":
+    unittest.main()
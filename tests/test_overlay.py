@@ -0,0 +1,75 @@
+import unittest
+
+from pyprc import hash, param
+
+TABLE = hash(0x10)
+KIND = hash(0x11)
+JUMPS = hash(0x12)
+SPEED = hash(0x13)
+
+
+def root():
+    entries = [
+        param.struct([
+            (KIND, param.hash(hash(0x90 + i))),
+            (JUMPS, param.u8(2)),
+            (SPEED, param.float(1.0)),
+        ])
+        for i in range(3)
+    ]
+    return param.struct([(TABLE, param.list(entries)), (SPEED, param.float(0.5))])
+
+
+class OverlayTest(unittest.TestCase):
+    def test_nested_dict(self):
+        p = root()
+        p.apply_overlay({TABLE: {1: {JUMPS: 3}}, SPEED: 2})
+        self.assertEqual(p[TABLE][1][JUMPS].value, 3)
+        self.assertEqual(p[TABLE][1][JUMPS].type, 3)
+        self.assertEqual(p[SPEED].value, 2.0)
+
+    def test_yaml_text(self):
+        p = root()
+        p.apply_overlay("'0x0000000010': {2: {'0x0000000013': 4.5}}")
+        self.assertEqual(p[TABLE][2][SPEED].value, 4.5)
+
+    def test_list_key_fields(self):
+        p = root()
+        p.apply_overlay({TABLE: {hash(0x91): {JUMPS: 5}}}, list_key_fields=[KIND])
+        self.assertEqual([e[JUMPS].value for e in p[TABLE]], [2, 5, 2])
+
+    def test_list_of_values(self):
+        p = root()
+        p.apply_overlay({TABLE: [{JUMPS: 7}, {JUMPS: 8}]})
+        self.assertEqual([e[JUMPS].value for e in p[TABLE]], [7, 8, 2])
+
+    def test_unmatched_keys_change_nothing(self):
+        p = root()
+        with self.assertRaises(KeyError):
+            p.apply_overlay({SPEED: 3.0, TABLE: {5: {JUMPS: 1}}})
+        self.assertEqual(p, root())
+
+    def test_wrong_type(self):
+        p = root()
+        with self.assertRaises(TypeError):
+            p.apply_overlay({TABLE: 3})
+        with self.assertRaises(OverflowError):
+            p.apply_overlay({TABLE: {0: {JUMPS: 300}}})
+        self.assertEqual(p, root())
+
+    def test_undone_together(self):
+        p = root()
+        p.track_history()
+        p.apply_overlay({TABLE: {0: {JUMPS: 3}, 1: {JUMPS: 4}}})
+        self.assertTrue(p.undo())
+        self.assertEqual(p, root())
+        self.assertFalse(p.undo())
+
+    def test_read_only(self):
+        p = root().freeze()
+        with self.assertRaises(TypeError):
+            p.apply_overlay({SPEED: 3.0})
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,44 @@
+import unittest
+
+from pyprc import hash, param
+
+H = hash(0x10)
+
+
+def nested(depth):
+    p = param.u8(1)
+    for _ in range(depth):
+        p = param.struct([(H, p)])
+    return p
+
+
+class PformatTest(unittest.TestCase):
+    def test_max_items(self):
+        lst = param.list([param.u8(i) for i in range(5)])
+        text = lst.pformat(max_items=2)
+        self.assertEqual(text.splitlines()[1:], ["  0: u8 (0)", "  1: u8 (1)", "  ... 3 more"])
+
+    def test_max_depth(self):
+        text = nested(3).pformat(max_depth=1, labels=False)
+        self.assertEqual(text.splitlines()[-1], "  0x0000000010: struct (len = 1) ...")
+
+
+class HtmlTest(unittest.TestCase):
+    def test_default_depth(self):
+        html = nested(6)._repr_html_()
+        self.assertEqual(html.count("<details"), 3)
+        self.assertIn("struct (len = 1) ...", html)
+
+    def test_default_items(self):
+        html = param.list([param.u8(i) for i in range(60)])._repr_html_()
+        self.assertIn("<b>49</b>", html)
+        self.assertNotIn("<b>50</b>", html)
+        self.assertIn("... 10 more", html)
+
+    def test_escapes(self):
+        html = param.list([param.str("<a>")])._repr_html_()
+        self.assertIn("&lt;a&gt;", html)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,146 @@
+import os
+import tempfile
+import unittest
+
+from pyprc import hash, param
+
+H = hash(0x10)
+K = hash(0x11)
+S = hash(0x12)
+
+READONLY = "Cannot modify a read-only param"
+
+
+def tree():
+    rows = [param.struct([(K, param.u8(i)), (S, param.str("s%d" % i))]) for i in range(3)]
+    return param.struct([(H, param.list(rows)), (K, param.u8(1)), (S, param.str("a"))])
+
+
+class MutatorsTest(unittest.TestCase):
+    def assertMutatorsRaise(self, root):
+        before = root.clone()
+        value = root[K]
+        rows = root[H]
+        setters = [
+            ("set_bool", True),
+            ("set_i8", 1),
+            ("set_u8", 1),
+            ("set_i16", 1),
+            ("set_u16", 1),
+            ("set_i32", 1),
+            ("set_u32", 1),
+            ("set_float", 1.0),
+            ("set_str", "b"),
+            ("set_hash", H),
+            ("set_list", []),
+            ("set_struct", []),
+        ]
+        for name, arg in setters:
+            with self.subTest(name), self.assertRaisesRegex(TypeError, READONLY):
+                getattr(value, name)(arg)
+        other = tree()
+        other[K].value = 9
+        mutators = {
+            "value": lambda: setattr(value, "value", 2),
+            "convert": lambda: value.convert("u16"),
+            "setitem": lambda: root.__setitem__(K, param.u8(2)),
+            "setitem list": lambda: rows.__setitem__(0, param.struct([])),
+            "sort": lambda: rows.sort(K, reverse=True),
+            "sort_keys": lambda: root.sort_keys("hash"),
+            "replace_string": lambda: root.replace_string("a", "b"),
+            "patch": lambda: root.patch(root.diff(other)),
+            "apply_overlay": lambda: root.apply_overlay({"0x11": 3}),
+        }
+        for name, mutate in mutators.items():
+            with self.subTest(name), self.assertRaisesRegex(TypeError, READONLY):
+                mutate()
+        self.assertEqual(root, before)
+
+
+class ReadonlyTest(MutatorsTest):
+    def test_every_mutator_raises(self):
+        root = tree().freeze()
+        self.assertTrue(root.readonly)
+        self.assertTrue(root[H][0].readonly)
+        self.assertMutatorsRaise(root)
+
+    def test_reading_still_works(self):
+        root = tree().freeze()
+        self.assertEqual(root[H][2][S].value, "s2")
+        self.assertEqual(len(root[H]), 3)
+        self.assertEqual(root.strings()["a"], 1)
+        self.assertEqual(root.find(value="s1"), [(H, 1, S)])
+
+    def test_freeze_leaves_the_original_editable(self):
+        root = tree()
+        frozen = root.freeze()
+        self.assertFalse(root.readonly)
+        root[K].value = 5
+        self.assertEqual(frozen[K].value, 5)
+        self.assertMutatorsRaise(frozen.clone().freeze())
+
+    def test_clone_of_frozen_is_independent(self):
+        frozen = tree().freeze()
+        clone = frozen.clone()
+        self.assertFalse(clone.readonly)
+        clone[K].value = 5
+        self.assertEqual(frozen[K].value, 1)
+        self.assertEqual(clone[K].value, 5)
+
+    def test_frozen_params_are_copied_in(self):
+        frozen = tree().freeze()
+        lst = param.list([frozen[H][0]])
+        lst[0][K].value = 7
+        self.assertFalse(lst.readonly)
+        self.assertEqual(frozen[H][0][K].value, 0)
+
+    def test_freeze_in_place(self):
+        root = tree()
+        child = root[H][0]
+        frozen = root.freeze(in_place=True)
+        self.assertTrue(frozen.readonly)
+        self.assertTrue(root.readonly)
+        self.assertTrue(child.readonly)
+        self.assertMutatorsRaise(root)
+
+    def test_freeze_in_place_does_not_leak(self):
+        root = tree()
+        other = tree()
+        shared = param.list([root[H][0]])
+        root.freeze(in_place=True)
+        self.assertFalse(other.readonly)
+        other[K].value = 5
+        self.assertFalse(shared.readonly)
+        self.assertFalse(shared[0].readonly)
+        shared[0][K].value = 6
+        self.assertEqual(shared[0][K].value, 6)
+        self.assertFalse(root.clone().readonly)
+        # frozen params shared afterwards are copied
+        later = param.list([root[H][1]])
+        later[0][K].value = 8
+        self.assertEqual(root[H][1][K].value, 1)
+
+
+class ReadonlyFileTest(MutatorsTest):
+    def setUp(self):
+        fd, self.path = tempfile.mkstemp(suffix=".prc")
+        os.close(fd)
+        tree().save(self.path)
+
+    def tearDown(self):
+        os.remove(self.path)
+
+    def test_open_readonly(self):
+        root = param(self.path, readonly=True)
+        self.assertTrue(root.readonly)
+        self.assertMutatorsRaise(root)
+        self.assertFalse(param(self.path).readonly)
+
+    def test_save_readonly(self):
+        root = param(self.path, readonly=True)
+        root.save(self.path)
+        self.assertEqual(param(self.path), tree())
+
+
+if __name__ == "__main__":
+    unittest.main()
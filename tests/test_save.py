@@ -0,0 +1,97 @@
+import os
+import tempfile
+import unittest
+
+from pyprc import hash, param
+
+H = hash(0x10)
+K = hash(0x11)
+S = hash(0x12)
+
+
+def tree():
+    rows = [param.struct([(S, param.str(name)), (K, param.u8(i))]) for i, name in enumerate(["b", "a"])]
+    return param.struct([(S, param.str("c")), (H, param.list(rows)), (K, param.hash(0x99))])
+
+
+class SaveTest(unittest.TestCase):
+    def setUp(self):
+        fd, self.path = tempfile.mkstemp(suffix=".prc")
+        os.close(fd)
+
+    def tearDown(self):
+        os.remove(self.path)
+
+    def test_roundtrip(self):
+        root = tree()
+        root.save(self.path)
+        self.assertIsNone(root.roundtrip_check(self.path))
+        self.assertIsNone(param(self.path).roundtrip_check(self.path))
+
+    def test_first_difference(self):
+        root = tree()
+        root.save(self.path, hash_order="sorted")
+        self.assertIsNone(root.roundtrip_check(self.path, hash_order="sorted"))
+        offset, section, path = root.roundtrip_check(self.path)
+        self.assertGreaterEqual(offset, 0x10)
+        self.assertEqual(section, "hash table")
+        self.assertIsNotNone(path)
+
+    def test_end_of_file(self):
+        root = tree()
+        root.save(self.path)
+        with open(self.path, "ab") as f:
+            f.write(b"\0")
+        size = os.path.getsize(self.path)
+        self.assertEqual(root.roundtrip_check(self.path)[:2], (size - 1, "end of file"))
+
+    def test_hash_orders(self):
+        root = tree()
+        for order in ["traversal", "sorted", [hash(0x99), K]]:
+            root.save(self.path, hash_order=order)
+            self.assertIsNone(root.roundtrip_check(self.path, hash_order=order))
+            # fields are read back in hash table order, so only compare values
+            read = param(self.path)
+            self.assertEqual(read[H][1][S].value, "a")
+            self.assertEqual(read[K].value, hash(0x99))
+        table = param.hash_table(self.path)
+        self.assertEqual(table[:3], [hash(0), hash(0x99), K])
+
+    def test_sorted_hash_table(self):
+        tree().save(self.path, hash_order="sorted")
+        table = [h.value for h in param.hash_table(self.path)]
+        self.assertEqual(table, sorted(table))
+
+    def test_keep_hash_order(self):
+        root = tree()
+        root.save(self.path, hash_order=[hash(0x99), S])
+        order = param.hash_table(self.path)
+        self.assertIsNone(root.roundtrip_check(self.path, hash_order=order))
+
+    def test_string_orders(self):
+        root = tree()
+        for order in ["traversal", "last", "sorted"]:
+            root.save(self.path, string_order=order)
+            self.assertEqual(param(self.path), root)
+            self.assertIsNone(root.roundtrip_check(self.path, string_order=order))
+        root.save(self.path, string_order="sorted")
+        with open(self.path, "rb") as f:
+            data = f.read()
+        self.assertLess(data.index(b"a\0"), data.index(b"b\0"))
+        self.assertLess(data.index(b"b\0"), data.index(b"c\0"))
+
+    def test_bad_options(self):
+        with self.assertRaises(ValueError):
+            tree().save(self.path, hash_order="backwards")
+        with self.assertRaises(ValueError):
+            tree().save(self.path, string_order="backwards")
+
+    def test_not_a_param_file(self):
+        with open(self.path, "wb") as f:
+            f.write(b"not a param file")
+        with self.assertRaises(ValueError):
+            param.hash_table(self.path)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,60 @@
+import unittest
+
+from pyprc import LabelMap, hash, param
+
+K = hash(0x11)
+CUSTOM = hash(0x99)
+
+
+def custom_labels():
+    labels = LabelMap()
+    labels.add_custom_labels([(CUSTOM, "custom_name"), (K, "kind")])
+    return labels
+
+
+def table():
+    return param.list([
+        param.struct([(K, param.hash(hash(0x98)))]),
+        param.struct([(K, param.hash(CUSTOM))]),
+        param.struct([(K, param.str("custom_name"))]),
+    ])
+
+
+class FindByTest(unittest.TestCase):
+    def test_hash_value(self):
+        self.assertEqual(table().find_by(K, CUSTOM), 1)
+        self.assertIsNone(table().find_by(K, hash(0x97)))
+
+    def test_string_value(self):
+        self.assertEqual(table().find_by(K, param.str("custom_name")), 2)
+
+    def test_labels(self):
+        self.assertEqual(table().find_by(K, "custom_name"), 2)
+        self.assertEqual(table().find_by("kind", "custom_name", labels=custom_labels()), 1)
+
+    def test_not_a_list(self):
+        with self.assertRaises(TypeError):
+            param.u8(1).find_by(K, 1)
+
+
+class FindTest(unittest.TestCase):
+    def root(self):
+        return param.struct([(K, table()), (hash(0x12), param.float(2.0)), (CUSTOM, param.u8(2))])
+
+    def test_value(self):
+        self.assertEqual(self.root().find(2), [(hash(0x12),), (CUSTOM,)])
+        self.assertEqual(self.root().find(2, kind="u8"), [(CUSTOM,)])
+
+    def test_key(self):
+        self.assertEqual(len(self.root().find(key=K)), 3)
+
+    def test_labels(self):
+        self.assertEqual(self.root().find("custom_name"), [(K, 2, K)])
+        found = self.root().find("custom_name", labels=custom_labels())
+        self.assertEqual(found, [(K, 1, K), (K, 2, K)])
+        found = self.root().find(key="kind", labels=custom_labels())
+        self.assertEqual(found, self.root().find(key=K))
+
+
+if __name__ == "__main__":
+    unittest.main()
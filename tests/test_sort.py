@@ -0,0 +1,119 @@
+import unittest
+
+from pyprc import LabelMap, hash, param
+
+H = hash(0x10)
+K = hash(0x11)
+S = hash(0x12)
+
+
+def rows(*pairs):
+    return param.list([param.struct([(K, param.u8(k)), (S, param.str(s))]) for k, s in pairs])
+
+
+def column(lst, key):
+    return [row[key].value for row in lst]
+
+
+class SortListTest(unittest.TestCase):
+    def test_values(self):
+        lst = param.list([param.u8(3), param.u8(1), param.u8(2)])
+        lst.sort()
+        self.assertEqual([p.value for p in lst], [1, 2, 3])
+        lst.sort(reverse=True)
+        self.assertEqual([p.value for p in lst], [3, 2, 1])
+
+    def test_hashes_sort_by_value(self):
+        lst = param.list([param.hash(0x30), param.hash(0x10), param.hash(0x20)])
+        lst.sort()
+        self.assertEqual([p.value.value for p in lst], [0x10, 0x20, 0x30])
+
+    def test_field_path(self):
+        lst = rows((2, "b"), (1, "c"), (3, "a"))
+        lst.sort(K)
+        self.assertEqual(column(lst, S), ["c", "b", "a"])
+        lst.sort((S,))
+        self.assertEqual(column(lst, S), ["a", "b", "c"])
+
+    def test_nested_path(self):
+        lst = param.list([param.struct([(H, rows((k, "x")))]) for k in [2, 3, 1]])
+        lst.sort((H, 0, K))
+        self.assertEqual([row[H][0][K].value for row in lst], [1, 2, 3])
+
+    def test_callable(self):
+        lst = rows((1, "bb"), (2, "a"), (3, "ccc"))
+        lst.sort(key=lambda row: len(row[S].value))
+        self.assertEqual(column(lst, K), [2, 1, 3])
+        lst.sort(key=lambda row: row[K].value, reverse=True)
+        self.assertEqual(column(lst, K), [3, 2, 1])
+
+    def test_equal_keys_keep_their_order(self):
+        lst = rows((1, "a"), (0, "b"), (1, "c"), (0, "d"), (1, "e"))
+        lst.sort(K)
+        self.assertEqual(column(lst, S), ["b", "d", "a", "c", "e"])
+        lst.sort(K, reverse=True)
+        self.assertEqual(column(lst, S), ["a", "c", "e", "b", "d"])
+
+    def test_sort_is_one_change(self):
+        lst = rows((2, "b"), (1, "a"))
+        lst.track_history()
+        lst.sort(K)
+        self.assertTrue(lst.undo())
+        self.assertEqual(column(lst, S), ["b", "a"])
+        self.assertFalse(lst.undo())
+
+    def test_list_changed_during_sort(self):
+        root = param.struct([(H, rows((2, "b"), (1, "a")))])
+        lst, alias = root[H], root[H]
+
+        def key(row):
+            alias[0] = param.struct([(K, param.u8(0)), (S, param.str("z"))])
+            return row[K].value
+
+        with self.assertRaisesRegex(RuntimeError, "changed while it was being sorted"):
+            lst.sort(key=key)
+        self.assertEqual(column(lst, S), ["z", "a"])
+
+    def test_errors(self):
+        with self.assertRaises(TypeError):
+            param.struct([]).sort()
+        with self.assertRaises(TypeError):
+            rows((1, "a"), (2, "b")).sort()
+        with self.assertRaises(TypeError):
+            rows((1, "a"), (2, "b")).sort(key=lambda row: None if row[K].value == 1 else 0)
+        with self.assertRaises(KeyError):
+            rows((1, "a"), (2, "b")).sort(H)
+
+
+class SortKeysTest(unittest.TestCase):
+    def setUp(self):
+        self.labels = LabelMap()
+        self.labels.add_custom_labels([(H, "zeta"), (K, "alpha")])
+        self.root = param.struct([(K, param.u8(1)), (H, param.u8(0)), (S, param.u8(2))])
+
+    def keys(self):
+        return [h.value for h, _ in self.root]
+
+    def test_by_label(self):
+        self.root.sort_keys("label", labels=self.labels)
+        # unlabelled hashes are written as 0x..., which comes before any letter
+        self.assertEqual(self.keys(), [0x12, 0x11, 0x10])
+
+    def test_by_hash(self):
+        self.root.sort_keys("hash")
+        self.assertEqual(self.keys(), [0x10, 0x11, 0x12])
+
+    def test_duplicate_keys_keep_their_order(self):
+        root = param.struct([(K, param.u8(1)), (H, param.u8(0)), (K, param.u8(2))])
+        root.sort_keys("hash")
+        self.assertEqual([(h.value, p.value) for h, p in root], [(0x10, 0), (0x11, 1), (0x11, 2)])
+
+    def test_errors(self):
+        with self.assertRaises(ValueError):
+            self.root.sort_keys("value")
+        with self.assertRaises(TypeError):
+            param.list([]).sort_keys()
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,111 @@
+import os
+import tempfile
+import unittest
+
+from pyprc import hash, param
+
+H = hash(0x10)
+K = hash(0x11)
+S = hash(0x12)
+
+
+def tree():
+    rows = [param.struct([(S, param.str(s)), (K, param.hash(0x99))]) for s in ["b", "a", "b"]]
+    return param.struct([(S, param.str("c")), (H, param.list(rows)), (K, param.str("b"))])
+
+
+class StringsTest(unittest.TestCase):
+    def test_counts_in_order(self):
+        counts = tree().strings()
+        self.assertEqual(counts, {"c": 1, "b": 3, "a": 1})
+        self.assertEqual(list(counts), ["c", "b", "a"])
+
+    def test_below_a_param(self):
+        self.assertEqual(tree()[H].strings(), {"b": 2, "a": 1})
+        self.assertEqual(tree()[K].strings(), {"b": 1})
+        self.assertEqual(param.list([param.u8(1)]).strings(), {})
+
+    def test_hashes_are_not_strings(self):
+        self.assertNotIn("0x99", tree().strings())
+        self.assertEqual(len(tree().strings()), 3)
+
+    def test_shared_params_count_each_use(self):
+        row = param.struct([(S, param.str("x"))])
+        root = param.list([row, row])
+        self.assertEqual(root.strings(), {"x": 2})
+
+    def test_lazy(self):
+        fd, path = tempfile.mkstemp(suffix=".prc")
+        os.close(fd)
+        try:
+            tree().save(path)
+            self.assertEqual(param.open_lazy(path).strings(), tree().strings())
+        finally:
+            os.remove(path)
+
+
+class ReplaceStringTest(unittest.TestCase):
+    def test_replace(self):
+        root = tree()
+        self.assertEqual(root.replace_string("b", "d"), 3)
+        self.assertEqual(root.strings(), {"c": 1, "d": 3, "a": 1})
+        self.assertEqual(root[H][2][S].value, "d")
+        self.assertEqual(root.replace_string("missing", "d"), 0)
+
+    def test_only_below_the_param(self):
+        root = tree()
+        self.assertEqual(root[H].replace_string("b", "d"), 2)
+        self.assertEqual(root[K].value, "b")
+
+    def test_same_string(self):
+        root = tree()
+        root.track_history()
+        self.assertEqual(root.replace_string("b", "b"), 3)
+        self.assertFalse(root.undo())
+
+    def test_undone_together(self):
+        root = tree()
+        root.track_history()
+        root.replace_string("b", "d")
+        self.assertTrue(root.undo())
+        self.assertEqual(root, tree())
+
+    def test_readonly(self):
+        with self.assertRaises(TypeError):
+            tree().freeze().replace_string("b", "d")
+
+
+class InternTest(unittest.TestCase):
+    """Identical strings share storage, which must never make them change together"""
+
+    def test_editing_one_copy(self):
+        root = tree()
+        root[H][0][S].value = "e"
+        self.assertEqual(root[H][2][S].value, "b")
+        self.assertEqual(root[K].value, "b")
+        self.assertEqual(root.strings(), {"c": 1, "e": 1, "a": 1, "b": 2})
+
+    def test_across_trees(self):
+        a, b = tree(), tree()
+        a.replace_string("b", "d")
+        self.assertEqual(b.strings(), {"c": 1, "b": 3, "a": 1})
+
+    def test_copied_in(self):
+        root = tree()
+        copy = param.list([param.str("b")])
+        root[H][0][S] = copy[0]
+        copy[0].value = "f"
+        self.assertEqual(root[H][0][S].value, "b")
+        self.assertEqual(root.strings()["b"], 3)
+
+    def test_after_collection(self):
+        root = tree()
+        for i in range(2000):
+            root[H][1][S].value = "s%d" % (i % 10)
+        root[H][1][S].value = "b"
+        self.assertEqual(root.strings(), {"c": 1, "b": 4})
+        self.assertEqual(root[H][1][S].value, "b")
+
+
+if __name__ == "__main__":
+    unittest.main()
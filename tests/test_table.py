@@ -0,0 +1,80 @@
+import os
+import tempfile
+import unittest
+
+from pyprc import hash, param
+
+A = hash(0x10)
+B = hash(0x11)
+C = hash(0x12)
+
+
+def table():
+    return param.list([
+        param.struct([(A, param.float(1.5)), (B, param.u8(1)), (C, param.str("x"))]),
+        param.struct([(A, param.float(2.5)), (C, param.str("y"))]),
+    ])
+
+
+class TableTest(unittest.TestCase):
+    def test_round_trip(self):
+        original = table()
+        rows = original.to_table()
+        self.assertEqual(rows["0x0000000011"], [1, None])
+        self.assertEqual(param.from_table(rows, original), original)
+
+    def test_rows_of_dicts(self):
+        rows = [{"0x0000000010": 3.0, "0x0000000011": 4}, {"0x0000000010": 5.0}]
+        built = param.from_table(rows, table())
+        self.assertEqual(len(built[0]), 3)
+        self.assertEqual(built[0][B].value, 4)
+        self.assertEqual([h for h, _ in built[1]], [A, C])
+        self.assertEqual(built[1][C].value, "x")
+
+    def test_columns_not_given_are_copied(self):
+        built = param.from_table({"0x0000000011": [7]}, table())
+        self.assertEqual([p.value for _, p in built[0]], [1.5, 7, "x"])
+
+    def test_unknown_column(self):
+        with self.assertRaises(KeyError):
+            param.from_table({"0x0000000099": [1]}, table())
+
+    def test_invalid_cell_is_located(self):
+        with self.assertRaisesRegex(OverflowError, "at '1/0x0000000011'"):
+            param.from_table({"0x0000000011": [1, 300]}, table())
+        with self.assertRaisesRegex(TypeError, "at '0/0x0000000012'"):
+            param.from_table([{"0x0000000012": 5}], table())
+
+
+class CsvTest(unittest.TestCase):
+    def setUp(self):
+        fd, self.path = tempfile.mkstemp(suffix=".csv")
+        os.close(fd)
+
+    def tearDown(self):
+        os.remove(self.path)
+
+    def test_round_trip(self):
+        original = table()
+        original.to_csv(self.path)
+        self.assertEqual(param.from_csv(self.path, original), original)
+
+    def test_missing_float(self):
+        original = param.list([
+            param.struct([(A, param.float(1.5)), (B, param.u8(1))]),
+            param.struct([(B, param.u8(2))]),
+        ])
+        original.to_csv(self.path)
+        read = param.from_csv(self.path, original)
+        self.assertEqual([h for h, _ in read[1]], [B])
+        self.assertEqual(read, original)
+
+    def test_invalid_cell(self):
+        with open(self.path, "w") as f:
+            f.write("0x0000000010\nabc\n")
+        with self.assertRaises(ValueError):
+            param.from_csv(self.path, table())
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,96 @@
+import unittest
+
+from pyprc import hash, param
+
+H = hash(0x10)
+K = hash(0x11)
+N = hash(0x12)
+
+
+def tree():
+    return param.struct([(H, param.u8(1)), (K, param.list([param.u8(2), param.u8(3)]))])
+
+
+class TrackTest(unittest.TestCase):
+    def test_untracked(self):
+        root = tree()
+        self.assertFalse(root.is_dirty)
+        with self.assertRaises(ValueError):
+            root.changes()
+
+    def test_value_change(self):
+        root = tree()
+        root.track_changes()
+        self.assertFalse(root.is_dirty)
+        root[K][1].value = 9
+        self.assertTrue(root.is_dirty)
+        (path, (old, new)), = root.changes().items()
+        self.assertEqual(path, (K, 1))
+        self.assertEqual((old.value, new.value), (3, 9))
+
+    def test_added_and_removed(self):
+        root = tree()
+        root.track_changes()
+        root.set_struct([(K, root[K]), (N, param.bool(True))])
+        changes = root.changes()
+        self.assertEqual(set(changes), {(H,), (N,)})
+        old, new = changes[(H,)]
+        self.assertEqual((old.value, new), (1, None))
+        old, new = changes[(N,)]
+        self.assertEqual((old, new.value), (None, True))
+
+    def test_clean_after_undo(self):
+        root = tree()
+        root.track_history()
+        root.track_changes()
+        root[H].value = 5
+        root[K].set_list([param.u8(4)])
+        self.assertTrue(root.is_dirty)
+        root.undo()
+        root.undo()
+        self.assertFalse(root.is_dirty)
+        self.assertEqual(root.changes(), {})
+        root.redo()
+        self.assertTrue(root.is_dirty)
+
+    def test_clean_after_setting_back(self):
+        root = tree()
+        root.track_changes()
+        root[H].value = 5
+        root[H].value = 1
+        self.assertFalse(root.is_dirty)
+        self.assertEqual(root.changes(), {})
+
+    def test_rolled_back_changes(self):
+        root = tree()
+        root.track_changes()
+        with self.assertRaises(ValueError):
+            with root.transaction():
+                root[H].value = 5
+                raise ValueError()
+        self.assertFalse(root.is_dirty)
+
+    def test_paths_relative_to_tracked_param(self):
+        root = tree()
+        root[K].track_changes()
+        root[K][0].value = 7
+        self.assertEqual(list(root.changes()), [(0,)])
+
+    def test_shared_child(self):
+        child = param.list([param.u8(1)])
+        root = param.struct([(H, child)])
+        root.track_changes()
+        child[0].value = 2
+        self.assertEqual(list(root.changes()), [(H, 0)])
+
+    def test_track_again_clears(self):
+        root = tree()
+        root.track_changes()
+        root[H].value = 5
+        root.track_changes()
+        self.assertFalse(root.is_dirty)
+        self.assertEqual(root.changes(), {})
+
+
+if __name__ == "__main__":
+    unittest.main()